@@ -32,12 +32,7 @@ fn test_python_to_rust_integration() -> TestResult {
     // Step 1: Convert to Rust
     let rust_code = simulate_python_to_rust_conversion(python_code);
     if rust_code.is_none() {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Python to Rust conversion failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Python to Rust conversion failed");
     }
     
     let rust_code = rust_code.unwrap();
@@ -45,31 +40,16 @@ fn test_python_to_rust_integration() -> TestResult {
     // Step 2: Verify Rust code compiles
     let compile_result = simulate_rust_compilation(&rust_code);
     if !compile_result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Generated Rust code doesn't compile".to_string()),
-        };
+        return TestResult::fail(test_name, "Generated Rust code doesn't compile");
     }
     
     // Step 3: Test functionality
     let result = simulate_rust_function_execution(&rust_code, "fibonacci", vec![10]);
     if result != 55 {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some(format!("Function execution failed, expected 55, got {}", result)),
-        };
+        return TestResult::fail(test_name, format!("Function execution failed, expected 55, got {}", result));
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test JavaScript to C++ integration workflow
@@ -97,12 +77,7 @@ fn test_javascript_to_cpp_integration() -> TestResult {
     // Step 1: Convert to C++
     let cpp_code = simulate_javascript_to_cpp_conversion(js_code);
     if cpp_code.is_none() {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("JavaScript to C++ conversion failed".to_string()),
-        };
+        return TestResult::fail(test_name, "JavaScript to C++ conversion failed");
     }
     
     let cpp_code = cpp_code.unwrap();
@@ -110,20 +85,10 @@ fn test_javascript_to_cpp_integration() -> TestResult {
     // Step 2: Verify C++ code compiles
     let compile_result = simulate_cpp_compilation(&cpp_code);
     if !compile_result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Generated C++ code doesn't compile".to_string()),
-        };
+        return TestResult::fail(test_name, "Generated C++ code doesn't compile");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test NEXUS project initialization
@@ -137,31 +102,16 @@ fn test_nexus_project_initialization() -> TestResult {
     // Step 1: Initialize NEXUS in project
     let result = simulate_nexus_project_init(project_path, language);
     if !result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Project initialization failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Project initialization failed");
     }
     
     // Step 2: Verify project structure
     let structure_valid = simulate_project_structure_verification(project_path);
     if !structure_valid {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Project structure verification failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Project structure verification failed");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test existing project integration
@@ -175,42 +125,22 @@ fn test_existing_project_integration() -> TestResult {
     // Step 1: Create existing project structure
     let project_created = simulate_existing_project_creation(project_path, language);
     if !project_created {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Existing project creation failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Existing project creation failed");
     }
     
     // Step 2: Integrate NEXUS
     let integration_result = simulate_nexus_integration(project_path, language);
     if !integration_result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("NEXUS integration failed".to_string()),
-        };
+        return TestResult::fail(test_name, "NEXUS integration failed");
     }
     
     // Step 3: Verify integration
     let integration_verified = simulate_integration_verification(project_path);
     if !integration_verified {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Integration verification failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Integration verification failed");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test multi-language workflow
@@ -228,44 +158,24 @@ fn test_multi_language_workflow() -> TestResult {
     for (lang, code) in &workflow {
         let conversion_result = simulate_language_conversion(code, lang);
         if !conversion_result {
-            return TestResult {
-                test_name: test_name.to_string(),
-                passed: false,
-                duration_ms: 0,
-                error_message: Some(format!("Language conversion failed for {}", lang)),
-            };
+            return TestResult::fail(test_name, format!("Language conversion failed for {}", lang));
         }
     }
     
     // Step 2: Test cross-language function calls
     let cross_language_result = simulate_cross_language_function_calls(&workflow);
     if !cross_language_result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Cross-language function calls failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Cross-language function calls failed");
     }
     
     // Step 3: Test data consistency across languages
     let test_data = vec![1, 2, 3, 4, 5];
     let consistency_result = simulate_data_consistency_test(&workflow, &test_data);
     if !consistency_result {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Data consistency test failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Data consistency test failed");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 // Simulation functions (replace with actual implementations later)
@@ -29,35 +29,20 @@ fn test_compression_basics() -> TestResult {
     let actual_compression = compressed.len() as f64 / input.len() as f64;
     
     if actual_compression > expected_compression {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some(format!(
-                "Compression ratio {} exceeds expected {}",
-                actual_compression,
-                expected_compression
-            )),
-        };
+        return TestResult::fail(test_name, format!(
+            "Compression ratio {} exceeds expected {}",
+            actual_compression,
+            expected_compression
+        ));
     }
     
     // Test 2: Decompression accuracy
     let decompressed = simulate_decompression(&compressed);
     if decompressed != input {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Decompression failed to restore original input".to_string()),
-        };
+        return TestResult::fail(test_name, "Decompression failed to restore original input");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test basic bridge functionality
@@ -70,12 +55,7 @@ fn test_bridge_basics() -> TestResult {
     for lang in languages {
         let bridge = simulate_bridge_creation(lang);
         if bridge.is_none() {
-            return TestResult {
-                test_name: test_name.to_string(),
-                passed: false,
-                duration_ms: 0,
-                error_message: Some(format!("Failed to create bridge for {}", lang)),
-            };
+            return TestResult::fail(test_name, format!("Failed to create bridge for {}", lang));
         }
     }
     
@@ -85,20 +65,10 @@ fn test_bridge_basics() -> TestResult {
     
     let mapping = simulate_function_mapping(python_func, "rust");
     if mapping != rust_func {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some(format!("Function mapping failed. Expected: '{}', Got: '{}'", rust_func, mapping)),
-        };
+        return TestResult::fail(test_name, format!("Function mapping failed. Expected: '{}', Got: '{}'", rust_func, mapping));
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test utility functions
@@ -109,44 +79,24 @@ fn test_utility_functions() -> TestResult {
     let input = "  hello world  ";
     let trimmed = input.trim();
     if trimmed != "hello world" {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("String trimming failed".to_string()),
-        };
+        return TestResult::fail(test_name, "String trimming failed");
     }
     
     // Test 2: Number utilities
     let numbers = vec![1, 2, 3, 4, 5];
     let sum: i32 = numbers.iter().sum();
     if sum != 15 {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Number summation failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Number summation failed");
     }
     
     // Test 3: Collection utilities
     let mut sorted = numbers.clone();
     sorted.sort();
     if sorted != vec![1, 2, 3, 4, 5] {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Collection sorting failed".to_string()),
-        };
+        return TestResult::fail(test_name, "Collection sorting failed");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 /// Test error handling
@@ -156,42 +106,22 @@ fn test_error_handling() -> TestResult {
     // Test 1: Invalid input handling
     let result = simulate_compression_with_error("");
     if result.is_ok() {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Should have returned error for empty input".to_string()),
-        };
+        return TestResult::fail(test_name, "Should have returned error for empty input");
     }
     
     // Test 2: Invalid language handling
     let result = simulate_bridge_creation("invalid_language");
     if result.is_some() {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Should have returned None for invalid language".to_string()),
-        };
+        return TestResult::fail(test_name, "Should have returned None for invalid language");
     }
     
     // Test 3: Type conversion error handling
     let result = simulate_type_conversion("invalid_type", "rust");
     if result.is_ok() {
-        return TestResult {
-            test_name: test_name.to_string(),
-            passed: false,
-            duration_ms: 0,
-            error_message: Some("Should have returned error for invalid type".to_string()),
-        };
+        return TestResult::fail(test_name, "Should have returned error for invalid type");
     }
     
-    TestResult {
-        test_name: test_name.to_string(),
-        passed: true,
-        duration_ms: 0,
-        error_message: None,
-    }
+    TestResult::pass(test_name)
 }
 
 // Simulation functions (replace with actual implementations later)
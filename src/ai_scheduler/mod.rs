@@ -1,20 +1,100 @@
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::error::Error;
 use std::fmt;
+use serde::{Serialize, Deserialize};
 
 /// Represents an AI/ML process with specific resource requirements
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AIProcess {
     pub pid: u32,
     pub priority: u32,
     pub gpu_requirements: Vec<u32>,
     pub memory_requirements: u64,
     pub estimated_runtime: Duration,
+    #[serde(with = "instant_age")]
     pub created_at: Instant,
     pub model_type: String,
     pub batch_size: u32,
+    /// Time by which the process must have finished running, if any
+    #[serde(default, with = "deadline_offset")]
+    pub deadline: Option<Instant>,
+}
+
+/// Serde adapter storing an `Instant` as its age, since `Instant` has no stable epoch
+mod instant_age {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant};
+
+    pub fn serialize<S: Serializer>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error> {
+        instant.elapsed().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Instant, D::Error> {
+        let age = Duration::deserialize(deserializer)?;
+        Ok(Instant::now().checked_sub(age).unwrap_or_else(Instant::now))
+    }
+}
+
+/// Serde adapter storing an optional deadline as signed seconds from now (negative once passed)
+mod deadline_offset {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant};
+
+    pub fn serialize<S: Serializer>(deadline: &Option<Instant>, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = Instant::now();
+        deadline.map(|deadline| match deadline.checked_duration_since(now) {
+            Some(remaining) => remaining.as_secs_f64(),
+            None => -now.duration_since(deadline).as_secs_f64(),
+        }).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Instant>, D::Error> {
+        let now = Instant::now();
+        Ok(Option::<f64>::deserialize(deserializer)?.map(|seconds| {
+            let offset = Duration::try_from_secs_f64(seconds.abs()).unwrap_or(Duration::MAX);
+            if seconds >= 0.0 {
+                now.checked_add(offset).unwrap_or(now)
+            } else {
+                now.checked_sub(offset).unwrap_or(now)
+            }
+        }))
+    }
+}
+
+impl AIProcess {
+    /// Check whether the process can still finish by its deadline if it starts at `now`
+    pub fn can_meet_deadline(&self, now: Instant) -> bool {
+        self.deadline.is_none_or(|deadline| {
+            now.checked_add(self.estimated_runtime).is_some_and(|end| end <= deadline)
+        })
+    }
+}
+
+/// Order in which `AIScheduler::schedule` considers ready processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SchedulingPolicy {
+    /// Highest priority first, shorter runtime breaking ties (the `Ord` on `AIProcess`)
+    #[default]
+    Priority,
+    /// Earliest deadline first; processes without a deadline follow in priority order
+    EarliestDeadlineFirst,
+}
+
+impl SchedulingPolicy {
+    /// Sort processes into the order they should be offered resources
+    fn order(self, processes: &mut [AIProcess]) {
+        match self {
+            SchedulingPolicy::Priority => processes.sort_by(|a, b| b.cmp(a)),
+            SchedulingPolicy::EarliestDeadlineFirst => processes.sort_by(|a, b| match (a.deadline, b.deadline) {
+                (Some(a_deadline), Some(b_deadline)) => a_deadline.cmp(&b_deadline).then_with(|| b.cmp(a)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => b.cmp(a),
+            }),
+        }
+    }
 }
 
 /// Custom ordering for AIProcess based on priority and resource efficiency
@@ -82,30 +162,38 @@ impl GPUMemoryManager {
     }
     
     pub fn can_allocate_gpu(&self, gpu_id: u32, memory_needed: u64) -> bool {
-        if let Some(gpu) = self.gpus.get(gpu_id as usize) {
-            gpu.memory_allocated + memory_needed <= self.total_gpu_memory / self.gpus.len() as u64
+        self.check_gpu(gpu_id, memory_needed).is_ok()
+    }
+    
+    /// Check whether a GPU can take `memory_needed`, reporting the shortfall if not
+    pub fn check_gpu(&self, gpu_id: u32, memory_needed: u64) -> Result<(), SchedulerError> {
+        let gpu = self.gpus.get(gpu_id as usize).ok_or(SchedulerError::InvalidGPUId)?;
+        let available = (self.total_gpu_memory / self.gpus.len() as u64).saturating_sub(gpu.memory_allocated);
+        
+        if memory_needed <= available {
+            Ok(())
         } else {
-            false
+            Err(SchedulerError::InsufficientGPUResources {
+                gpu_id,
+                requested: memory_needed,
+                available,
+            })
         }
     }
     
+    /// Allocated share of all GPU memory, in percent
+    pub fn utilization_percent(&self) -> f32 {
+        let allocated: u64 = self.gpus.iter().map(|gpu| gpu.memory_allocated).sum();
+        percent(allocated, self.total_gpu_memory)
+    }
+    
     pub fn allocate_gpu(&mut self, gpu_id: u32, memory_needed: u64, process_id: u32) -> Result<(), SchedulerError> {
-        // Calculate available memory before getting mutable borrow
-        let available_memory = self.total_gpu_memory / self.gpus.len() as u64;
+        self.check_gpu(gpu_id, memory_needed)?;
         
-        if let Some(gpu) = self.gpus.get_mut(gpu_id as usize) {
-            let can_allocate = gpu.memory_allocated + memory_needed <= available_memory;
-            
-            if can_allocate {
-                gpu.memory_allocated += memory_needed;
-                gpu.process_id = Some(process_id);
-                Ok(())
-            } else {
-                Err(SchedulerError::InsufficientGPUResources)
-            }
-        } else {
-            Err(SchedulerError::InvalidGPUId)
-        }
+        let gpu = &mut self.gpus[gpu_id as usize];
+        gpu.memory_allocated += memory_needed;
+        gpu.process_id = Some(process_id);
+        Ok(())
     }
     
     pub fn free_gpu(&mut self, gpu_id: u32, memory_freed: u64) -> Result<(), SchedulerError> {
@@ -159,6 +247,11 @@ impl MemoryManager {
         self.allocated_memory + size <= self.total_memory
     }
     
+    /// Allocated share of system memory, in percent
+    pub fn utilization_percent(&self) -> f32 {
+        percent(self.allocated_memory, self.total_memory)
+    }
+    
     pub fn allocate(&mut self, size: u64, process_id: u32) -> Result<u64, SchedulerError> {
         if !self.can_allocate(size) {
             return Err(SchedulerError::InsufficientMemory);
@@ -195,33 +288,57 @@ impl MemoryManager {
 }
 
 /// Custom error types for the scheduler
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SchedulerError {
-    InsufficientGPUResources,
+    InsufficientGPUResources {
+        gpu_id: u32,
+        requested: u64,
+        available: u64,
+    },
     InsufficientMemory,
     InvalidGPUId,
     InvalidMemoryFree,
     ProcessNotFound,
     ResourceAllocationFailed,
     InvalidProcessId,
+    DeadlineMissed {
+        pid: u32,
+    },
 }
 
 impl fmt::Display for SchedulerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SchedulerError::InsufficientGPUResources => write!(f, "Insufficient GPU resources"),
+            SchedulerError::InsufficientGPUResources { gpu_id, requested, available } => write!(
+                f,
+                "Insufficient GPU resources on GPU {}: requested {} bytes, {} available",
+                gpu_id, requested, available
+            ),
             SchedulerError::InsufficientMemory => write!(f, "Insufficient system memory"),
             SchedulerError::InvalidGPUId => write!(f, "Invalid GPU ID"),
             SchedulerError::InvalidMemoryFree => write!(f, "Invalid memory free operation"),
             SchedulerError::ProcessNotFound => write!(f, "Process not found"),
             SchedulerError::ResourceAllocationFailed => write!(f, "Resource allocation failed"),
             SchedulerError::InvalidProcessId => write!(f, "Invalid process ID"),
+            SchedulerError::DeadlineMissed { pid } => write!(f, "Process {} cannot finish before its deadline", pid),
         }
     }
 }
 
 impl Error for SchedulerError {}
 
+/// `part` as a percentage of `total`, 0 when there is nothing to use
+fn percent(part: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        (part as f64 / total as f64 * 100.0) as f32
+    }
+}
+
+/// Number of utilization samples kept by `SchedulerStats`
+pub const UTILIZATION_HISTORY_LEN: usize = 256;
+
 /// Main AI process scheduler
 pub struct AIScheduler {
     processes: Arc<Mutex<BinaryHeap<AIProcess>>>,
@@ -229,6 +346,7 @@ pub struct AIScheduler {
     gpu_manager: Arc<Mutex<GPUMemoryManager>>,
     memory_manager: Arc<Mutex<MemoryManager>>,
     stats: Arc<Mutex<SchedulerStats>>,
+    policy: SchedulingPolicy,
 }
 
 /// Scheduler performance statistics
@@ -239,6 +357,43 @@ pub struct SchedulerStats {
     pub total_memory_utilization: f32,
     pub average_scheduling_time: Duration,
     pub failed_allocations: u64,
+    /// Why the most recent allocation failed, including any GPU shortfall
+    pub last_allocation_error: Option<SchedulerError>,
+    /// Processes dropped because they could no longer finish by their deadline
+    pub deadline_misses: u64,
+    /// PIDs dropped for missing their deadline in the most recent `schedule` call, in the order considered
+    pub missed_deadlines: Vec<u32>,
+    /// `(time, gpu%, mem%)` after each `schedule` call, oldest first, at most `UTILIZATION_HISTORY_LEN`
+    utilization_samples: VecDeque<(Instant, f32, f32)>,
+}
+
+impl SchedulerStats {
+    /// Record current utilization, dropping the oldest sample once the buffer is full
+    fn record_utilization(&mut self, gpu_percent: f32, memory_percent: f32) {
+        if self.utilization_samples.len() == UTILIZATION_HISTORY_LEN {
+            self.utilization_samples.pop_front();
+        }
+        self.utilization_samples.push_back((Instant::now(), gpu_percent, memory_percent));
+        self.total_gpu_utilization = gpu_percent;
+        self.total_memory_utilization = memory_percent;
+    }
+    
+    /// Utilization time series as `(time, gpu%, mem%)`, oldest first
+    pub fn utilization_history(&self) -> Vec<(Instant, f32, f32)> {
+        self.utilization_samples.iter().copied().collect()
+    }
+}
+
+/// Serializable scheduler state used to warm-start after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    pub gpu_count: u32,
+    pub gpu_memory: u64,
+    pub system_memory: u64,
+    pub pending: Vec<AIProcess>,
+    pub running: Vec<AIProcess>,
+    #[serde(default)]
+    pub policy: SchedulingPolicy,
 }
 
 impl AIScheduler {
@@ -249,9 +404,16 @@ impl AIScheduler {
             gpu_manager: Arc::new(Mutex::new(GPUMemoryManager::new(gpu_count, gpu_memory))),
             memory_manager: Arc::new(Mutex::new(MemoryManager::new(system_memory))),
             stats: Arc::new(Mutex::new(SchedulerStats::default())),
+            policy: SchedulingPolicy::default(),
         }
     }
     
+    /// Use `policy` to order ready processes
+    pub fn with_policy(mut self, policy: SchedulingPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    
     /// Add a new AI process to the scheduling queue
     pub fn add_process(&self, process: AIProcess) -> Result<(), SchedulerError> {
         let mut processes = self.processes.lock().unwrap();
@@ -260,6 +422,11 @@ impl AIScheduler {
     }
     
     /// Schedule processes based on available resources
+    ///
+    /// Ready processes are offered resources in the scheduler's policy order.
+    /// A process that can no longer finish by its deadline is removed from the
+    /// queue and recorded as `SchedulerError::DeadlineMissed`; others that do
+    /// not fit stay queued for the next call.
     pub fn schedule(&self) -> Result<Vec<AIProcess>, SchedulerError> {
         let start_time = Instant::now();
         let mut processes = self.processes.lock().unwrap();
@@ -271,34 +438,28 @@ impl AIScheduler {
         let mut scheduled = Vec::new();
         let mut failed = Vec::new();
         
-        while let Some(process) = processes.pop() {
-            if self.can_allocate_resources(&process, &gpu_manager, &memory_manager) {
-                // Allocate GPU resources
-                for &gpu_id in &process.gpu_requirements {
-                    if let Err(_e) = gpu_manager.allocate_gpu(gpu_id, process.memory_requirements, process.pid) {
-                        failed.push(process.clone());
-                        stats.failed_allocations += 1;
-                        continue;
-                    }
-                }
-                
-                // Allocate system memory
-                if let Err(_e) = memory_manager.allocate(process.memory_requirements, process.pid) {
-                    // Rollback GPU allocation
-                    for &gpu_id in &process.gpu_requirements {
-                        let _ = gpu_manager.free_gpu(gpu_id, process.memory_requirements);
-                    }
-                    failed.push(process.clone());
-                    stats.failed_allocations += 1;
-                    continue;
-                }
-                
+        let mut ready: Vec<AIProcess> = processes.drain().collect();
+        self.policy.order(&mut ready);
+        stats.missed_deadlines.clear();
+        
+        for process in ready {
+            if !process.can_meet_deadline(Instant::now()) {
+                stats.deadline_misses += 1;
+                stats.missed_deadlines.push(process.pid);
+                stats.last_allocation_error = Some(SchedulerError::DeadlineMissed { pid: process.pid });
+            } else if let Err(e) = self.can_allocate_resources(&process, &gpu_manager, &memory_manager) {
+                failed.push(process);
+                stats.failed_allocations += 1;
+                stats.last_allocation_error = Some(e);
+            } else if let Err(e) = Self::allocate_process(&process, &mut gpu_manager, &mut memory_manager) {
+                // Partial GPU and memory allocations have already been rolled back
+                failed.push(process);
+                stats.failed_allocations += 1;
+                stats.last_allocation_error = Some(e);
+            } else {
                 scheduled.push(process.clone());
                 running.push(process);
                 stats.total_processes_scheduled += 1;
-            } else {
-                failed.push(process);
-                stats.failed_allocations += 1;
             }
         }
         
@@ -309,30 +470,113 @@ impl AIScheduler {
         
         // Update statistics
         stats.average_scheduling_time = start_time.elapsed();
+        stats.record_utilization(gpu_manager.utilization_percent(), memory_manager.utilization_percent());
         
         Ok(scheduled)
     }
     
+    /// Capture the pending queue, running list and resource sizes
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let processes = self.processes.lock().unwrap();
+        let running = self.running_processes.lock().unwrap();
+        let gpu_manager = self.gpu_manager.lock().unwrap();
+        let memory_manager = self.memory_manager.lock().unwrap();
+        
+        let gpu_count = gpu_manager.gpus.len() as u32;
+        let gpu_memory = if gpu_count > 0 {
+            gpu_manager.total_gpu_memory / gpu_count as u64
+        } else {
+            0
+        };
+        
+        SchedulerSnapshot {
+            gpu_count,
+            gpu_memory,
+            system_memory: memory_manager.total_memory,
+            pending: processes.iter().cloned().collect(),
+            running: running.clone(),
+            policy: self.policy,
+        }
+    }
+    
+    /// Rebuild a scheduler from a snapshot, re-applying allocations for running processes
+    ///
+    /// Each running process is allocated exactly once. A process whose resources
+    /// can no longer be granted is returned to the pending queue instead of being lost.
+    pub fn restore(snapshot: SchedulerSnapshot) -> Self {
+        let scheduler = Self::new(snapshot.gpu_count, snapshot.gpu_memory, snapshot.system_memory)
+            .with_policy(snapshot.policy);
+        
+        {
+            let mut processes = scheduler.processes.lock().unwrap();
+            let mut gpu_manager = scheduler.gpu_manager.lock().unwrap();
+            let mut memory_manager = scheduler.memory_manager.lock().unwrap();
+            let mut running = scheduler.running_processes.lock().unwrap();
+            let mut stats = scheduler.stats.lock().unwrap();
+            
+            for process in snapshot.pending {
+                processes.push(process);
+            }
+            
+            for process in snapshot.running {
+                match Self::allocate_process(&process, &mut gpu_manager, &mut memory_manager) {
+                    Ok(()) => running.push(process),
+                    Err(_) => {
+                        stats.failed_allocations += 1;
+                        processes.push(process);
+                    }
+                }
+            }
+        }
+        
+        scheduler
+    }
+    
+    /// Allocate GPU and system memory for a process, undoing partial work on failure
+    fn allocate_process(
+        process: &AIProcess,
+        gpu_manager: &mut GPUMemoryManager,
+        memory_manager: &mut MemoryManager,
+    ) -> Result<(), SchedulerError> {
+        let mut allocated_gpus = Vec::new();
+        for &gpu_id in &process.gpu_requirements {
+            if let Err(e) = gpu_manager.allocate_gpu(gpu_id, process.memory_requirements, process.pid) {
+                for &done in &allocated_gpus {
+                    let _ = gpu_manager.free_gpu(done, process.memory_requirements);
+                }
+                return Err(e);
+            }
+            allocated_gpus.push(gpu_id);
+        }
+        
+        if let Err(e) = memory_manager.allocate(process.memory_requirements, process.pid) {
+            for &done in &allocated_gpus {
+                let _ = gpu_manager.free_gpu(done, process.memory_requirements);
+            }
+            return Err(e);
+        }
+        
+        Ok(())
+    }
+    
     /// Check if resources can be allocated for a process
     fn can_allocate_resources(
         &self,
         process: &AIProcess,
         gpu_manager: &GPUMemoryManager,
         memory_manager: &MemoryManager,
-    ) -> bool {
+    ) -> Result<(), SchedulerError> {
         // Check GPU availability
         for &gpu_id in &process.gpu_requirements {
-            if !gpu_manager.can_allocate_gpu(gpu_id, process.memory_requirements) {
-                return false;
-            }
+            gpu_manager.check_gpu(gpu_id, process.memory_requirements)?;
         }
         
         // Check system memory
         if !memory_manager.can_allocate(process.memory_requirements) {
-            return false;
+            return Err(SchedulerError::InsufficientMemory);
         }
         
-        true
+        Ok(())
     }
     
     /// Get current scheduler statistics
@@ -340,6 +584,11 @@ impl AIScheduler {
         self.stats.lock().unwrap().clone()
     }
     
+    /// Utilization sampled at each `schedule` call as `(time, gpu%, mem%)`, oldest first
+    pub fn utilization_history(&self) -> Vec<(Instant, f32, f32)> {
+        self.stats.lock().unwrap().utilization_history()
+    }
+    
     /// Get GPU utilization information
     pub fn get_gpu_utilization(&self) -> Vec<GPUAllocation> {
         self.gpu_manager.lock().unwrap().gpus.clone()
@@ -369,6 +618,7 @@ mod tests {
             created_at: Instant::now(),
             model_type: "test".to_string(),
             batch_size: 32,
+            deadline: None,
         };
         
         let high_priority = AIProcess {
@@ -380,6 +630,7 @@ mod tests {
             created_at: Instant::now(),
             model_type: "test".to_string(),
             batch_size: 32,
+            deadline: None,
         };
         
         processes.push(low_priority);
@@ -399,12 +650,180 @@ mod tests {
         assert!(gpu_manager.allocate_gpu(0, 1024, 1).is_ok());
         
         // Try to allocate more than available
-        assert!(gpu_manager.allocate_gpu(0, 8000, 2).is_err());
+        assert_eq!(
+            gpu_manager.allocate_gpu(0, 8000, 2),
+            Err(SchedulerError::InsufficientGPUResources { gpu_id: 0, requested: 8000, available: 7168 })
+        );
         
         // Free memory
         assert!(gpu_manager.free_gpu(0, 1024).is_ok());
     }
     
+    #[test]
+    fn test_schedule_records_gpu_shortfall() {
+        let scheduler = AIScheduler::new(2, 4096, 65536);
+        scheduler.add_process(AIProcess {
+            pid: 7,
+            priority: 1,
+            gpu_requirements: vec![1],
+            memory_requirements: 6000,
+            estimated_runtime: Duration::from_secs(10),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 8,
+            deadline: None,
+        }).unwrap();
+        
+        assert!(scheduler.schedule().unwrap().is_empty());
+        assert_eq!(
+            scheduler.get_stats().last_allocation_error,
+            Some(SchedulerError::InsufficientGPUResources { gpu_id: 1, requested: 6000, available: 4096 })
+        );
+    }
+    
+    #[test]
+    fn test_utilization_history() {
+        let scheduler = AIScheduler::new(2, 4096, 8192);
+        assert!(scheduler.utilization_history().is_empty());
+        
+        scheduler.add_process(AIProcess {
+            pid: 1,
+            priority: 1,
+            gpu_requirements: vec![0],
+            memory_requirements: 2048,
+            estimated_runtime: Duration::from_secs(1),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 1,
+            deadline: None,
+        }).unwrap();
+        scheduler.schedule().unwrap();
+        scheduler.schedule().unwrap();
+        
+        let history = scheduler.utilization_history();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].0 <= history[1].0);
+        assert_eq!((history[1].1, history[1].2), (25.0, 25.0));
+        assert_eq!(scheduler.get_stats().total_gpu_utilization, 25.0);
+        
+        for _ in 0..UTILIZATION_HISTORY_LEN {
+            scheduler.schedule().unwrap();
+        }
+        assert_eq!(scheduler.utilization_history().len(), UTILIZATION_HISTORY_LEN);
+    }
+    
+    #[test]
+    fn test_schedule_rolls_back_partial_gpu_allocation() {
+        let scheduler = AIScheduler::new(2, 4096, 65536);
+        // Each GPU passes the pre-check alone, but the second allocation on GPU 0 overflows it
+        scheduler.add_process(AIProcess {
+            pid: 3,
+            priority: 1,
+            gpu_requirements: vec![1, 0, 0],
+            memory_requirements: 3000,
+            estimated_runtime: Duration::from_secs(10),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 8,
+            deadline: None,
+        }).unwrap();
+        
+        assert!(scheduler.schedule().unwrap().is_empty());
+        assert!(scheduler.get_gpu_utilization().iter().all(|gpu| gpu.memory_allocated == 0));
+        assert_eq!(scheduler.get_memory_utilization().0, 0);
+        assert_eq!(scheduler.get_stats().failed_allocations, 1);
+    }
+    
+    #[test]
+    fn test_earliest_deadline_first() {
+        let process = |pid, priority, deadline| AIProcess {
+            pid,
+            priority,
+            gpu_requirements: vec![],
+            memory_requirements: 1024,
+            estimated_runtime: Duration::from_secs(1),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 1,
+            deadline,
+        };
+        let now = Instant::now();
+        // Room for only one process at a time
+        let scheduler = AIScheduler::new(0, 0, 1024).with_policy(SchedulingPolicy::EarliestDeadlineFirst);
+        scheduler.add_process(process(1, 10, None)).unwrap();
+        scheduler.add_process(process(2, 1, Some(now + Duration::from_secs(60)))).unwrap();
+        scheduler.add_process(process(3, 5, Some(now + Duration::from_secs(30)))).unwrap();
+        scheduler.add_process(process(4, 99, Some(now))).unwrap();
+        
+        let scheduled: Vec<u32> = scheduler.schedule().unwrap().iter().map(|process| process.pid).collect();
+        assert_eq!(scheduled, vec![3]);
+        let stats = scheduler.get_stats();
+        assert_eq!(stats.deadline_misses, 1);
+        assert_eq!(stats.last_allocation_error, Some(SchedulerError::InsufficientMemory));
+        assert_eq!(scheduler.processes.lock().unwrap().len(), 2);
+        
+        // Under plain priority ordering the deadline-free, high-priority job wins
+        let scheduler = AIScheduler::new(0, 0, 1024);
+        scheduler.add_process(process(1, 10, None)).unwrap();
+        scheduler.add_process(process(3, 5, Some(now + Duration::from_secs(30)))).unwrap();
+        let scheduled: Vec<u32> = scheduler.schedule().unwrap().iter().map(|process| process.pid).collect();
+        assert_eq!(scheduled, vec![1]);
+        
+        // A runtime too long to add to `now` misses the deadline instead of panicking
+        let endless = AIProcess { estimated_runtime: Duration::MAX, ..process(5, 1, Some(now + Duration::from_secs(60))) };
+        assert!(!endless.can_meet_deadline(now));
+        
+        // Every process that misses in one call is reported, not just the last
+        let scheduler = AIScheduler::new(0, 0, 4096).with_policy(SchedulingPolicy::EarliestDeadlineFirst);
+        scheduler.add_process(process(6, 1, Some(now))).unwrap();
+        scheduler.add_process(process(7, 1, Some(now + Duration::from_millis(1)))).unwrap();
+        scheduler.add_process(process(8, 1, None)).unwrap();
+        let scheduled: Vec<u32> = scheduler.schedule().unwrap().iter().map(|process| process.pid).collect();
+        assert_eq!(scheduled, vec![8]);
+        let stats = scheduler.get_stats();
+        assert_eq!(stats.missed_deadlines, vec![6, 7]);
+        assert_eq!(stats.deadline_misses, 2);
+        scheduler.schedule().unwrap();
+        assert!(scheduler.get_stats().missed_deadlines.is_empty());
+    }
+    
+    #[test]
+    fn test_snapshot_restore() {
+        let scheduler = AIScheduler::new(2, 8192, 16384);
+        scheduler.add_process(AIProcess {
+            pid: 1,
+            priority: 5,
+            gpu_requirements: vec![0],
+            memory_requirements: 1024,
+            estimated_runtime: Duration::from_secs(10),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 32,
+            deadline: None,
+        }).unwrap();
+        scheduler.schedule().unwrap();
+        scheduler.add_process(AIProcess {
+            pid: 2,
+            priority: 1,
+            gpu_requirements: vec![1],
+            memory_requirements: 2048,
+            estimated_runtime: Duration::from_secs(5),
+            created_at: Instant::now(),
+            model_type: "test".to_string(),
+            batch_size: 16,
+            deadline: None,
+        }).unwrap();
+        
+        let json = serde_json::to_string(&scheduler.snapshot()).unwrap();
+        let snapshot: SchedulerSnapshot = serde_json::from_str(&json).unwrap();
+        let restored = AIScheduler::restore(snapshot);
+        
+        assert_eq!(restored.get_memory_utilization(), (1024, 16384));
+        assert_eq!(restored.get_gpu_utilization()[0].process_id, Some(1));
+        assert_eq!(restored.running_processes.lock().unwrap().len(), 1);
+        assert_eq!(restored.processes.lock().unwrap().len(), 1);
+    }
+    
     #[test]
     fn test_scheduler_creation() {
         let scheduler = AIScheduler::new(4, 8192, 16384);
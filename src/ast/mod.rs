@@ -3,6 +3,7 @@
 //! This module provides the core data structures for representing code in a universal format.
 //! The AST is designed to be language-agnostic and support efficient compression and conversion.
 
+use crate::parser::ParseError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -59,8 +60,8 @@ pub enum NodeType {
     Error,
 }
 
-/// Represents a location in source code
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Represents a location in source code, optionally spanning to an end position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Location {
     /// Line number (1-indexed)
     pub line: usize,
@@ -68,6 +69,37 @@ pub struct Location {
     pub column: usize,
     /// File path or identifier
     pub file: Option<String>,
+    /// Line of the end position, when the location is a span
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// Column just past the last character of the span (exclusive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+}
+
+impl Location {
+    /// A single point with no file and no end
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column, file: None, end_line: None, end_column: None }
+    }
+    
+    /// Extend this location into a span ending at `end_line`:`end_column` (exclusive)
+    pub fn with_end(mut self, end_line: usize, end_column: usize) -> Self {
+        self.end_line = Some(end_line);
+        self.end_column = Some(end_column);
+        self
+    }
+    
+    /// End position of the span, if one was recorded
+    pub fn end(&self) -> Option<(usize, usize)> {
+        Some((self.end_line?, self.end_column?))
+    }
+    
+    /// Whether `line`:`column` falls inside the span (a point location contains only itself)
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        let (end_line, end_column) = self.end().unwrap_or((self.line, self.column + 1));
+        (line, column) >= (self.line, self.column) && (line, column) < (end_line, end_column)
+    }
 }
 
 /// Represents a universal type
@@ -117,6 +149,60 @@ impl Node {
         }
     }
     
+    /// Create an error recovery node carrying the diagnostic and the skipped source text
+    ///
+    /// The span, normally built with `Location::with_end`, is stored as the node
+    /// location; the message is kept in metadata so the node serializes like any other.
+    pub fn error(message: String, span: Location, recovered_text: String) -> Self {
+        let mut node = Self::new(NodeType::Error, recovered_text);
+        node.add_metadata("error".to_string(), message);
+        node.set_location(span);
+        node
+    }
+    
+    /// Create an error node for a recovered `ParseError`, keeping its severity and kind
+    ///
+    /// Both are stored in metadata by their variant names (`"Warning"`,
+    /// `"UnexpectedToken"`, ...) so tools can tell a recovered warning from a hard error.
+    pub fn from_parse_error(error: ParseError, span: Location, recovered_text: String) -> Self {
+        let mut node = Self::error(error.message, span, recovered_text);
+        node.add_metadata("severity".to_string(), format!("{:?}", error.severity));
+        node.add_metadata("error_kind".to_string(), format!("{:?}", error.kind));
+        node
+    }
+    
+    /// Get the error message of an error node
+    pub fn error_message(&self) -> Option<&str> {
+        if self.node_type != NodeType::Error {
+            return None;
+        }
+        self.metadata.get("error").map(String::as_str)
+    }
+    
+    /// Get the severity of an error node built from a `ParseError`
+    pub fn error_severity(&self) -> Option<&str> {
+        if self.node_type != NodeType::Error {
+            return None;
+        }
+        self.metadata.get("severity").map(String::as_str)
+    }
+    
+    /// Get the `ParseErrorKind` name of an error node built from a `ParseError`
+    pub fn error_kind(&self) -> Option<&str> {
+        if self.node_type != NodeType::Error {
+            return None;
+        }
+        self.metadata.get("error_kind").map(String::as_str)
+    }
+    
+    /// Get the source span of an error node
+    pub fn error_span(&self) -> Option<&Location> {
+        if self.node_type != NodeType::Error {
+            return None;
+        }
+        self.location.as_ref()
+    }
+    
     /// Add a child node
     pub fn add_child(&mut self, child: Node) {
         self.children.push(child);
@@ -235,6 +321,15 @@ impl AST {
         count
     }
     
+    /// Walk every node in preorder together with its depth (roots are depth 0)
+    ///
+    /// Uses an explicit stack, so very deep trees do not risk overflowing the call stack.
+    pub fn walk(&self) -> impl Iterator<Item = (&Node, usize)> {
+        AstWalk {
+            stack: self.roots.iter().rev().map(|root| (root, 0)).collect(),
+        }
+    }
+    
     /// Find all nodes of a specific type
     pub fn find_nodes_by_type(&self, node_type: &NodeType) -> Vec<&Node> {
         let mut nodes = Vec::new();
@@ -245,6 +340,23 @@ impl AST {
     }
 }
 
+/// Iterative preorder traversal backing `AST::walk`
+struct AstWalk<'a> {
+    stack: Vec<(&'a Node, usize)>,
+}
+
+impl<'a> Iterator for AstWalk<'a> {
+    type Item = (&'a Node, usize);
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        for child in node.children.iter().rev() {
+            self.stack.push((child, depth + 1));
+        }
+        Some((node, depth))
+    }
+}
+
 /// Count the total number of nodes in a tree
 fn count_nodes(node: &Node) -> usize {
     let mut count = 1; // Count this node
@@ -267,6 +379,7 @@ fn collect_nodes_by_type<'a>(node: &'a Node, target_type: &NodeType, nodes: &mut
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::{ErrorSeverity, ParseErrorKind};
     
     #[test]
     fn test_node_creation() {
@@ -290,6 +403,46 @@ mod tests {
         assert!(!parent.is_leaf());
     }
     
+    #[test]
+    fn test_error_node() {
+        let span = Location::new(1, 3).with_end(1, 5);
+        let node = Node::error("bad token".to_string(), span.clone(), "@@".to_string());
+        
+        assert_eq!(node.node_type, NodeType::Error);
+        assert_eq!(node.value, "@@");
+        assert_eq!(node.error_message(), Some("bad token"));
+        assert_eq!(node.error_span(), Some(&span));
+        assert_eq!(node.metadata.len(), 1);
+        assert_eq!(Node::new(NodeType::Literal, "1".to_string()).error_span(), None);
+        assert_eq!(node.error_severity(), None);
+        
+        let error = ParseError {
+            message: "stray token".to_string(),
+            location: Location::new(1, 3),
+            severity: ErrorSeverity::Warning,
+            kind: ParseErrorKind::UnexpectedToken,
+        };
+        let node = Node::from_parse_error(error, span.clone(), "@@".to_string());
+        assert_eq!(node.error_message(), Some("stray token"));
+        assert_eq!(node.error_span(), Some(&span));
+        assert_eq!(node.error_severity(), Some("Warning"));
+        assert_eq!(node.error_kind(), Some("UnexpectedToken"));
+    }
+    
+    #[test]
+    fn test_location_span() {
+        let span = Location::new(2, 5).with_end(3, 4);
+        assert_eq!(span.end(), Some((3, 4)));
+        assert!(span.contains(2, 5) && span.contains(2, 80) && span.contains(3, 3));
+        assert!(!span.contains(2, 4) && !span.contains(3, 4));
+        assert!(Location::new(1, 1).contains(1, 1) && !Location::new(1, 1).contains(1, 2));
+        
+        // Locations serialized before spans existed still load, as points
+        let old: Location = serde_json::from_str(r#"{"line":2,"column":5,"file":null}"#).unwrap();
+        assert_eq!(old, Location::new(2, 5));
+        assert!(!serde_json::to_string(&old).unwrap().contains("end_line"));
+    }
+    
     #[test]
     fn test_type_creation() {
         let mut type_info = Type::new("List".to_string());
@@ -300,6 +453,23 @@ mod tests {
         assert_eq!(type_info.full_name(), "List<String>");
     }
     
+    #[test]
+    fn test_walk_preorder_with_depth() {
+        let mut parent = Node::new(NodeType::BinaryOp, "+".to_string());
+        let mut left = Node::new(NodeType::UnaryOp, "-".to_string());
+        left.add_child(Node::new(NodeType::Literal, "1".to_string()));
+        parent.add_child(left);
+        parent.add_child(Node::new(NodeType::Literal, "2".to_string()));
+        
+        let mut ast = AST::new();
+        ast.add_root(parent);
+        ast.add_root(Node::new(NodeType::Variable, "x".to_string()));
+        
+        let visited: Vec<(&str, usize)> = ast.walk().map(|(node, depth)| (node.value.as_str(), depth)).collect();
+        assert_eq!(visited, vec![("+", 0), ("-", 1), ("1", 2), ("2", 1), ("x", 0)]);
+        assert_eq!(ast.walk().count(), ast.node_count());
+    }
+    
     #[test]
     fn test_ast_creation() {
         let mut ast = AST::new();
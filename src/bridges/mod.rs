@@ -8,49 +8,36 @@ use std::path::PathBuf;
 use anyhow::Result;
 use tracing::{info, warn, error};
 
+pub use crate::languages::{BridgeConfig, BridgeHandler, BridgeRequest, SupportedLanguage};
+
 pub mod python;
 pub mod rust;
 pub mod javascript;
 pub mod cpp;
 pub mod go;
 
-/// Supported language bridges
-#[derive(Debug, Clone, PartialEq)]
-pub enum SupportedLanguage {
-    Python,
-    Rust,
-    JavaScript,
-    TypeScript,
-    Cpp,
-    Go,
-    Java,
-    CSharp,
-}
-
-impl std::str::FromStr for SupportedLanguage {
-    type Err = anyhow::Error;
-    
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "python" | "py" => Ok(SupportedLanguage::Python),
-            "rust" | "rs" => Ok(SupportedLanguage::Rust),
-            "javascript" | "js" => Ok(SupportedLanguage::JavaScript),
-            "typescript" | "ts" => Ok(SupportedLanguage::TypeScript),
-            "cpp" | "c++" | "c" => Ok(SupportedLanguage::Cpp),
-            "go" | "golang" => Ok(SupportedLanguage::Go),
-            "java" => Ok(SupportedLanguage::Java),
-            "csharp" | "c#" => Ok(SupportedLanguage::CSharp),
-            _ => Err(anyhow::anyhow!("Unsupported language: {}", s)),
-        }
-    }
-}
-
 /// Initialize NEXUS integration in an existing project
 pub async fn init_integration(
     project_dir: &PathBuf,
     language: &str,
     examples: bool,
 ) -> Result<()> {
+    init_integration_with(&BridgeConfig::default(), project_dir, language, examples).await
+}
+
+/// Initialize NEXUS integration, trying custom handlers from `config` first
+pub async fn init_integration_with(
+    config: &BridgeConfig,
+    project_dir: &PathBuf,
+    language: &str,
+    examples: bool,
+) -> Result<()> {
+    if let Some(result) = config.run_custom(language, BridgeRequest::Init { project_dir, examples }) {
+        result?;
+        info!("✅ NEXUS integration initialized with custom {} bridge", language);
+        return Ok(());
+    }
+    
     let lang: SupportedLanguage = language.parse()?;
     info!("🔗 Initializing NEXUS integration for {} project", language);
     
@@ -86,6 +73,22 @@ pub async fn add_nexus_to_file(
     file: &PathBuf,
     generate_bridge: bool,
 ) -> Result<()> {
+    add_nexus_to_file_with(&BridgeConfig::default(), language, file, generate_bridge).await
+}
+
+/// Add NEXUS to an existing file, trying custom handlers from `config` first
+pub async fn add_nexus_to_file_with(
+    config: &BridgeConfig,
+    language: &str,
+    file: &PathBuf,
+    generate_bridge: bool,
+) -> Result<()> {
+    if let Some(result) = config.run_custom(language, BridgeRequest::AddToFile { file, generate_bridge }) {
+        result?;
+        info!("✅ NEXUS integration added to file with custom {} bridge", language);
+        return Ok(());
+    }
+    
     let lang: SupportedLanguage = language.parse()?;
     info!("➕ Adding NEXUS to {} file: {:?}", language, file);
     
@@ -280,13 +283,4 @@ mod tests {
         assert_eq!(lang, SupportedLanguage::Rust);
         assert_eq!(pkg, "serde");
     }
-    
-    #[test]
-    fn test_parse_language() {
-        let lang: SupportedLanguage = "python".parse().unwrap();
-        assert_eq!(lang, SupportedLanguage::Python);
-        
-        let lang: SupportedLanguage = "PYTHON".parse().unwrap();
-        assert_eq!(lang, SupportedLanguage::Python);
-    }
 }
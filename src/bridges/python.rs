@@ -11,6 +11,8 @@ use anyhow::Result;
 use tracing::{info, warn, error};
 use std::process::Command;
 use std::fs;
+use crate::languages::python::measure_compressibility;
+use super::SupportedLanguage;
 
 /// Initialize NEXUS integration in a Python project
 pub async fn init_integration(project_dir: &PathBuf, examples: bool) -> Result<()> {
@@ -213,6 +215,10 @@ pub async fn profile_directory(dir: &PathBuf, threshold_ms: u64) -> Result<Strin
             report.push_str(&format!("   Lines: {}\n", analysis.line_count));
             report.push_str(&format!("   Complexity: {}\n", analysis.complexity));
             report.push_str(&format!("   Migration Score: {:.1f}%\n", analysis.migration_score));
+            match analysis.compression_ratio {
+                Some(ratio) => report.push_str(&format!("   Compressibility: {:.2}x ({} Γ-AST nodes)\n", ratio, analysis.gamma_nodes)),
+                None => report.push_str("   Compressibility: unavailable\n"),
+            }
             
             if analysis.migration_score > 70.0 {
                 report.push_str("   🚀 HIGH PRIORITY for NEXUS migration!\n");
@@ -316,7 +322,8 @@ async fn find_python_files(dir: &PathBuf) -> Result<Vec<PathBuf>> {
         for entry in entries {
             if let Ok(entry) = entry {
                 let path = entry.path();
-                if path.is_file() && path.extension().map_or(false, |ext| ext == "py") {
+                let language = path.extension().and_then(|ext| ext.to_str()).and_then(SupportedLanguage::from_extension);
+                if path.is_file() && language == Some(SupportedLanguage::Python) {
                     python_files.push(path);
                 } else if path.is_dir() {
                     let sub_files = find_python_files(&path).await?;
@@ -335,6 +342,8 @@ struct PythonFileAnalysis {
     line_count: usize,
     complexity: f64,
     migration_score: f64,
+    gamma_nodes: usize,
+    compression_ratio: Option<f64>,
 }
 
 /// Analyze a Python file for migration opportunities
@@ -364,10 +373,21 @@ async fn analyze_python_file(file: &PathBuf, threshold_ms: u64) -> Result<Python
     // Calculate migration score based on complexity and size
     let migration_score = (complexity * 10.0 + line_count as f64 * 0.1).min(100.0);
     
+    // Measure real compressibility by running the file through the engine
+    let (gamma_nodes, compression_ratio) = match measure_compressibility(&content) {
+        Ok(measured) => (measured.gamma_nodes, Some(measured.compression_ratio)),
+        Err(e) => {
+            warn!("Could not compress {:?}: {}", file, e);
+            (0, None)
+        }
+    };
+    
     Ok(PythonFileAnalysis {
         line_count,
         complexity,
         migration_score,
+        gamma_nodes,
+        compression_ratio,
     })
 }
 
@@ -493,5 +513,7 @@ def test_function():
         
         let analysis = analyze_python_file(&python_file, 100).await.unwrap();
         assert!(analysis.migration_score > 0.0);
+        assert!(analysis.gamma_nodes > 0);
+        assert!(analysis.compression_ratio.is_some());
     }
 }
@@ -4,13 +4,24 @@
 //! between different programming languages. It includes compression algorithms,
 //! pattern recognition, and metadata support.
 
-use crate::ast::Location;
-use std::collections::HashMap;
+use crate::ast::{Location, Node, NodeType, AST};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::borrow::{Borrow, Cow};
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Serialize, Deserialize};
 
 /// Represents a compressed node in the Γ-AST
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// `Hash` covers node type, value and children only, so structurally identical
+/// nodes hash alike regardless of ID, location or metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GammaNode {
     /// Unique identifier for this node
     pub id: u64,
@@ -28,8 +39,293 @@ pub struct GammaNode {
     pub compression_level: CompressionLevel,
 }
 
+impl std::hash::Hash for GammaNode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.node_type.hash(state);
+        self.value.hash(state);
+        self.children.hash(state);
+    }
+}
+
+impl GammaNode {
+    /// Start building a node; unset fields default to no value, children,
+    /// location or metadata, and `CompressionLevel::None`
+    pub fn builder(id: u64, node_type: GammaNodeType) -> GammaNodeBuilder {
+        GammaNodeBuilder {
+            node: GammaNode {
+                id,
+                node_type,
+                value: GammaValue::None,
+                location: None,
+                children: Vec::new(),
+                metadata: HashMap::new(),
+                compression_level: CompressionLevel::None,
+            },
+        }
+    }
+    
+    /// Structural signature over node type, value and child count, without allocating
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = SignatureHasher::new();
+        self.write_shape(&mut hasher);
+        hasher.write_u64(self.children.len() as u64);
+        hasher.finish()
+    }
+    
+    /// Feed type, value and the ordered child IDs
+    fn write_local_content(&self, hasher: &mut SignatureHasher) {
+        self.write_shape(hasher);
+        hasher.write_u64(self.children.len() as u64);
+        for child in &self.children {
+            hasher.write_u64(*child);
+        }
+    }
+    
+    fn write_shape(&self, hasher: &mut SignatureHasher) {
+        // Same fields as `as_str`, without formatting a string for custom types;
+        // no built-in name contains ':', so the prefixes cannot collide with one
+        match &self.node_type {
+            GammaNodeType::Custom(name) => {
+                hasher.write_str("Custom:");
+                hasher.write_str(name);
+            }
+            GammaNodeType::InternedCustom(id) => {
+                hasher.write_str("InternedCustom:");
+                hasher.write_u64(*id as u64);
+            }
+            fixed => hasher.write_str(&fixed.as_str()),
+        }
+        self.write_value(hasher);
+    }
+    
+    fn write_value(&self, hasher: &mut SignatureHasher) {
+        match &self.value {
+            GammaValue::Direct(value) => hasher.write_str(value),
+            GammaValue::PatternRef(id) => hasher.write_u64(*id),
+            GammaValue::CompressedHash(hash) => hasher.write_u64(*hash),
+            GammaValue::DictRef { dict_id, entry } => {
+                hasher.write_u64(*dict_id as u64);
+                hasher.write_u64(*entry as u64);
+            }
+            GammaValue::None => {}
+        }
+    }
+}
+
+/// Fluent constructor for `GammaNode`, from `GammaNode::builder`
+#[derive(Debug, Clone)]
+pub struct GammaNodeBuilder {
+    node: GammaNode,
+}
+
+impl GammaNodeBuilder {
+    pub fn value(mut self, value: GammaValue) -> Self {
+        self.node.value = value;
+        self
+    }
+    
+    /// Set a `GammaValue::Direct` value
+    pub fn direct(self, value: impl Into<String>) -> Self {
+        self.value(GammaValue::Direct(value.into()))
+    }
+    
+    pub fn children(mut self, children: impl IntoIterator<Item = u64>) -> Self {
+        self.node.children = children.into_iter().collect();
+        self
+    }
+    
+    pub fn location(mut self, location: Location) -> Self {
+        self.node.location = Some(location);
+        self
+    }
+    
+    /// Add one metadata entry, replacing any earlier value for `key`
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.node.metadata.insert(key.into(), value.into());
+        self
+    }
+    
+    pub fn compression_level(mut self, level: CompressionLevel) -> Self {
+        self.node.compression_level = level;
+        self
+    }
+    
+    pub fn build(self) -> GammaNode {
+        self.node
+    }
+}
+
+/// SplitMix64 finalizer, a cheap seeded draw that is stable across platforms
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Non-allocating hasher for node signatures
+///
+/// Input is fed a machine word at a time into two CRC32-C lanes, the second
+/// seeing each word multiplied by an odd constant so the lanes stay independent,
+/// and `finish` avalanches the pair. With the `simd` feature on x86_64 CPUs with
+/// SSE4.2 the lanes use the hardware CRC32 instruction instead of a table; both
+/// compute the same function, so signatures match across builds.
+#[derive(Debug, Clone)]
+pub struct SignatureHasher {
+    low: u32,
+    high: u32,
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    hardware: bool,
+}
+
+impl SignatureHasher {
+    const SEED: u64 = 0x517c_c1b7_2722_0a95;
+    
+    pub fn new() -> Self {
+        Self {
+            low: u32::MAX,
+            high: u32::MAX,
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            hardware: std::arch::is_x86_feature_detected!("sse4.2"),
+        }
+    }
+    
+    /// Feed a string, length-prefixed so adjacent fields cannot run together
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+    }
+    
+    pub fn write_u64(&mut self, value: u64) {
+        self.mix(value);
+    }
+    
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            self.mix(u64::from_le_bytes(word));
+        }
+        let remainder = chunks.remainder();
+        if !remainder.is_empty() {
+            let mut word = [0u8; 8];
+            word[..remainder.len()].copy_from_slice(remainder);
+            self.mix(u64::from_le_bytes(word));
+        }
+    }
+    
+    pub fn finish(&self) -> u64 {
+        // Final avalanche so low-entropy inputs still spread over all bits
+        let mut hash = (self.high as u64) << 32 | self.low as u64;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        hash
+    }
+    
+    fn mix(&mut self, word: u64) {
+        let spread = word.wrapping_mul(Self::SEED);
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        if self.hardware {
+            // SAFETY: `hardware` is only set when SSE4.2 was detected at runtime
+            unsafe {
+                self.low = crc32c_hardware(self.low, word);
+                self.high = crc32c_hardware(self.high, spread);
+            }
+            return;
+        }
+        self.low = crc32c_software(self.low, word);
+        self.high = crc32c_software(self.high, spread);
+    }
+}
+
+impl Default for SignatureHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Source of pattern and synthetic node IDs that are unique within one compression run
+///
+/// IDs count up from a seed, so none repeats until 2^64 have been issued; the
+/// counter then wraps rather than panicking. Seeding from an AST starts just
+/// past its highest node or pattern ID, so issued IDs never collide with them.
+#[derive(Debug, Default)]
+pub struct IdGenerator {
+    next: AtomicU64,
+}
+
+impl IdGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self { next: AtomicU64::new(seed) }
+    }
+    
+    /// Seed one past the highest node or pattern ID already in `ast`
+    pub fn seeded_from(ast: &GammaAST) -> Self {
+        let highest = ast.nodes.keys().chain(ast.patterns.keys()).max();
+        Self::new(highest.map_or(0, |id| id.wrapping_add(1)))
+    }
+    
+    /// Take the next ID
+    pub fn next_id(&self) -> u64 {
+        // fetch_add wraps at u64::MAX
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// CRC32-C lookup tables for slicing by eight: entry `k` advances a byte through `k` more zero bytes
+const CRC32C_TABLES: [[u32; 256]; 8] = {
+    let mut tables = [[0u32; 256]; 8];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+            bit += 1;
+        }
+        tables[0][index] = crc;
+        index += 1;
+    }
+    let mut slice = 1;
+    while slice < 8 {
+        let mut index = 0;
+        while index < 256 {
+            let previous = tables[slice - 1][index];
+            tables[slice][index] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            index += 1;
+        }
+        slice += 1;
+    }
+    tables
+};
+
+/// CRC32-C update over one little-endian word, without the usual pre- and post-inversion
+fn crc32c_software(crc: u32, word: u64) -> u32 {
+    let x = word ^ crc as u64;
+    let byte = |shift: u32| ((x >> shift) & 0xff) as usize;
+    CRC32C_TABLES[7][byte(0)]
+        ^ CRC32C_TABLES[6][byte(8)]
+        ^ CRC32C_TABLES[5][byte(16)]
+        ^ CRC32C_TABLES[4][byte(24)]
+        ^ CRC32C_TABLES[3][byte(32)]
+        ^ CRC32C_TABLES[2][byte(40)]
+        ^ CRC32C_TABLES[1][byte(48)]
+        ^ CRC32C_TABLES[0][byte(56)]
+}
+
+/// `crc32c_software` in one instruction
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.2")]
+unsafe fn crc32c_hardware(crc: u32, word: u64) -> u32 {
+    std::arch::x86_64::_mm_crc32_u64(crc as u64, word) as u32
+}
+
 /// Types of nodes in the Γ-AST (optimized for compression)
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Serialized through its stable textual encoding (see `as_str`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GammaNodeType {
     // Core types (single byte)
     Literal,
@@ -58,10 +354,158 @@ pub enum GammaNodeType {
     
     // Custom types (compressed)
     Custom(String),
+    /// Custom type name stored in the owning AST's `custom_types` table
+    InternedCustom(u32),
+}
+
+impl GammaNodeType {
+    /// Fixed names for the built-in types; never rename these, signatures depend on them
+    const FIXED: [(&'static str, GammaNodeType); 17] = [
+        ("Literal", GammaNodeType::Literal),
+        ("Variable", GammaNodeType::Variable),
+        ("Function", GammaNodeType::Function),
+        ("Class", GammaNodeType::Class),
+        ("Module", GammaNodeType::Module),
+        ("If", GammaNodeType::If),
+        ("Loop", GammaNodeType::Loop),
+        ("Switch", GammaNodeType::Switch),
+        ("Try", GammaNodeType::Try),
+        ("BinaryOp", GammaNodeType::BinaryOp),
+        ("UnaryOp", GammaNodeType::UnaryOp),
+        ("Assignment", GammaNodeType::Assignment),
+        ("Call", GammaNodeType::Call),
+        ("Block", GammaNodeType::Block),
+        ("Expression", GammaNodeType::Expression),
+        ("Statement", GammaNodeType::Statement),
+        ("Declaration", GammaNodeType::Declaration),
+    ];
+    
+    /// Stable textual encoding, independent of the `Debug` output
+    ///
+    /// Built-in types use their fixed name; custom types are encoded as
+    /// `Custom:<name>` and interned ones as `InternedCustom:<id>`.
+    pub fn as_str(&self) -> Cow<'_, str> {
+        match self {
+            GammaNodeType::Custom(name) => Cow::Owned(format!("Custom:{}", name)),
+            GammaNodeType::InternedCustom(id) => Cow::Owned(format!("InternedCustom:{}", id)),
+            fixed => Cow::Borrowed(
+                Self::FIXED.iter()
+                    .find(|(_, node_type)| node_type == fixed)
+                    .map(|(name, _)| *name)
+                    .expect("every built-in node type has a fixed name"),
+            ),
+        }
+    }
+    
+    /// Parse the encoding produced by `as_str`
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(text: &str) -> Option<GammaNodeType> {
+        if let Some(name) = text.strip_prefix("Custom:") {
+            return Some(GammaNodeType::Custom(name.to_string()));
+        }
+        if let Some(id) = text.strip_prefix("InternedCustom:") {
+            return id.parse().ok().map(GammaNodeType::InternedCustom);
+        }
+        Self::FIXED.iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, node_type)| node_type.clone())
+    }
+    
+    // Classification covers the built-in types only; custom names carry no
+    // agreed meaning, so `Custom` and `InternedCustom` are never classified.
+    
+    /// Branching, looping and exception handling: `If`, `Loop`, `Switch`, `Try`
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self, GammaNodeType::If | GammaNodeType::Loop | GammaNodeType::Switch | GammaNodeType::Try)
+    }
+    
+    /// Nodes that introduce a name: `Function`, `Class`, `Module`, `Declaration`
+    pub fn is_declaration(&self) -> bool {
+        matches!(self, GammaNodeType::Function | GammaNodeType::Class | GammaNodeType::Module | GammaNodeType::Declaration)
+    }
+    
+    /// Nodes that produce a value: literals, variables, operators, calls and `Expression`
+    ///
+    /// `Assignment` is treated as a statement, as the parser emits it.
+    pub fn is_expression(&self) -> bool {
+        matches!(
+            self,
+            GammaNodeType::Literal
+                | GammaNodeType::Variable
+                | GammaNodeType::BinaryOp
+                | GammaNodeType::UnaryOp
+                | GammaNodeType::Call
+                | GammaNodeType::Expression
+        )
+    }
+    
+    pub fn is_literal(&self) -> bool {
+        matches!(self, GammaNodeType::Literal)
+    }
+}
+
+/// Parser node types map to their Γ-AST kind; calls and loops share one kind each
+impl From<&NodeType> for GammaNodeType {
+    fn from(node_type: &NodeType) -> Self {
+        match node_type {
+            NodeType::Literal => GammaNodeType::Literal,
+            NodeType::Variable => GammaNodeType::Variable,
+            NodeType::BinaryOp => GammaNodeType::BinaryOp,
+            NodeType::UnaryOp => GammaNodeType::UnaryOp,
+            NodeType::FunctionCall | NodeType::MethodCall => GammaNodeType::Call,
+            NodeType::Expression => GammaNodeType::Expression,
+            NodeType::Assignment => GammaNodeType::Assignment,
+            NodeType::Declaration => GammaNodeType::Declaration,
+            NodeType::If => GammaNodeType::If,
+            NodeType::While | NodeType::For => GammaNodeType::Loop,
+            NodeType::Block => GammaNodeType::Block,
+            NodeType::Function => GammaNodeType::Function,
+            NodeType::Class => GammaNodeType::Class,
+            NodeType::Module => GammaNodeType::Module,
+            other => GammaNodeType::Custom(format!("{:?}", other)),
+        }
+    }
+}
+
+impl fmt::Display for GammaNodeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.as_str())
+    }
+}
+
+impl Serialize for GammaNodeType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for GammaNodeType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// Externally tagged form written before the textual encoding existed
+        #[derive(Deserialize)]
+        enum LegacyNodeType {
+            Custom(String),
+            InternedCustom(u32),
+        }
+        
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Encoded {
+            Text(String),
+            Legacy(LegacyNodeType),
+        }
+        
+        match Encoded::deserialize(deserializer)? {
+            Encoded::Text(text) => GammaNodeType::from_str(&text)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown node type: {}", text))),
+            Encoded::Legacy(LegacyNodeType::Custom(name)) => Ok(GammaNodeType::Custom(name)),
+            Encoded::Legacy(LegacyNodeType::InternedCustom(id)) => Ok(GammaNodeType::InternedCustom(id)),
+        }
+    }
 }
 
 /// Compressed values in the Γ-AST
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GammaValue {
     /// Direct value (for small literals)
     Direct(String),
@@ -69,6 +513,8 @@ pub enum GammaValue {
     PatternRef(u64),
     /// Compressed hash (for large values)
     CompressedHash(u64),
+    /// Entry in an external, shared `PatternDictionary`
+    DictRef { dict_id: u32, entry: u32 },
     /// Null/empty value
     None,
 }
@@ -80,13 +526,31 @@ impl GammaValue {
             GammaValue::Direct(s) => s.clone(),
             GammaValue::PatternRef(id) => format!("pattern_{}", id),
             GammaValue::CompressedHash(hash) => format!("hash_{:x}", hash),
+            GammaValue::DictRef { dict_id, entry } => format!("dict_{}_{}", dict_id, entry),
             GammaValue::None => "".to_string(),
         }
     }
+    
+    /// Render the value, resolving references to the original where `ast` records it
+    ///
+    /// `PatternRef`s are followed through patterns whose snapshot nodes share one
+    /// value and through nodes (dedup references); `CompressedHash`es are read back
+    /// from the `original_value`/`original_string` metadata of a node carrying the
+    /// same hash; `DictRef`s resolve through the AST's metadata dictionary.
+    /// Anything unrecoverable, such as lossy value-table IDs, renders as `to_string`.
+    pub fn display_with(&self, ast: &GammaAST) -> String {
+        ast.resolve_value(self).unwrap_or_else(|| self.to_string())
+    }
 }
 
 /// Compression levels achieved
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Passes record how they encoded a node so decoding can pick the matching
+/// inverse (see `GammaAST::decompress_levels`): `Light` marks a value replaced
+/// by a `PatternRef` to another node holding the same value, `Medium` a node
+/// replaced by a `PatternRef` to a pattern that snapshots the original, and
+/// `Heavy`/`Maximum` lossy rewrites that cannot be undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CompressionLevel {
     /// No compression (original size)
     None,
@@ -101,7 +565,7 @@ pub enum CompressionLevel {
 }
 
 /// Pattern recognition system for identifying common code structures
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pattern {
     /// Unique pattern identifier
     pub id: u64,
@@ -117,6 +581,28 @@ pub struct Pattern {
     pub languages: Vec<String>,
 }
 
+impl Pattern {
+    /// Expected payoff of applying this pattern: `frequency × size × compression_potential`
+    pub fn value_score(&self, compression_potential: f64) -> f64 {
+        self.frequency as f64 * self.size as f64 * compression_potential
+    }
+}
+
+/// Order patterns so the most valuable claim their nodes first
+///
+/// Sorts by `Pattern::value_score` descending, with `compression_potential`
+/// evaluated once per pattern. Equal scores fall back to ascending pattern ID,
+/// so overlapping patterns are always resolved the same way.
+pub fn sort_patterns_by_value<P: Borrow<Pattern>>(patterns: &mut Vec<P>, compression_potential: impl Fn(&Pattern) -> f64) {
+    let mut scored: Vec<(f64, P)> = patterns.drain(..)
+        .map(|pattern| (pattern.borrow().value_score(compression_potential(pattern.borrow())), pattern))
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.total_cmp(a_score).then(a.borrow().id.cmp(&b.borrow().id))
+    });
+    patterns.extend(scored.into_iter().map(|(_, pattern)| pattern));
+}
+
 /// Cross-file pattern for maximum compression across codebases
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrossFilePattern {
@@ -151,6 +637,12 @@ pub struct MetaPattern {
     pub hierarchical_level: u8,
 }
 
+/// First bytes of every file written by `GammaAST::save_to_file`
+pub const FILE_MAGIC: [u8; 4] = *b"NXGA";
+
+/// Layout version written after `FILE_MAGIC`; bump it when the encoding changes
+pub const FILE_FORMAT_VERSION: u8 = 1;
+
 /// Γ-AST implementation with compression and pattern recognition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GammaAST {
@@ -166,128 +658,1648 @@ pub struct GammaAST {
     pub compression_stats: CompressionStats,
     /// Pattern registry for reuse
     pub pattern_registry: PatternRegistry,
+    /// Interned custom node type names
+    #[serde(default)]
+    pub custom_types: CustomTypeTable,
+    /// Shared table of metadata keys and values while metadata is interned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_dictionary: Option<PatternDictionary>,
+    /// Nodes whose metadata stayed plain text while the rest is interned
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub plain_metadata: HashSet<u64>,
+    /// What `decompress` needs to undo compression, if the engine recorded it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconstruction: Option<ReconstructionTable>,
 }
 
-/// Compression statistics and metrics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CompressionStats {
-    /// Original size in bytes
-    pub original_size: usize,
-    /// Compressed size in bytes
-    pub compressed_size: usize,
-    /// Compression ratio achieved
-    pub compression_ratio: f64,
-    /// Number of patterns identified
-    pub patterns_found: usize,
-    /// Memory usage optimization
-    pub memory_optimization: f64,
+/// Interning table for custom node type names, indexed by `GammaNodeType::InternedCustom`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomTypeTable {
+    /// Names in ID order
+    names: Vec<String>,
+    /// Reverse lookup, rebuilt on demand after deserialization
+    #[serde(skip)]
+    ids: HashMap<String, u32>,
 }
 
-/// Pattern registry for efficient pattern reuse
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PatternRegistry {
-    /// Pattern signatures mapped to pattern IDs
-    pub signatures: HashMap<u64, u64>,
-    /// Pattern frequency tracking
-    pub frequencies: HashMap<u64, u32>,
-    /// Pattern size distribution
-    pub size_distribution: HashMap<usize, u32>,
+impl CustomTypeTable {
+    /// Get the ID for a name, adding it if new
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if self.ids.len() != self.names.len() {
+            self.ids = self.names.iter().enumerate().map(|(i, n)| (n.clone(), i as u32)).collect();
+        }
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+    
+    /// Look up the name for an ID
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+    
+    /// Number of interned names
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    
+    /// Check if no names are interned
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
 }
 
-impl GammaAST {
-    /// Create a new Γ-AST
-    pub fn new() -> Self {
+/// Shared value dictionary referenced by `GammaValue::DictRef` across files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PatternDictionary {
+    /// Identifier stored in `DictRef::dict_id`
+    pub id: u32,
+    /// Values in entry order
+    entries: Vec<String>,
+    /// Reverse lookup, rebuilt on demand after deserialization
+    #[serde(skip)]
+    index: HashMap<String, u32>,
+}
+
+impl PatternDictionary {
+    /// Create an empty dictionary with the given ID
+    pub fn new(id: u32) -> Self {
         Self {
-            roots: Vec::new(),
-            nodes: HashMap::new(),
-            patterns: HashMap::new(),
-            source_language: String::new(),
-            compression_stats: CompressionStats::new(),
-            pattern_registry: PatternRegistry::new(),
+            id,
+            ..Self::default()
         }
     }
     
-    /// Set the source language
-    pub fn set_source_language(&mut self, language: String) {
-        self.source_language = language;
+    /// Get the entry for a value, adding it if new
+    pub fn insert(&mut self, value: &str) -> u32 {
+        if self.index.len() != self.entries.len() {
+            self.index = self.entries.iter().enumerate().map(|(i, v)| (v.clone(), i as u32)).collect();
+        }
+        if let Some(&entry) = self.index.get(value) {
+            return entry;
+        }
+        let entry = self.entries.len() as u32;
+        self.entries.push(value.to_string());
+        self.index.insert(value.to_string(), entry);
+        entry
     }
     
-    /// Add a root node
-    pub fn add_root(&mut self, node_id: u64) {
-        self.roots.push(node_id);
+    /// Look up the value for an entry
+    pub fn get(&self, entry: u32) -> Option<&str> {
+        self.entries.get(entry as usize).map(String::as_str)
     }
     
-    /// Add a node to the AST
-    pub fn add_node(&mut self, node: GammaNode) {
-        self.nodes.insert(node.id, node);
+    /// Reference to `value` in this dictionary, adding it if new
+    pub fn reference(&mut self, value: &str) -> GammaValue {
+        GammaValue::DictRef {
+            dict_id: self.id,
+            entry: self.insert(value),
+        }
     }
     
-    /// Get a node by ID
-    pub fn get_node(&self, id: u64) -> Option<&GammaNode> {
-        self.nodes.get(&id)
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
     
-    /// Get a mutable reference to a node
-    pub fn get_node_mut(&mut self, id: u64) -> Option<&mut GammaNode> {
-        self.nodes.get_mut(&id)
+    /// Check if the dictionary has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
-    
-    /// Add a recognized pattern
-    pub fn add_pattern(&mut self, pattern: Pattern) {
-        self.patterns.insert(pattern.id, pattern.clone());
-        self.pattern_registry.signatures.insert(pattern.signature, pattern.id);
-        self.pattern_registry.frequencies.insert(pattern.id, pattern.frequency);
-        self.pattern_registry.size_distribution.entry(pattern.size).and_modify(|e| *e += 1).or_insert(1);
+}
+
+/// Node-level changes between two Γ-ASTs, used for incremental transport
+///
+/// Only nodes and roots are tracked; patterns and statistics are rebuilt by the receiver.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AstPatch {
+    /// Nodes present only in the newer AST
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<GammaNode>,
+    /// IDs of nodes present only in the older AST
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<u64>,
+    /// Nodes whose content changed (full replacement)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modified: Vec<GammaNode>,
+    /// New root list, if it changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Vec<u64>>,
+}
+
+impl AstPatch {
+    /// Check if the patch carries no changes
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() && self.roots.is_none()
     }
-    
-    /// Find patterns in the AST
-    pub fn find_patterns(&mut self) -> Vec<u64> {
-        let mut found_patterns = Vec::new();
+}
+
+/// What `GammaAST::decompress` cannot decode on its own, stored on a compressed Γ-AST
+///
+/// Only information a pass threw away is kept. `Light` and `Medium` nodes and
+/// interned metadata decode from the AST itself and are not recorded; lossy
+/// value references are kept once per reference, not once per node.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconstructionTable {
+    /// Original values of lossy `PatternRef` IDs (value-table entries)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub values: HashMap<u64, String>,
+    /// Original values of `CompressedHash` hashes
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub hashes: HashMap<u64, String>,
+    /// Original values of nodes whose reference stands for different values on different nodes
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub node_values: HashMap<u64, GammaValue>,
+    /// Original children of nodes whose children a pass cleared or replaced
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub children: HashMap<u64, Vec<u64>>,
+    /// Original metadata of nodes whose metadata a pass rewrote or dropped
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<u64, HashMap<String, String>>,
+    /// Whole originals of nodes removed or changed in any other way (type, location, level)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub nodes: HashMap<u64, GammaNode>,
+    /// IDs of nodes compression added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added_nodes: Vec<u64>,
+    /// Roots before compression, if they changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Vec<u64>>,
+}
+
+impl ReconstructionTable {
+    /// Record what `decompress` needs, beyond `compressed` itself, to rebuild `original`
+    pub fn between(original: &GammaAST, compressed: &GammaAST) -> Self {
+        let mut table = Self::default();
+        // Interned metadata is compared in its expanded form, since `decompress` expands it
+        let dictionary = compressed.metadata_dictionary.as_ref().filter(|_| original.metadata_dictionary.is_none());
+        let expand = |entry: &String| {
+            dictionary.and_then(|dictionary| dictionary.get(entry.parse().ok()?)).map(str::to_string)
+        };
+        // Reference values seen so far; `None` once a reference stands for several values
+        let mut shared: HashMap<GammaValue, Option<String>> = HashMap::new();
+        let mut lossy_refs = Vec::new();
         
-        // Analyze node structures for common patterns
-        for (id, _node) in &self.nodes {
-            if let Some(pattern_id) = self.identify_pattern(*id) {
-                found_patterns.push(pattern_id);
+        for (&id, node) in &original.nodes {
+            let Some(compressed_node) = compressed.nodes.get(&id) else {
+                table.nodes.insert(id, node.clone());
+                continue;
+            };
+            if compressed_node == node {
+                continue;
+            }
+            if node.compression_level != CompressionLevel::None
+                || compressed_node.node_type != node.node_type
+                || compressed_node.location != node.location
+            {
+                table.nodes.insert(id, node.clone());
+                continue;
+            }
+            
+            let decodable = matches!(compressed_node.compression_level, CompressionLevel::Light | CompressionLevel::Medium);
+            if compressed_node.value != node.value && !decodable {
+                match (&compressed_node.value, &node.value) {
+                    (reference @ (GammaValue::PatternRef(_) | GammaValue::CompressedHash(_)), GammaValue::Direct(value)) => {
+                        let entry = shared.entry(reference.clone()).or_insert_with(|| Some(value.clone()));
+                        if entry.as_ref() != Some(value) {
+                            *entry = None;
+                        }
+                        lossy_refs.push((id, reference.clone()));
+                    }
+                    _ => {
+                        table.node_values.insert(id, node.value.clone());
+                    }
+                }
+            }
+            if compressed_node.children != node.children {
+                table.children.insert(id, node.children.clone());
+            }
+            let metadata_kept = match dictionary.filter(|_| !compressed.plain_metadata.contains(&id)) {
+                Some(_) => compressed_node.metadata.len() == node.metadata.len()
+                    && compressed_node.metadata.iter().all(|(key, value)| {
+                        matches!((expand(key), expand(value)), (Some(key), Some(value)) if node.metadata.get(&key) == Some(&value))
+                    }),
+                None => compressed_node.metadata == node.metadata,
+            };
+            if !metadata_kept {
+                table.metadata.insert(id, node.metadata.clone());
             }
         }
         
-        found_patterns
-    }
-    
-    /// Identify if a node matches a known pattern
-    fn identify_pattern(&self, node_id: u64) -> Option<u64> {
-        // This is a simplified pattern identification
-        // In a full implementation, this would use sophisticated pattern matching algorithms
-        if let Some(node) = self.get_node(node_id) {
-            // Check if this node structure matches any known patterns
-            for (pattern_id, pattern) in &self.patterns {
-                if self.matches_pattern(node, pattern) {
-                    return Some(*pattern_id);
+        for (id, reference) in lossy_refs {
+            match (&reference, shared.get(&reference).cloned().flatten()) {
+                (GammaValue::PatternRef(key), Some(value)) => {
+                    table.values.insert(*key, value);
+                }
+                (GammaValue::CompressedHash(key), Some(value)) => {
+                    table.hashes.insert(*key, value);
+                }
+                _ => {
+                    table.node_values.insert(id, original.nodes[&id].value.clone());
                 }
             }
         }
-        None
+        
+        table.added_nodes = compressed.nodes.keys().copied().filter(|id| !original.nodes.contains_key(id)).collect();
+        table.added_nodes.sort_unstable();
+        if compressed.roots != original.roots {
+            table.roots = Some(original.roots.clone());
+        }
+        table
     }
     
-    /// Check if a node matches a pattern
-    fn matches_pattern(&self, node: &GammaNode, pattern: &Pattern) -> bool {
-        // Simplified pattern matching - in reality this would be much more sophisticated
-        node.node_type == pattern.nodes[0].node_type && 
-        node.children.len() == pattern.nodes[0].children.len()
+    /// Check if compression threw nothing away
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+            && self.hashes.is_empty()
+            && self.node_values.is_empty()
+            && self.children.is_empty()
+            && self.metadata.is_empty()
+            && self.nodes.is_empty()
+            && self.added_nodes.is_empty()
+            && self.roots.is_none()
     }
     
-    /// Calculate compression statistics
-    pub fn calculate_compression_stats(&mut self) {
-        let original_size = self.calculate_original_size();
-        let compressed_size = self.calculate_compressed_size();
-        let compression_ratio = if original_size > 0 {
-            original_size as f64 / compressed_size as f64
-        } else {
-            1.0
+    /// Approximate bytes the table adds to a compressed AST: string contents plus 8 bytes per ID
+    pub fn size_bytes(&self) -> usize {
+        let id = std::mem::size_of::<u64>();
+        let value_bytes = |value: &GammaValue| match value {
+            GammaValue::Direct(value) => value.len(),
+            _ => id,
         };
+        let metadata_bytes = |metadata: &HashMap<String, String>| metadata.iter().map(|(key, value)| key.len() + value.len()).sum::<usize>();
         
-        self.compression_stats = CompressionStats {
-            original_size,
+        self.values.values().chain(self.hashes.values()).map(|value| id + value.len()).sum::<usize>()
+            + self.node_values.values().map(|value| id + value_bytes(value)).sum::<usize>()
+            + self.children.values().map(|children| id * (1 + children.len())).sum::<usize>()
+            + self.metadata.values().map(|metadata| id + metadata_bytes(metadata)).sum::<usize>()
+            + self.nodes.values()
+                .map(|node| std::mem::size_of_val(node) + value_bytes(&node.value) + metadata_bytes(&node.metadata))
+                .sum::<usize>()
+            + id * (self.added_nodes.len() + self.roots.as_ref().map_or(0, Vec::len))
+    }
+}
+
+/// Returned by `GammaAST::decompress` when the AST cannot be fully decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Nodes that stay encoded after every decoding step, sorted by ID
+    Undecodable(Vec<u64>),
+    /// Interned metadata entry that names no dictionary entry
+    Metadata(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Undecodable(ids) => write!(f, "nodes {:?} cannot be decoded", ids),
+            DecodeError::Metadata(entry) => write!(f, "metadata entry {} is not in the dictionary", entry),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A compression transformation that can touch individual nodes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PassKind {
+    ValueCompression,
+    Deduplication,
+    PatternApplication,
+    /// Dictionary-encoding or dropping node metadata
+    Metadata,
+    /// Merging patterns shared across files
+    CrossFile,
+    /// Engine-specific pass not covered above
+    Custom(String),
+}
+
+/// Ordered record of the passes that changed each node, keyed by node ID
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompressionTrace {
+    pub passes: HashMap<u64, Vec<PassKind>>,
+}
+
+impl CompressionTrace {
+    /// Record that `pass` changed the node `node_id`
+    pub fn record(&mut self, node_id: u64, pass: PassKind) {
+        self.passes.entry(node_id).or_default().push(pass);
+    }
+    
+    /// Passes applied to a node, in the order they ran
+    pub fn passes_for(&self, node_id: u64) -> &[PassKind] {
+        self.passes.get(&node_id).map(Vec::as_slice).unwrap_or(&[])
+    }
+    
+    /// Check if no node was touched
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+}
+
+/// Bytes saved by each compression pass and for each node type
+///
+/// Sizes are node JSON encodings, as in `GammaAST::node_bytes`, so pattern
+/// storage is not counted. Savings are negative where a pass grew the AST.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetailedCompressionStats {
+    /// Bytes saved per pass, in the order the passes first ran
+    pub by_pass: Vec<(PassKind, i64)>,
+    /// Bytes saved per node type, keyed by `GammaNodeType::as_str`
+    pub by_node_type: HashMap<String, i64>,
+}
+
+impl DetailedCompressionStats {
+    /// Charge the size change from `before` to `after` to `pass`
+    pub fn record_pass(&mut self, pass: PassKind, before: &HashMap<u64, GammaNode>, after: &HashMap<u64, GammaNode>) {
+        let saved = self.record_nodes(before, after);
+        match self.by_pass.iter_mut().find(|(kind, _)| *kind == pass) {
+            Some((_, total)) => *total += saved,
+            None => self.by_pass.push((pass, saved)),
+        }
+    }
+    
+    /// Add each changed node's savings to its type, returning the total
+    ///
+    /// A node counts under its type in `before`, or in `after` if it is new.
+    fn record_nodes(&mut self, before: &HashMap<u64, GammaNode>, after: &HashMap<u64, GammaNode>) -> i64 {
+        let bytes = |node: Option<&GammaNode>| {
+            node.and_then(|node| serde_json::to_vec(node).ok()).map_or(0, |bytes| bytes.len() as i64)
+        };
+        
+        let added = after.keys().filter(|id| !before.contains_key(id));
+        let mut total = 0;
+        for id in before.keys().chain(added) {
+            let (old, new) = (before.get(id), after.get(id));
+            if old == new {
+                continue;
+            }
+            let Some(node) = old.or(new) else { continue };
+            let saved = bytes(old) - bytes(new);
+            *self.by_node_type.entry(node.node_type.as_str().into_owned()).or_default() += saved;
+            total += saved;
+        }
+        total
+    }
+    
+    /// Bytes saved by `pass` (0 if it did not run)
+    pub fn saved_by_pass(&self, pass: &PassKind) -> i64 {
+        self.by_pass.iter().find(|(kind, _)| kind == pass).map_or(0, |(_, saved)| *saved)
+    }
+    
+    /// Bytes saved on nodes of `node_type`
+    pub fn saved_by_node_type(&self, node_type: &GammaNodeType) -> i64 {
+        self.by_node_type.get(node_type.as_str().as_ref()).copied().unwrap_or(0)
+    }
+    
+    /// Bytes saved across all node types
+    pub fn total_saved(&self) -> i64 {
+        self.by_node_type.values().sum()
+    }
+}
+
+/// Returned by `GammaAST::try_add_node` when the node's ID is already taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateIdError {
+    /// The contested node ID
+    pub id: u64,
+}
+
+impl fmt::Display for DuplicateIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node ID {} is already in use", self.id)
+    }
+}
+
+impl std::error::Error for DuplicateIdError {}
+
+/// What a visitor wants the traversal to do after visiting a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitAction {
+    /// Go on to the node's children, then the rest of the tree
+    Continue,
+    /// Do not descend into this node's children
+    SkipChildren,
+    /// End the traversal immediately
+    Stop,
+}
+
+/// Read-only pass over a `GammaAST`, driven by `GammaAST::accept`
+pub trait Visitor {
+    fn visit_node(&mut self, node: &GammaNode) -> VisitAction;
+}
+
+/// Editing pass over a `GammaAST`, driven by `GammaAST::accept_mut`
+///
+/// Children are read after the visit, so edits to `children` decide what is walked next.
+pub trait VisitorMut {
+    fn visit_node_mut(&mut self, node: &mut GammaNode) -> VisitAction;
+}
+
+/// Structural template searched for by `GammaAST::find_matches`
+///
+/// Children are matched position by position, so a query only matches nodes
+/// with exactly as many children as it lists.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternQuery {
+    /// A node of this type, with this direct value if one is given
+    Node {
+        node_type: GammaNodeType,
+        value: Option<String>,
+        children: Vec<PatternQuery>,
+    },
+    /// A node of any type whose children match the sub-queries
+    Any(Vec<PatternQuery>),
+    /// Any single subtree, whatever its shape
+    AnySubtree,
+}
+
+impl PatternQuery {
+    /// Match a node of `node_type` with any value
+    pub fn node(node_type: GammaNodeType, children: Vec<PatternQuery>) -> Self {
+        PatternQuery::Node { node_type, value: None, children }
+    }
+    
+    /// Match a node of `node_type` whose value is `Direct(value)`
+    pub fn with_value(node_type: GammaNodeType, value: &str, children: Vec<PatternQuery>) -> Self {
+        PatternQuery::Node { node_type, value: Some(value.to_string()), children }
+    }
+}
+
+/// Compression statistics and metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionStats {
+    /// Original size in bytes
+    pub original_size: usize,
+    /// Compressed size in bytes
+    pub compressed_size: usize,
+    /// Compression ratio achieved
+    pub compression_ratio: f64,
+    /// Number of patterns identified
+    pub patterns_found: usize,
+    /// Memory usage optimization
+    pub memory_optimization: f64,
+}
+
+/// Pattern registry for efficient pattern reuse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternRegistry {
+    /// Pattern signatures mapped to pattern IDs
+    pub signatures: HashMap<u64, u64>,
+    /// Pattern frequency tracking
+    pub frequencies: HashMap<u64, u32>,
+    /// Pattern size distribution
+    pub size_distribution: HashMap<usize, u32>,
+}
+
+impl GammaAST {
+    /// Create a new Γ-AST
+    pub fn new() -> Self {
+        Self {
+            roots: Vec::new(),
+            nodes: HashMap::new(),
+            patterns: HashMap::new(),
+            source_language: String::new(),
+            compression_stats: CompressionStats::new(),
+            pattern_registry: PatternRegistry::new(),
+            custom_types: CustomTypeTable::default(),
+            metadata_dictionary: None,
+            plain_metadata: HashSet::new(),
+            reconstruction: None,
+        }
+    }
+    
+    /// Flatten a parser AST into a Γ-AST
+    ///
+    /// Nodes get sequential IDs from 1 in preorder, so the same tree always
+    /// gets the same IDs. Values become `Direct`, locations and metadata are
+    /// kept, and node types without a Γ-AST counterpart become `Custom` with
+    /// the parser's type name. Walks with an explicit stack, like `AST::walk`.
+    pub fn from_ast(ast: &AST) -> GammaAST {
+        let mut gamma_ast = GammaAST::new();
+        gamma_ast.source_language = ast.source_language.clone().unwrap_or_default();
+        
+        let mut next_id = 1;
+        let mut stack: Vec<(&Node, Option<u64>)> = ast.roots.iter().rev().map(|root| (root, None)).collect();
+        while let Some((node, parent)) = stack.pop() {
+            let id = next_id;
+            next_id += 1;
+            match parent.and_then(|parent| gamma_ast.nodes.get_mut(&parent)) {
+                Some(parent) => parent.children.push(id),
+                None => gamma_ast.add_root(id),
+            }
+            gamma_ast.add_node(GammaNode {
+                id,
+                node_type: GammaNodeType::from(&node.node_type),
+                value: GammaValue::Direct(node.value.clone()),
+                location: node.location.clone(),
+                children: Vec::with_capacity(node.children.len()),
+                metadata: node.metadata.clone(),
+                compression_level: CompressionLevel::None,
+            });
+            stack.extend(node.children.iter().rev().map(|child| (child, Some(id))));
+        }
+        gamma_ast
+    }
+    
+    /// Set the source language
+    pub fn set_source_language(&mut self, language: String) {
+        self.source_language = language;
+    }
+    
+    /// Add a root node
+    pub fn add_root(&mut self, node_id: u64) {
+        self.roots.push(node_id);
+    }
+    
+    /// Add a node to the AST, overwriting any node with the same ID
+    ///
+    /// Use `try_add_node` to reject duplicates instead.
+    pub fn add_node(&mut self, node: GammaNode) {
+        self.nodes.insert(node.id, node);
+    }
+    
+    /// Add a node, leaving the AST unchanged if its ID is already in use
+    pub fn try_add_node(&mut self, node: GammaNode) -> Result<(), DuplicateIdError> {
+        match self.nodes.entry(node.id) {
+            Entry::Occupied(_) => Err(DuplicateIdError { id: node.id }),
+            Entry::Vacant(slot) => {
+                slot.insert(node);
+                Ok(())
+            }
+        }
+    }
+    
+    /// Insert a node, returning the one it replaced, if any
+    pub fn replace_node(&mut self, node: GammaNode) -> Option<GammaNode> {
+        self.nodes.insert(node.id, node)
+    }
+    
+    /// Get a node by ID
+    pub fn get_node(&self, id: u64) -> Option<&GammaNode> {
+        self.nodes.get(&id)
+    }
+    
+    /// Get a mutable reference to a node
+    pub fn get_node_mut(&mut self, id: u64) -> Option<&mut GammaNode> {
+        self.nodes.get_mut(&id)
+    }
+    
+    /// Get the custom type name of a node type, resolving interned IDs
+    pub fn custom_type_name<'a>(&'a self, node_type: &'a GammaNodeType) -> Option<&'a str> {
+        match node_type {
+            GammaNodeType::Custom(name) => Some(name),
+            GammaNodeType::InternedCustom(id) => self.custom_types.name(*id),
+            _ => None,
+        }
+    }
+    
+    /// Get a node's type with interned custom types expanded back to `Custom(String)`
+    pub fn resolved_node_type(&self, id: u64) -> Option<GammaNodeType> {
+        let node = self.get_node(id)?;
+        Some(match &node.node_type {
+            GammaNodeType::InternedCustom(custom_id) => self.custom_types.name(*custom_id)
+                .map(|name| GammaNodeType::Custom(name.to_string()))
+                .unwrap_or_else(|| node.node_type.clone()),
+            other => other.clone(),
+        })
+    }
+    
+    /// Replace every `Custom(String)` node type with an interned ID
+    pub fn intern_custom_types(&mut self) {
+        for node in self.nodes.values_mut() {
+            if let GammaNodeType::Custom(name) = &node.node_type {
+                node.node_type = GammaNodeType::InternedCustom(self.custom_types.intern(name));
+            }
+        }
+    }
+    
+    /// Expand every interned custom type back into `Custom(String)`
+    pub fn expand_custom_types(&mut self) {
+        for node in self.nodes.values_mut() {
+            if let GammaNodeType::InternedCustom(id) = node.node_type {
+                if let Some(name) = self.custom_types.name(id) {
+                    node.node_type = GammaNodeType::Custom(name.to_string());
+                }
+            }
+        }
+    }
+    
+    /// Rewrite custom node types whose name is a key of `map` to the mapped type
+    ///
+    /// Run before compression so constructs that front-ends name differently
+    /// ("func", "function") dedup and share patterns. Interned custom types are
+    /// matched by name as well, and stay interned when mapped to another
+    /// `Custom` name. Returns the number of nodes changed.
+    pub fn remap_node_types(&mut self, map: &HashMap<String, GammaNodeType>) -> usize {
+        let mut changed = 0;
+        for node in self.nodes.values_mut() {
+            let (name, interned) = match &node.node_type {
+                GammaNodeType::Custom(name) => (name.as_str(), false),
+                GammaNodeType::InternedCustom(id) => match self.custom_types.name(*id) {
+                    Some(name) => (name, true),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let Some(target) = map.get(name) else { continue };
+            
+            let target = match target {
+                GammaNodeType::Custom(target_name) if interned => {
+                    GammaNodeType::InternedCustom(self.custom_types.intern(target_name))
+                }
+                other => other.clone(),
+            };
+            if node.node_type != target {
+                node.node_type = target;
+                changed += 1;
+            }
+        }
+        changed
+    }
+    
+    /// Replace every metadata key and value with its entry number in `metadata_dictionary`
+    ///
+    /// Entries are assigned in node ID then key order, so the encoding is
+    /// reproducible. Does nothing if metadata is already interned.
+    pub fn intern_metadata(&mut self) {
+        self.intern_metadata_except(&HashSet::new());
+    }
+    
+    /// Like `intern_metadata`, but leave the metadata of the `skip` nodes as plain text
+    ///
+    /// The skipped IDs are kept in `plain_metadata`, which `expand_metadata` passes over.
+    pub fn intern_metadata_except(&mut self, skip: &HashSet<u64>) {
+        if self.metadata_dictionary.is_some() {
+            return;
+        }
+        let mut dictionary = PatternDictionary::new(0);
+        let mut ids: Vec<u64> = self.nodes.keys().copied().filter(|id| !skip.contains(id)).collect();
+        ids.sort_unstable();
+        for id in ids {
+            let node = self.nodes.get_mut(&id).expect("id taken from the node map");
+            let mut entries: Vec<(String, String)> = node.metadata.drain().collect();
+            entries.sort_unstable();
+            node.metadata = entries.into_iter()
+                .map(|(key, value)| (dictionary.insert(&key).to_string(), dictionary.insert(&value).to_string()))
+                .collect();
+        }
+        self.metadata_dictionary = Some(dictionary);
+        self.plain_metadata = skip.iter().copied().filter(|id| self.nodes.contains_key(id)).collect();
+    }
+    
+    /// Restore metadata interned by `intern_metadata`
+    ///
+    /// Fails with the offending entry text if a key or value does not name a
+    /// dictionary entry; metadata is left interned in that case.
+    pub fn expand_metadata(&mut self) -> Result<(), String> {
+        let Some(dictionary) = &self.metadata_dictionary else {
+            return Ok(());
+        };
+        let lookup = |entry: &String| {
+            entry.parse().ok().and_then(|entry| dictionary.get(entry)).map(str::to_string).ok_or_else(|| entry.clone())
+        };
+        let mut expanded = Vec::with_capacity(self.nodes.len());
+        for (id, node) in self.nodes.iter().filter(|(id, _)| !self.plain_metadata.contains(id)) {
+            let metadata = node.metadata.iter()
+                .map(|(key, value)| Ok((lookup(key)?, lookup(value)?)))
+                .collect::<Result<HashMap<String, String>, String>>()?;
+            expanded.push((*id, metadata));
+        }
+        for (id, metadata) in expanded {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.metadata = metadata;
+            }
+        }
+        self.metadata_dictionary = None;
+        self.plain_metadata.clear();
+        Ok(())
+    }
+    
+    /// Add a recognized pattern
+    pub fn add_pattern(&mut self, pattern: Pattern) {
+        debug_assert!(!self.patterns.contains_key(&pattern.id), "duplicate pattern ID {}", pattern.id);
+        self.patterns.insert(pattern.id, pattern.clone());
+        self.pattern_registry.signatures.insert(pattern.signature, pattern.id);
+        self.pattern_registry.frequencies.insert(pattern.id, pattern.frequency);
+        self.pattern_registry.size_distribution.entry(pattern.size).and_modify(|e| *e += 1).or_insert(1);
+    }
+    
+    /// Find patterns in the AST
+    pub fn find_patterns(&mut self) -> Vec<u64> {
+        let mut found_patterns = Vec::new();
+        
+        // Analyze node structures for common patterns
+        for (id, _node) in &self.nodes {
+            if let Some(pattern_id) = self.identify_pattern(*id) {
+                found_patterns.push(pattern_id);
+            }
+        }
+        
+        found_patterns
+    }
+    
+    /// Identify if a node matches a known pattern
+    fn identify_pattern(&self, node_id: u64) -> Option<u64> {
+        // This is a simplified pattern identification
+        // In a full implementation, this would use sophisticated pattern matching algorithms
+        if let Some(node) = self.get_node(node_id) {
+            // Check if this node structure matches any known patterns
+            for (pattern_id, pattern) in &self.patterns {
+                if self.matches_pattern(node, pattern) {
+                    return Some(*pattern_id);
+                }
+            }
+        }
+        None
+    }
+    
+    /// Check if a node matches a pattern; a pattern without nodes matches nothing
+    fn matches_pattern(&self, node: &GammaNode, pattern: &Pattern) -> bool {
+        // Simplified pattern matching - in reality this would be much more sophisticated
+        pattern.nodes.first().is_some_and(|first| {
+            node.node_type == first.node_type && node.children.len() == first.children.len()
+        })
+    }
+    
+    /// IDs of all nodes whose subtree matches `query`, sorted
+    ///
+    /// Custom node types are compared by name, so interned and plain
+    /// `Custom` types match each other.
+    pub fn find_matches(&self, query: &PatternQuery) -> Vec<u64> {
+        let mut matches: Vec<u64> = self.nodes.keys()
+            .copied()
+            .filter(|&id| self.matches_query(id, query))
+            .collect();
+        matches.sort_unstable();
+        matches
+    }
+    
+    /// Check the subtree at `id` against `query`; recursion is bounded by the query's depth
+    fn matches_query(&self, id: u64, query: &PatternQuery) -> bool {
+        let Some(node) = self.get_node(id) else {
+            return false;
+        };
+        let children = match query {
+            PatternQuery::AnySubtree => return true,
+            PatternQuery::Any(children) => children,
+            PatternQuery::Node { node_type, value, children } => {
+                let type_matches = match node_type {
+                    GammaNodeType::Custom(_) => self.resolved_node_type(id).as_ref() == Some(node_type),
+                    _ => &node.node_type == node_type,
+                };
+                let value_matches = value.as_ref()
+                    .is_none_or(|value| matches!(&node.value, GammaValue::Direct(direct) if direct == value));
+                if !type_matches || !value_matches {
+                    return false;
+                }
+                children
+            }
+        };
+        node.children.len() == children.len()
+            && node.children.iter().zip(children).all(|(&child, query)| self.matches_query(child, query))
+    }
+    
+    /// Compute the patch that turns this AST into `newer`
+    pub fn diff(&self, newer: &GammaAST) -> AstPatch {
+        let mut patch = AstPatch::default();
+        
+        for (id, node) in &newer.nodes {
+            match self.nodes.get(id) {
+                None => patch.added.push(node.clone()),
+                Some(old) if old != node => patch.modified.push(node.clone()),
+                Some(_) => {}
+            }
+        }
+        for id in self.nodes.keys() {
+            if !newer.nodes.contains_key(id) {
+                patch.removed.push(*id);
+            }
+        }
+        if self.roots != newer.roots {
+            patch.roots = Some(newer.roots.clone());
+        }
+        
+        // Keep patches deterministic regardless of HashMap ordering
+        patch.added.sort_by_key(|node| node.id);
+        patch.modified.sort_by_key(|node| node.id);
+        patch.removed.sort_unstable();
+        patch
+    }
+    
+    /// Apply a patch produced by `diff`
+    pub fn apply_patch(&mut self, patch: &AstPatch) {
+        for id in &patch.removed {
+            self.nodes.remove(id);
+        }
+        for node in patch.added.iter().chain(patch.modified.iter()) {
+            self.nodes.insert(node.id, node.clone());
+        }
+        if let Some(roots) = &patch.roots {
+            self.roots = roots.clone();
+        }
+    }
+    
+    /// Swap the subtree under `root` for the contents of `new`
+    ///
+    /// Nodes of the old subtree are removed unless they are still reachable from
+    /// outside it (a shared DAG child, another root, or a `PatternRef` target),
+    /// in which case they and everything below them stay. `new`'s nodes are
+    /// inserted under fresh IDs above every current node and pattern ID; children
+    /// and `PatternRef`s between them are remapped and dangling children dropped.
+    /// `new`'s patterns are not carried over, so it should be uncompressed.
+    /// Every reference to `root` (child lists and `roots`) is replaced by the
+    /// remapped roots of `new`, in order.
+    ///
+    /// Returns the new root IDs, or `None` if `root` does not exist or there are
+    /// not enough IDs left above the current maximum; `self` is unchanged then.
+    pub fn replace_subtree(&mut self, root: u64, mut new: GammaAST) -> Option<Vec<u64>> {
+        if !self.nodes.contains_key(&root) {
+            return None;
+        }
+        
+        // Allocate before touching `self` so running out of IDs leaves it intact
+        let used: HashSet<u64> = self.nodes.keys().chain(self.patterns.keys()).copied().collect();
+        let mut floor = used.iter().max().copied();
+        let ids = IdGenerator::seeded_from(self);
+        let mut new_ids: Vec<u64> = new.nodes.keys().copied().collect();
+        new_ids.sort_unstable();
+        let mut remap = HashMap::with_capacity(new_ids.len());
+        for old_id in new_ids {
+            let fresh = loop {
+                let id = ids.next_id();
+                // The generator wraps at u64::MAX; anything at or below the last ID means it did
+                if floor.is_some_and(|floor| id <= floor) {
+                    return None;
+                }
+                floor = Some(id);
+                if !used.contains(&id) {
+                    break id;
+                }
+            };
+            remap.insert(old_id, fresh);
+        }
+        
+        let reachable = |starts: Vec<u64>, within: &dyn Fn(u64) -> bool| {
+            let mut seen = HashSet::new();
+            let mut stack = starts;
+            while let Some(id) = stack.pop() {
+                if within(id) && seen.insert(id) {
+                    stack.extend(self.nodes[&id].children.iter().copied());
+                }
+            }
+            seen
+        };
+        let subtree = reachable(vec![root], &|id| self.nodes.contains_key(&id));
+        
+        // Anything in the subtree referenced from outside it has to survive
+        let external: Vec<u64> = self.nodes.values()
+            .filter(|node| !subtree.contains(&node.id))
+            .flat_map(|node| {
+                let target = match node.value {
+                    GammaValue::PatternRef(target) => Some(target),
+                    _ => None,
+                };
+                node.children.iter().copied().chain(target)
+            })
+            .chain(self.roots.iter().copied())
+            .filter(|id| *id != root)
+            .collect();
+        let kept = reachable(external, &|id| id != root && subtree.contains(&id));
+        for id in subtree.difference(&kept) {
+            self.nodes.remove(id);
+        }
+        
+        new.expand_custom_types();
+        let new_roots: Vec<u64> = new.roots.iter().filter_map(|id| remap.get(id).copied()).collect();
+        
+        let splice = |ids: &mut Vec<u64>| {
+            if ids.contains(&root) {
+                *ids = ids.iter()
+                    .flat_map(|&id| if id == root { new_roots.clone() } else { vec![id] })
+                    .collect();
+            }
+        };
+        for node in self.nodes.values_mut() {
+            splice(&mut node.children);
+        }
+        splice(&mut self.roots);
+        
+        for (_, mut node) in new.nodes {
+            node.id = remap[&node.id];
+            node.children = node.children.iter().filter_map(|child| remap.get(child).copied()).collect();
+            if let GammaValue::PatternRef(target) = node.value {
+                if let Some(&mapped) = remap.get(&target) {
+                    node.value = GammaValue::PatternRef(mapped);
+                }
+            }
+            self.nodes.insert(node.id, node);
+        }
+        
+        Some(new_roots)
+    }
+    
+    /// Remove every node `keep` rejects, reconnecting its children to its parents
+    ///
+    /// Each edge to a removed node is replaced, in place, by that node's own
+    /// children (recursively through chains of removed nodes); the same applies
+    /// to `roots`, which are deduplicated afterwards. Edges that loop back through
+    /// removed nodes are dropped. `PatternRef` values are not rewritten, so this
+    /// is meant to run before compression.
+    pub fn retain_nodes(&mut self, keep: impl Fn(&GammaNode) -> bool) {
+        let mut removed: Vec<u64> = self.nodes.values().filter(|node| !keep(node)).map(|node| node.id).collect();
+        if removed.is_empty() {
+            return;
+        }
+        removed.sort_unstable();
+        let removed_set: HashSet<u64> = removed.iter().copied().collect();
+        
+        // What each removed node expands to, computed bottom-up without recursion
+        let mut expansion: HashMap<u64, Vec<u64>> = HashMap::new();
+        for &start in &removed {
+            if expansion.contains_key(&start) {
+                continue;
+            }
+            let mut stack = vec![(start, 0usize)];
+            let mut on_path = HashSet::from([start]);
+            while let Some((id, next)) = stack.last_mut() {
+                let children = &self.nodes[id].children;
+                if let Some(&child) = children.get(*next) {
+                    *next += 1;
+                    if removed_set.contains(&child) && !expansion.contains_key(&child) && on_path.insert(child) {
+                        stack.push((child, 0));
+                    }
+                } else {
+                    let id = *id;
+                    let spliced = children.iter()
+                        .flat_map(|child| if removed_set.contains(child) {
+                            expansion.get(child).cloned().unwrap_or_default()
+                        } else {
+                            vec![*child]
+                        })
+                        .collect();
+                    expansion.insert(id, spliced);
+                    on_path.remove(&id);
+                    stack.pop();
+                }
+            }
+        }
+        
+        let splice = |ids: &[u64]| -> Vec<u64> {
+            ids.iter()
+                .flat_map(|id| expansion.get(id).cloned().unwrap_or_else(|| vec![*id]))
+                .collect()
+        };
+        for id in &removed {
+            self.nodes.remove(id);
+        }
+        for node in self.nodes.values_mut() {
+            if node.children.iter().any(|child| removed_set.contains(child)) {
+                node.children = splice(&node.children);
+            }
+        }
+        let mut seen = HashSet::new();
+        self.roots = splice(&self.roots).into_iter().filter(|root| seen.insert(*root)).collect();
+    }
+    
+    /// Copy a seeded random selection of whole subtrees, for cheap compressibility probes
+    ///
+    /// Subtrees are picked from the shallowest level with enough nodes that
+    /// `fraction` of them is at least one (starting at `roots`); each candidate
+    /// is kept when a hash of `seed` and its ID falls below `fraction`, so the
+    /// choice is reproducible and independent of map order. At least one
+    /// subtree is kept unless `fraction` is not positive or the AST is empty.
+    /// Kept subtrees are copied whole, under their original IDs, and become the
+    /// sample's roots. Patterns are not carried over, so this is meant to run
+    /// before compression.
+    pub fn sample(&self, fraction: f64, seed: u64) -> GammaAST {
+        let mut sample = GammaAST {
+            source_language: self.source_language.clone(),
+            custom_types: self.custom_types.clone(),
+            metadata_dictionary: self.metadata_dictionary.clone(),
+            plain_metadata: self.plain_metadata.clone(),
+            ..GammaAST::new()
+        };
+        if fraction.is_nan() || fraction <= 0.0 {
+            return sample;
+        }
+        
+        let mut candidates: Vec<u64> = self.roots.iter().copied().filter(|id| self.nodes.contains_key(id)).collect();
+        while (candidates.len() as f64) * fraction < 1.0 {
+            let mut seen = HashSet::new();
+            let next: Vec<u64> = candidates.iter()
+                .flat_map(|id| self.nodes[id].children.iter().copied())
+                .filter(|id| self.nodes.contains_key(id) && seen.insert(*id))
+                .collect();
+            if next.len() <= candidates.len() {
+                break;
+            }
+            candidates = next;
+        }
+        
+        let draw = |id: u64| splitmix64(seed ^ splitmix64(id));
+        let cutoff = (fraction.min(1.0) * u64::MAX as f64) as u64;
+        let mut chosen: Vec<u64> = candidates.iter().copied().filter(|id| draw(*id) <= cutoff).collect();
+        if chosen.is_empty() {
+            chosen.extend(candidates.iter().copied().min_by_key(|id| draw(*id)));
+        }
+        
+        let mut stack = chosen.clone();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if let Entry::Vacant(slot) = sample.nodes.entry(id) {
+                stack.extend(node.children.iter().copied());
+                slot.insert(node.clone());
+            }
+        }
+        // Dropping children outside the copied nodes keeps the sample self-contained
+        for node in sample.nodes.values_mut() {
+            node.children.retain(|child| self.nodes.contains_key(child));
+        }
+        sample.roots = chosen;
+        sample
+    }
+    
+    /// Find a loop in the `PatternRef` graph, if any
+    ///
+    /// A `PatternRef` points at a node when that ID exists and at a pattern otherwise
+    /// (the same rule `to_dot` uses); a pattern points wherever its nodes' `PatternRef`s
+    /// do. Returns the IDs along the first cycle found, searching in ID order.
+    pub fn find_reference_cycle(&self) -> Option<Vec<u64>> {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        enum Ref {
+            Node(u64),
+            Pattern(u64),
+        }
+        
+        let resolve = |value: &GammaValue| match *value {
+            GammaValue::PatternRef(target) if self.nodes.contains_key(&target) => Some(Ref::Node(target)),
+            GammaValue::PatternRef(target) if self.patterns.contains_key(&target) => Some(Ref::Pattern(target)),
+            _ => None,
+        };
+        let targets = |vertex: Ref| -> Vec<Ref> {
+            match vertex {
+                Ref::Node(id) => self.nodes.get(&id).and_then(|node| resolve(&node.value)).into_iter().collect(),
+                Ref::Pattern(id) => self.patterns.get(&id)
+                    .map(|pattern| pattern.nodes.iter().filter_map(|node| resolve(&node.value)).collect())
+                    .unwrap_or_default(),
+            }
+        };
+        let id_of = |vertex: &Ref| match *vertex {
+            Ref::Node(id) | Ref::Pattern(id) => id,
+        };
+        
+        let mut node_ids: Vec<u64> = self.nodes.keys().copied().collect();
+        node_ids.sort_unstable();
+        let mut pattern_ids: Vec<u64> = self.patterns.keys().copied().collect();
+        pattern_ids.sort_unstable();
+        let starts = node_ids.into_iter().map(Ref::Node).chain(pattern_ids.into_iter().map(Ref::Pattern));
+        
+        let mut finished: HashSet<Ref> = HashSet::new();
+        for start in starts {
+            if finished.contains(&start) {
+                continue;
+            }
+            // Iterative DFS; `path` mirrors the stack so a back edge yields the cycle directly
+            let mut stack = vec![(start, targets(start), 0usize)];
+            let mut path = vec![start];
+            while let Some((vertex, outgoing, next)) = stack.last_mut() {
+                if let Some(&target) = outgoing.get(*next) {
+                    *next += 1;
+                    if let Some(position) = path.iter().position(|on_path| *on_path == target) {
+                        return Some(path[position..].iter().map(id_of).collect());
+                    }
+                    if !finished.contains(&target) {
+                        stack.push((target, targets(target), 0));
+                        path.push(target);
+                    }
+                } else {
+                    finished.insert(*vertex);
+                    stack.pop();
+                    path.pop();
+                }
+            }
+        }
+        
+        None
+    }
+    
+    /// Replace every `DictRef` value with the direct value from the supplied dictionaries
+    ///
+    /// On failure the offending `(dict_id, entry)` is returned and nodes resolved
+    /// so far keep their direct values.
+    pub fn resolve_dict_refs(&mut self, dictionaries: &[PatternDictionary]) -> Result<(), (u32, u32)> {
+        for node in self.nodes.values_mut() {
+            if let GammaValue::DictRef { dict_id, entry } = node.value {
+                let value = dictionaries.iter()
+                    .find(|dictionary| dictionary.id == dict_id)
+                    .and_then(|dictionary| dictionary.get(entry))
+                    .ok_or((dict_id, entry))?;
+                node.value = GammaValue::Direct(value.to_string());
+            }
+        }
+        Ok(())
+    }
+    
+    /// Compression level recorded on a node, or `None` if there is no such node
+    pub fn compress_level_for_node(&self, id: u64) -> Option<CompressionLevel> {
+        self.get_node(id).map(|node| node.compression_level)
+    }
+    
+    /// Undo per-node compression, dispatching on each node's recorded level
+    ///
+    /// `Medium` nodes are restored from their pattern's snapshot first, then
+    /// `Light` references are followed to the node whose value they share.
+    /// Decoded nodes are reset to `CompressionLevel::None`. Returns the sorted
+    /// IDs of nodes that stay encoded: lossy levels and dangling references.
+    pub fn decompress_levels(&mut self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        let mut undecodable = Vec::new();
+        
+        for &id in &ids {
+            match self.compress_level_for_node(id) {
+                Some(CompressionLevel::Medium) => {
+                    let snapshot = match self.nodes[&id].value {
+                        GammaValue::PatternRef(pattern_id) => self.patterns.get(&pattern_id)
+                            .and_then(|pattern| pattern.nodes.iter().find(|node| node.id == id))
+                            .cloned(),
+                        _ => None,
+                    };
+                    match snapshot {
+                        Some(mut original) => {
+                            original.compression_level = CompressionLevel::None;
+                            self.nodes.insert(id, original);
+                        }
+                        None => undecodable.push(id),
+                    }
+                }
+                Some(CompressionLevel::Heavy) | Some(CompressionLevel::Maximum) => undecodable.push(id),
+                _ => {}
+            }
+        }
+        
+        let mut resolved = Vec::new();
+        for &id in &ids {
+            if self.compress_level_for_node(id) != Some(CompressionLevel::Light) {
+                continue;
+            }
+            match self.light_reference_value(id) {
+                Some(value) => resolved.push((id, value)),
+                None => undecodable.push(id),
+            }
+        }
+        for (id, value) in resolved {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.value = GammaValue::Direct(value);
+                node.compression_level = CompressionLevel::None;
+            }
+        }
+        
+        undecodable.sort_unstable();
+        undecodable
+    }
+    
+    /// Rebuild the AST as it was before compression
+    ///
+    /// Interned metadata is expanded, then the `reconstruction` table supplies
+    /// what the passes threw away (lossy values, cleared children, rewritten
+    /// metadata, removed nodes), and `decompress_levels` decodes the rest.
+    /// Patterns are left as compression produced them. Fails with
+    /// `DecodeError::Undecodable` listing the nodes that stay encoded, which
+    /// happens when a lossy pass ran without a table being recorded.
+    pub fn decompress(&self) -> Result<GammaAST, DecodeError> {
+        let mut ast = self.clone();
+        ast.expand_metadata().map_err(DecodeError::Metadata)?;
+        let table = ast.reconstruction.take().unwrap_or_default();
+        
+        for id in &table.added_nodes {
+            ast.nodes.remove(id);
+        }
+        for node in ast.nodes.values_mut() {
+            let value = table.node_values.get(&node.id).cloned().or_else(|| match node.value {
+                // `Light` and `Medium` references decode below; any other level means a value-table ID
+                GammaValue::PatternRef(key) if !matches!(node.compression_level, CompressionLevel::Light | CompressionLevel::Medium) => {
+                    table.values.get(&key).cloned().map(GammaValue::Direct)
+                }
+                GammaValue::CompressedHash(key) => table.hashes.get(&key).cloned().map(GammaValue::Direct),
+                _ => None,
+            });
+            let mut restored = false;
+            if let Some(value) = value {
+                node.value = value;
+                restored = true;
+            }
+            if let Some(children) = table.children.get(&node.id) {
+                node.children = children.clone();
+                restored = true;
+            }
+            if let Some(metadata) = table.metadata.get(&node.id) {
+                node.metadata = metadata.clone();
+                restored = true;
+            }
+            // A lossy node is decoded once the table has given back what its passes dropped
+            if restored && matches!(node.value, GammaValue::Direct(_)) {
+                node.compression_level = CompressionLevel::None;
+            }
+        }
+        ast.nodes.extend(table.nodes);
+        if let Some(roots) = table.roots {
+            ast.roots = roots;
+        }
+        
+        let mut undecodable = ast.decompress_levels();
+        let hashed: Vec<u64> = ast.nodes.values()
+            .filter(|node| matches!(node.value, GammaValue::CompressedHash(_)) && !undecodable.contains(&node.id))
+            .map(|node| node.id)
+            .collect();
+        if !undecodable.is_empty() || !hashed.is_empty() {
+            undecodable.extend(hashed);
+            undecodable.sort_unstable();
+            return Err(DecodeError::Undecodable(undecodable));
+        }
+        Ok(ast)
+    }
+    
+    /// Follow `Light` node references from `id` to the direct value they share
+    fn light_reference_value(&self, id: u64) -> Option<String> {
+        let mut visited = HashSet::new();
+        let mut current = id;
+        loop {
+            if !visited.insert(current) {
+                return None;
+            }
+            let node = self.get_node(current)?;
+            match &node.value {
+                GammaValue::Direct(value) => return Some(value.clone()),
+                GammaValue::PatternRef(target) if node.compression_level == CompressionLevel::Light => current = *target,
+                _ => return None,
+            }
+        }
+    }
+    
+    /// Original value behind a possibly encoded value, if this AST records it
+    fn resolve_value(&self, value: &GammaValue) -> Option<String> {
+        // Value-table IDs only ever sit on lossy nodes and must not be read as node IDs
+        let mut carriers = self.nodes.values().filter(|node| node.value == *value).peekable();
+        if matches!(value, GammaValue::PatternRef(_)) && carriers.peek().is_some()
+            && carriers.all(|node| matches!(node.compression_level, CompressionLevel::Heavy | CompressionLevel::Maximum))
+        {
+            return None;
+        }
+        
+        let mut visited = HashSet::new();
+        let mut current = value;
+        loop {
+            match current {
+                GammaValue::Direct(value) => return Some(value.clone()),
+                GammaValue::PatternRef(id) => {
+                    if !visited.insert(*id) {
+                        return None;
+                    }
+                    current = match self.patterns.get(id) {
+                        Some(pattern) => {
+                            let first = &pattern.nodes.first()?.value;
+                            if !pattern.nodes.iter().all(|node| node.value == *first) {
+                                return None;
+                            }
+                            first
+                        }
+                        None => &self.get_node(*id)?.value,
+                    };
+                }
+                GammaValue::CompressedHash(_) => {
+                    return self.nodes.values()
+                        .filter(|node| node.value == *current)
+                        .find_map(|node| node.metadata.get("original_value").or_else(|| node.metadata.get("original_string")))
+                        .cloned();
+                }
+                GammaValue::DictRef { dict_id, entry } => {
+                    return self.metadata_dictionary.as_ref()
+                        .filter(|dictionary| dictionary.id == *dict_id)
+                        .and_then(|dictionary| dictionary.get(*entry))
+                        .map(str::to_string);
+                }
+                GammaValue::None => return None,
+            }
+        }
+    }
+    
+    /// Pair every node with the passes that touched it, in node ID order
+    ///
+    /// Nodes the trace does not mention are included with an empty pass list.
+    pub fn compressed_view<'a>(&'a self, trace: &'a CompressionTrace) -> Vec<(&'a GammaNode, &'a [PassKind])> {
+        let mut view: Vec<(&GammaNode, &[PassKind])> = self.nodes.values()
+            .map(|node| (node, trace.passes_for(node.id)))
+            .collect();
+        view.sort_by_key(|(node, _)| node.id);
+        view
+    }
+    
+    /// Check that no root is also the child of another node
+    ///
+    /// Returns the offending root IDs, sorted, if any root is referenced as a child.
+    pub fn validate_roots(&self) -> Result<(), Vec<u64>> {
+        let roots: HashSet<u64> = self.roots.iter().copied().collect();
+        let mut offending: Vec<u64> = self.nodes.values()
+            .flat_map(|node| node.children.iter().copied())
+            .filter(|child| roots.contains(child))
+            .collect::<HashSet<u64>>()
+            .into_iter()
+            .collect();
+        
+        if offending.is_empty() {
+            Ok(())
+        } else {
+            offending.sort_unstable();
+            Err(offending)
+        }
+    }
+    
+    /// IDs of the nodes no node lists as a child, sorted, whatever `roots` says
+    ///
+    /// Nodes reachable only through a cycle have no true root and are not listed.
+    pub fn topological_roots(&self) -> Vec<u64> {
+        let children: HashSet<u64> = self.nodes.values()
+            .flat_map(|node| node.children.iter().copied())
+            .collect();
+        let mut roots: Vec<u64> = self.nodes.keys()
+            .copied()
+            .filter(|id| !children.contains(id))
+            .collect();
+        roots.sort_unstable();
+        roots
+    }
+    
+    /// Replace `roots` with `topological_roots`
+    ///
+    /// Existing roots that are true roots keep their order; missing ones are
+    /// appended by ID, and non-roots and unknown IDs are dropped.
+    pub fn fix_roots(&mut self) {
+        let true_roots = self.topological_roots();
+        let wanted: HashSet<u64> = true_roots.iter().copied().collect();
+        let mut seen = HashSet::new();
+        self.roots.retain(|id| wanted.contains(id) && seen.insert(*id));
+        self.roots.extend(true_roots.into_iter().filter(|id| !seen.contains(id)));
+    }
+    
+    /// Walk the AST depth-first from the roots, in order, calling `visitor` on each node
+    ///
+    /// Every reachable node is visited exactly once, so shared subtrees and
+    /// cyclic child edges are safe. Missing child IDs are skipped. Returns
+    /// `false` if the visitor stopped the walk early.
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<u64> = self.roots.iter().rev().copied().collect();
+        
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if !visited.insert(id) {
+                continue;
+            }
+            match visitor.visit_node(node) {
+                VisitAction::Continue => stack.extend(node.children.iter().rev().copied()),
+                VisitAction::SkipChildren => {}
+                VisitAction::Stop => return false,
+            }
+        }
+        
+        true
+    }
+    
+    /// Like `accept`, but the visitor may edit each node as it goes
+    pub fn accept_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<u64> = self.roots.iter().rev().copied().collect();
+        
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get_mut(&id) else { continue };
+            if !visited.insert(id) {
+                continue;
+            }
+            match visitor.visit_node_mut(node) {
+                VisitAction::Continue => stack.extend(node.children.iter().rev().copied()),
+                VisitAction::SkipChildren => {}
+                VisitAction::Stop => return false,
+            }
+        }
+        
+        true
+    }
+    
+    /// Pre-order walk from `root`, pairing each node with its ID path from `root` (inclusive)
+    ///
+    /// A node reachable along several paths is yielded once per path. Child
+    /// edges leading back onto the current path are skipped, so cycles end the
+    /// walk instead of looping; missing children are skipped too.
+    pub fn walk_with_path(&self, root: u64) -> impl Iterator<Item = (&GammaNode, Vec<u64>)> + '_ {
+        let mut stack = vec![(root, 0usize)];
+        let mut path: Vec<u64> = Vec::new();
+        std::iter::from_fn(move || {
+            while let Some((id, depth)) = stack.pop() {
+                path.truncate(depth);
+                let Some(node) = self.nodes.get(&id) else { continue };
+                if path.contains(&id) {
+                    continue;
+                }
+                path.push(id);
+                stack.extend(node.children.iter().rev().map(|&child| (child, depth + 1)));
+                return Some((node, path.clone()));
+            }
+            None
+        })
+    }
+    
+    /// Depth-first pre-order walk over every node reachable from `roots`
+    ///
+    /// Children are followed in order and each node is yielded once, at its
+    /// first visit, so shared subtrees and cycles cannot repeat it; missing
+    /// IDs are skipped.
+    pub fn iter_dfs(&self) -> impl Iterator<Item = &GammaNode> + '_ {
+        let mut stack: Vec<u64> = self.roots.iter().rev().copied().collect();
+        let mut visited = HashSet::new();
+        std::iter::from_fn(move || {
+            while let Some(id) = stack.pop() {
+                let Some(node) = self.nodes.get(&id) else { continue };
+                if !visited.insert(id) {
+                    continue;
+                }
+                stack.extend(node.children.iter().rev().copied());
+                return Some(node);
+            }
+            None
+        })
+    }
+    
+    /// Breadth-first walk over every node reachable from `roots`, level by level
+    ///
+    /// Like `iter_dfs`, each node is yielded once and missing IDs are skipped.
+    pub fn iter_bfs(&self) -> impl Iterator<Item = &GammaNode> + '_ {
+        let mut visited: HashSet<u64> = HashSet::new();
+        let mut queue: VecDeque<u64> = self.roots.iter().copied().filter(|&id| visited.insert(id)).collect();
+        std::iter::from_fn(move || {
+            while let Some(id) = queue.pop_front() {
+                let Some(node) = self.nodes.get(&id) else { continue };
+                queue.extend(node.children.iter().copied().filter(|&child| visited.insert(child)));
+                return Some(node);
+            }
+            None
+        })
+    }
+    
+    /// Length in nodes of the longest root-to-leaf path (0 for an empty AST)
+    ///
+    /// Child edges that lead back onto the current path are ignored, so cyclic
+    /// or malformed trees still terminate. Shared subtrees are measured once.
+    pub fn depth(&self) -> usize {
+        let mut longest: HashMap<u64, usize> = HashMap::new();
+        let mut on_path: HashSet<u64> = HashSet::new();
+        let mut depth = 0;
+        
+        for &root in &self.roots {
+            if !self.nodes.contains_key(&root) {
+                continue;
+            }
+            if !longest.contains_key(&root) {
+                let mut stack = vec![(root, 0usize)];
+                on_path.insert(root);
+                while let Some((id, next)) = stack.last_mut() {
+                    let children = self.nodes.get(id).map(|node| node.children.as_slice()).unwrap_or(&[]);
+                    if let Some(&child) = children.get(*next) {
+                        *next += 1;
+                        if self.nodes.contains_key(&child) && !on_path.contains(&child) && !longest.contains_key(&child) {
+                            on_path.insert(child);
+                            stack.push((child, 0));
+                        }
+                    } else {
+                        let id = *id;
+                        let below = children.iter().filter_map(|child| longest.get(child)).max().copied().unwrap_or(0);
+                        longest.insert(id, below + 1);
+                        on_path.remove(&id);
+                        stack.pop();
+                    }
+                }
+            }
+            depth = depth.max(longest[&root]);
+        }
+        
+        depth
+    }
+    
+    /// Level of every node reachable from the roots (roots are level 1)
+    ///
+    /// Levels come from a breadth-first walk, so a node reachable along several
+    /// paths is placed at its shallowest level and visited only once.
+    pub fn node_depths(&self) -> HashMap<u64, usize> {
+        let mut depths = HashMap::new();
+        let mut queue: VecDeque<(u64, usize)> = self.roots.iter().map(|&root| (root, 1)).collect();
+        
+        while let Some((id, depth)) = queue.pop_front() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if depths.contains_key(&id) {
+                continue;
+            }
+            depths.insert(id, depth);
+            for &child in &node.children {
+                if !depths.contains_key(&child) {
+                    queue.push_back((child, depth + 1));
+                }
+            }
+        }
+        
+        depths
+    }
+    
+    /// Largest number of nodes on any single level (see `node_depths`)
+    pub fn max_width(&self) -> usize {
+        let mut widths: HashMap<usize, usize> = HashMap::new();
+        for depth in self.node_depths().into_values() {
+            *widths.entry(depth).or_insert(0) += 1;
+        }
+        widths.into_values().max().unwrap_or(0)
+    }
+    
+    /// Seal the AST into a read-only view that threads can share without locking
+    pub fn freeze(self) -> Arc<FrozenGammaAST> {
+        Arc::new(FrozenGammaAST { ast: self })
+    }
+    
+    /// Serialize the AST to compact JSON
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+    
+    /// Serialize the AST to indented, human-readable JSON
+    pub fn to_json_pretty(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+    
+    /// Parse an AST previously written by `to_json` or `to_json_pretty`
+    pub fn from_json(json: &str) -> Result<GammaAST, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+    
+    /// Render the AST as a Graphviz DOT graph
+    ///
+    /// Nodes are labeled with their type and value. Child edges are solid;
+    /// `PatternRef` edges are dashed and point at the referenced node, or at a
+    /// separate pattern node when the reference is not a node ID.
+    pub fn to_dot(&self) -> String {
+        let mut ids: Vec<u64> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        
+        let mut dot = String::from("digraph gamma_ast {\n");
+        dot.push_str("    node [shape=box];\n");
+        
+        for id in &ids {
+            let node = &self.nodes[id];
+            let label = format!("#{} {}\n{}", id, node.node_type, node.value.to_string());
+            let style = if self.roots.contains(id) { ", style=bold" } else { "" };
+            dot.push_str(&format!("    n{} [label=\"{}\"{}];\n", id, escape_dot(&label), style));
+        }
+        
+        let mut pattern_targets = Vec::new();
+        for id in &ids {
+            let node = &self.nodes[id];
+            for child in &node.children {
+                dot.push_str(&format!("    n{} -> n{};\n", id, child));
+            }
+            if let GammaValue::PatternRef(target) = node.value {
+                if self.nodes.contains_key(&target) {
+                    dot.push_str(&format!("    n{} -> n{} [style=dashed, color=blue];\n", id, target));
+                } else {
+                    dot.push_str(&format!("    n{} -> p{} [style=dashed, color=blue];\n", id, target));
+                    pattern_targets.push(target);
+                }
+            }
+        }
+        
+        pattern_targets.sort_unstable();
+        pattern_targets.dedup();
+        for target in pattern_targets {
+            dot.push_str(&format!("    p{} [label=\"pattern {}\", shape=ellipse, color=blue];\n", target, target));
+        }
+        
+        dot.push_str("}\n");
+        dot
+    }
+    
+    /// Calculate compression statistics
+    pub fn calculate_compression_stats(&mut self) {
+        let original_size = self.calculate_original_size();
+        let compressed_size = self.calculate_compressed_size();
+        let compression_ratio = if original_size > 0 {
+            original_size as f64 / compressed_size as f64
+        } else {
+            1.0
+        };
+        
+        self.compression_stats = CompressionStats {
+            original_size,
             compressed_size,
             compression_ratio,
             patterns_found: self.patterns.len(),
@@ -295,140 +2307,1475 @@ impl GammaAST {
         };
     }
     
-    /// Calculate the original size (estimated)
-    fn calculate_original_size(&self) -> usize {
-        // Estimate original size based on node content
-        let mut size = 0;
-        for node in self.nodes.values() {
-            size += node.value.to_string().len();
-            size += node.children.len() * 8; // 8 bytes per child reference
-            size += node.metadata.len() * 16; // 16 bytes per metadata entry
+    /// Bytes saved per node type in `compressed` relative to this AST
+    ///
+    /// Only `by_node_type` is filled; the engine adds the per-pass breakdown
+    /// when `CompressionConfig::detailed_stats` is set.
+    pub fn compress_stats_detailed(&self, compressed: &GammaAST) -> DetailedCompressionStats {
+        let mut stats = DetailedCompressionStats::default();
+        stats.record_nodes(&self.nodes, &compressed.nodes);
+        stats
+    }
+    
+    /// Write the AST to `path` in the binary file format
+    ///
+    /// The file is `FILE_MAGIC`, the `FILE_FORMAT_VERSION` byte and then the
+    /// whole AST as CBOR, which keeps integer map keys and omitted optional
+    /// fields intact.
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&FILE_MAGIC)?;
+        writer.write_all(&[FILE_FORMAT_VERSION])?;
+        ciborium::into_writer(self, &mut writer).map_err(|e| match e {
+            ciborium::ser::Error::Io(e) => e,
+            ciborium::ser::Error::Value(message) => io::Error::new(io::ErrorKind::InvalidData, message),
+        })?;
+        writer.flush()
+    }
+    
+    /// Read an AST written by `save_to_file`
+    ///
+    /// Fails with `InvalidData` if the file does not start with `FILE_MAGIC`,
+    /// was written by another format version, or does not decode.
+    pub fn load_from_file(path: &Path) -> io::Result<GammaAST> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; 5];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => io::Error::new(io::ErrorKind::InvalidData, "file is too short to be a Γ-AST file"),
+            _ => e,
+        })?;
+        if header[..4] != FILE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a Γ-AST file"));
+        }
+        if header[4] != FILE_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Γ-AST file format version {} is not supported (expected {})", header[4], FILE_FORMAT_VERSION),
+            ));
+        }
+        ciborium::from_reader(reader).map_err(|e| match e {
+            ciborium::de::Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        })
+    }
+    
+    /// Content address of the subtree at `id`, or `None` if there is no such node
+    ///
+    /// Hashes type, value and, recursively, the children's content IDs, so
+    /// identical subtrees share an address wherever they sit and whatever
+    /// their node IDs; it is safe to key a deduplicating node store on.
+    /// Interned custom types are hashed by name, like `Custom`, so the address
+    /// does not depend on this AST's intern table. `memo`
+    /// keeps finished subtrees between calls. Children that are missing or lead
+    /// back into a cycle contribute their raw ID instead.
+    pub fn content_id(&self, id: u64, memo: &mut HashMap<u64, u64>) -> Option<u64> {
+        if !self.nodes.contains_key(&id) {
+            return None;
+        }
+        let mut visiting = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(&current) = stack.last() {
+            if memo.contains_key(&current) {
+                stack.pop();
+                continue;
+            }
+            let node = &self.nodes[&current];
+            if visiting.insert(current) {
+                // Children first; the node is finished when it is back on top
+                stack.extend(node.children.iter().filter(|child| {
+                    self.nodes.contains_key(child) && !memo.contains_key(child) && !visiting.contains(child)
+                }));
+                continue;
+            }
+            stack.pop();
+            let mut hasher = SignatureHasher::new();
+            let interned_name = match node.node_type {
+                GammaNodeType::InternedCustom(index) => self.custom_types.name(index),
+                _ => None,
+            };
+            match interned_name {
+                Some(name) => {
+                    hasher.write_str("Custom:");
+                    hasher.write_str(name);
+                    node.write_value(&mut hasher);
+                }
+                None => node.write_shape(&mut hasher),
+            }
+            hasher.write_u64(node.children.len() as u64);
+            for child in &node.children {
+                match memo.get(child) {
+                    Some(content_id) => {
+                        hasher.write_u64(0);
+                        hasher.write_u64(*content_id);
+                    }
+                    None => {
+                        hasher.write_u64(1);
+                        hasher.write_u64(*child);
+                    }
+                }
+            }
+            memo.insert(current, hasher.finish());
+        }
+        memo.get(&id).copied()
+    }
+    
+    /// Content IDs of every node, keyed by node ID
+    pub fn content_ids(&self) -> HashMap<u64, u64> {
+        let mut memo = HashMap::with_capacity(self.nodes.len());
+        for id in self.nodes.keys() {
+            self.content_id(*id, &mut memo);
+        }
+        memo
+    }
+    
+    /// Hash of everything compression reads, independent of map iteration order
+    ///
+    /// Covers the source language, custom type names, roots, every node (ID,
+    /// type, value, children, level, location and metadata), the patterns,
+    /// the metadata dictionary and the nodes it skips. Compression statistics and the pattern registry
+    /// are derived data and left out. Meant for caches within one process.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = SignatureHasher::new();
+        hasher.write_str(&self.source_language);
+        hasher.write_u64(self.custom_types.names.len() as u64);
+        for name in &self.custom_types.names {
+            hasher.write_str(name);
+        }
+        hasher.write_u64(self.roots.len() as u64);
+        for root in &self.roots {
+            hasher.write_u64(*root);
+        }
+        
+        let mut ids: Vec<u64> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        hasher.write_u64(ids.len() as u64);
+        for id in ids {
+            let node = &self.nodes[&id];
+            hasher.write_u64(id);
+            node.write_local_content(&mut hasher);
+            hasher.write_u64(node.compression_level as u64);
+            if let Some(location) = &node.location {
+                hasher.write_u64(location.line as u64);
+                hasher.write_u64(location.column as u64);
+                hasher.write_str(location.file.as_deref().unwrap_or_default());
+                let (end_line, end_column) = location.end().unwrap_or((0, 0));
+                hasher.write_u64(end_line as u64);
+                hasher.write_u64(end_column as u64);
+            }
+            let mut metadata: Vec<(&String, &String)> = node.metadata.iter().collect();
+            metadata.sort_unstable();
+            hasher.write_u64(metadata.len() as u64);
+            for (key, value) in metadata {
+                hasher.write_str(key);
+                hasher.write_str(value);
+            }
+        }
+        
+        let mut pattern_ids: Vec<u64> = self.patterns.keys().copied().collect();
+        pattern_ids.sort_unstable();
+        hasher.write_u64(pattern_ids.len() as u64);
+        for id in pattern_ids {
+            let pattern = &self.patterns[&id];
+            hasher.write_u64(id);
+            hasher.write_u64(pattern.signature);
+            hasher.write_u64(pattern.frequency as u64);
+            for node in &pattern.nodes {
+                node.write_local_content(&mut hasher);
+            }
+        }
+        
+        if let Some(dictionary) = &self.metadata_dictionary {
+            hasher.write_u64(dictionary.id as u64);
+            for entry in &dictionary.entries {
+                hasher.write_str(entry);
+            }
+            let mut plain: Vec<u64> = self.plain_metadata.iter().copied().collect();
+            plain.sort_unstable();
+            for id in plain {
+                hasher.write_u64(id);
+            }
+        }
+        hasher.finish()
+    }
+    
+    /// Check whether two ASTs agree on everything `content_hash` covers
+    ///
+    /// Confirms a hash match, since different ASTs can share a 64-bit hash.
+    pub fn same_content(&self, other: &GammaAST) -> bool {
+        let same_patterns = self.patterns.len() == other.patterns.len()
+            && self.patterns.iter().all(|(id, pattern)| other.patterns.get(id).is_some_and(|other| {
+                (other.signature, other.frequency, &other.nodes) == (pattern.signature, pattern.frequency, &pattern.nodes)
+            }));
+        let same_dictionary = match (&self.metadata_dictionary, &other.metadata_dictionary) {
+            (Some(dictionary), Some(other)) => (dictionary.id, &dictionary.entries) == (other.id, &other.entries),
+            (None, None) => true,
+            _ => false,
+        };
+        self.source_language == other.source_language
+            && self.custom_types.names == other.custom_types.names
+            && self.roots == other.roots
+            && self.nodes == other.nodes
+            && same_patterns
+            && same_dictionary
+            && self.plain_metadata == other.plain_metadata
+    }
+    
+    /// First way `restored`, a decompressed copy of this AST, differs from it, if any
+    ///
+    /// Roots must match, and every node must still exist with the same resolved
+    /// type, value and child list, and the same metadata when `compare_metadata`
+    /// is set. Extra synthetic nodes in `restored` are allowed.
+    pub fn round_trip_mismatch(&self, restored: &GammaAST, compare_metadata: bool) -> Option<String> {
+        if self.roots != restored.roots {
+            return Some(format!("roots changed from {:?} to {:?}", self.roots, restored.roots));
+        }
+        let mut ids: Vec<u64> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let (original, Some(node)) = (&self.nodes[&id], restored.nodes.get(&id)) else {
+                return Some(format!("node {} missing", id));
+            };
+            if node.children != original.children {
+                return Some(format!("node {} children changed", id));
+            }
+            if restored.resolved_node_type(id) != self.resolved_node_type(id) {
+                return Some(format!("node {} type changed", id));
+            }
+            if node.value != original.value {
+                return Some(format!("node {} value changed", id));
+            }
+            if compare_metadata && node.metadata != original.metadata {
+                return Some(format!("node {} metadata changed", id));
+            }
+        }
+        None
+    }
+    
+    /// Bytes of metadata keys and values on all nodes, plus any metadata dictionary entries
+    ///
+    /// Measures the bookkeeping passes attach, which can outweigh what they save.
+    pub fn total_metadata_bytes(&self) -> usize {
+        let on_nodes: usize = self.nodes.values()
+            .flat_map(|node| &node.metadata)
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        let in_dictionary: usize = self.metadata_dictionary.iter()
+            .flat_map(|dictionary| (0..dictionary.len() as u32).filter_map(|entry| dictionary.get(entry)))
+            .map(str::len)
+            .sum();
+        on_nodes + in_dictionary
+    }
+    
+    /// Serialized size in bytes of a single node (0 if it does not exist)
+    ///
+    /// Measured as the node's JSON encoding, the format `compress_source` emits,
+    /// so it includes the value, children, metadata and location but not the
+    /// node's descendants.
+    pub fn node_bytes(&self, id: u64) -> usize {
+        self.nodes.get(&id)
+            .and_then(|node| serde_json::to_vec(node).ok())
+            .map_or(0, |bytes| bytes.len())
+    }
+    
+    /// Serialized size of a node plus every node below it
+    ///
+    /// Shared descendants are counted once and cyclic child edges are not followed.
+    pub fn subtree_bytes(&self, id: u64) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![id];
+        let mut total = 0;
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(&id) else { continue };
+            if seen.insert(id) {
+                total += self.node_bytes(id);
+                stack.extend(node.children.iter().copied());
+            }
+        }
+        total
+    }
+    
+    /// Every node with its `node_bytes`, heaviest first (ties in ID order)
+    pub fn size_profile(&self) -> Vec<(u64, usize)> {
+        let mut profile: Vec<(u64, usize)> = self.nodes.keys().map(|&id| (id, self.node_bytes(id))).collect();
+        profile.sort_unstable_by(|(a_id, a_bytes), (b_id, b_bytes)| b_bytes.cmp(a_bytes).then(a_id.cmp(b_id)));
+        profile
+    }
+    
+    /// IDs of nodes whose value refers to a structural pattern in `patterns`, sorted
+    ///
+    /// A `PatternRef` whose ID names no recorded pattern is a value-table entry,
+    /// not pattern coverage, and does not count; nor do `DictRef` values.
+    pub fn covered_nodes(&self) -> Vec<u64> {
+        let mut covered: Vec<u64> = self.nodes.values()
+            .filter(|node| matches!(node.value, GammaValue::PatternRef(id) if self.patterns.contains_key(&id)))
+            .map(|node| node.id)
+            .collect();
+        covered.sort_unstable();
+        covered
+    }
+    
+    /// Fraction of nodes covered by a structural pattern (see `covered_nodes`), 0.0 for an empty AST
+    ///
+    /// A high byte ratio with low coverage usually means the ratio came from
+    /// something other than pattern compression.
+    pub fn pattern_coverage(&self) -> f64 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        self.covered_nodes().len() as f64 / self.nodes.len() as f64
+    }
+    
+    /// Calculate the original size (estimated)
+    fn calculate_original_size(&self) -> usize {
+        // Estimate original size based on node content
+        let mut size = 0;
+        for node in self.nodes.values() {
+            size += node.value.to_string().len();
+            size += node.children.len() * 8; // 8 bytes per child reference
+            size += node.metadata.len() * 16; // 16 bytes per metadata entry
+        }
+        size
+    }
+    
+    /// Calculate the compressed size
+    fn calculate_compressed_size(&self) -> usize {
+        // Calculate actual compressed size
+        let mut size = 0;
+        for node in self.nodes.values() {
+            size += 8; // Node ID
+            size += 1; // Node type (enum)
+            size += match &node.value {
+                GammaValue::Direct(s) => s.len(),
+                GammaValue::PatternRef(_) => 8,
+                GammaValue::CompressedHash(_) => 8,
+                GammaValue::DictRef { .. } => 8,
+                GammaValue::None => 0,
+            };
+            size += node.children.len() * 8; // Child references
+            size += node.metadata.len() * 8; // Compressed metadata
+        }
+        size
+    }
+}
+
+/// Escape a string for use inside a double-quoted DOT label
+fn escape_dot(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Immutable Γ-AST produced by `GammaAST::freeze`
+///
+/// Derefs to `GammaAST`, so every `&self` method (lookups, traversal, stats)
+/// is available while nothing can be mutated. Share it by cloning the `Arc`.
+#[derive(Debug)]
+pub struct FrozenGammaAST {
+    ast: GammaAST,
+}
+
+impl FrozenGammaAST {
+    /// Copy the AST back into an editable `GammaAST`
+    pub fn thaw(&self) -> GammaAST {
+        self.ast.clone()
+    }
+}
+
+impl Deref for FrozenGammaAST {
+    type Target = GammaAST;
+    
+    fn deref(&self) -> &GammaAST {
+        &self.ast
+    }
+}
+
+impl fmt::Display for FrozenGammaAST {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ast.fmt(f)
+    }
+}
+
+impl CompressionStats {
+    /// Create new compression stats
+    pub fn new() -> Self {
+        Self {
+            original_size: 0,
+            compressed_size: 0,
+            compression_ratio: 1.0,
+            patterns_found: 0,
+            memory_optimization: 1.0,
+        }
+    }
+    
+    /// Get compression percentage
+    pub fn compression_percentage(&self) -> f64 {
+        space_saving_percent(self.original_size, self.compressed_size)
+    }
+}
+
+/// Percentage of `original_size` saved, negative when the output grew
+///
+/// Returns 0.0 for an empty original rather than dividing by zero.
+pub fn space_saving_percent(original_size: usize, compressed_size: usize) -> f64 {
+    if original_size > 0 {
+        if compressed_size <= original_size {
+            ((original_size - compressed_size) as f64 / original_size as f64) * 100.0
+        } else {
+            // If compressed size is larger, that means expansion occurred
+            -((compressed_size - original_size) as f64 / original_size as f64) * 100.0
+        }
+    } else {
+        0.0
+    }
+}
+
+impl PatternRegistry {
+    /// Create a new pattern registry
+    pub fn new() -> Self {
+        Self {
+            signatures: HashMap::new(),
+            frequencies: HashMap::new(),
+            size_distribution: HashMap::new(),
+        }
+    }
+    
+    /// Merge another registry into this one
+    ///
+    /// Patterns with matching signatures are combined and their frequencies summed.
+    /// Patterns whose IDs clash with an existing, different pattern get a fresh ID.
+    /// Size distribution counts are summed. Returns the mapping from `other`'s
+    /// pattern IDs to their IDs in the merged registry.
+    pub fn merge(&mut self, other: &PatternRegistry) -> HashMap<u64, u64> {
+        let mut used_ids: HashSet<u64> = self.frequencies.keys()
+            .chain(self.signatures.values())
+            .copied()
+            .collect();
+        let mut next_id = used_ids.iter().max().and_then(|max| max.checked_add(1)).unwrap_or(0);
+        let mut remap = HashMap::new();
+        
+        // Visit signatures in a stable order so fresh IDs are deterministic
+        let mut other_signatures: Vec<(u64, u64)> = other.signatures.iter().map(|(s, id)| (*s, *id)).collect();
+        other_signatures.sort_unstable();
+        
+        for (signature, other_id) in other_signatures {
+            let merged_id = if let Some(&existing) = self.signatures.get(&signature) {
+                existing
+            } else {
+                let id = if used_ids.contains(&other_id) {
+                    Self::fresh_id(&used_ids, &mut next_id)
+                } else {
+                    other_id
+                };
+                self.signatures.insert(signature, id);
+                id
+            };
+            used_ids.insert(merged_id);
+            remap.insert(other_id, merged_id);
+        }
+        
+        // Patterns tracked by frequency only (no signature) keep their ID unless it clashes
+        let mut unsigned: Vec<u64> = other.frequencies.keys()
+            .filter(|id| !remap.contains_key(id))
+            .copied()
+            .collect();
+        unsigned.sort_unstable();
+        for other_id in unsigned {
+            let merged_id = if used_ids.contains(&other_id) {
+                Self::fresh_id(&used_ids, &mut next_id)
+            } else {
+                other_id
+            };
+            used_ids.insert(merged_id);
+            remap.insert(other_id, merged_id);
+        }
+        
+        for (other_id, frequency) in &other.frequencies {
+            let merged_id = remap[other_id];
+            *self.frequencies.entry(merged_id).or_insert(0) += frequency;
+        }
+        
+        for (size, count) in &other.size_distribution {
+            *self.size_distribution.entry(*size).or_insert(0) += count;
+        }
+        
+        remap
+    }
+    
+    /// Take the first ID at or after `next` that is not in `used`, wrapping past `u64::MAX` to 0
+    ///
+    /// `used` holds fewer than 2^64 IDs, so the search always finds one.
+    fn fresh_id(used: &HashSet<u64>, next: &mut u64) -> u64 {
+        while used.contains(next) {
+            *next = next.checked_add(1).unwrap_or(0);
+        }
+        let fresh = *next;
+        *next = next.checked_add(1).unwrap_or(0);
+        fresh
+    }
+    
+    /// Get the most frequent patterns
+    pub fn get_top_patterns(&self, limit: usize) -> Vec<(u64, u32)> {
+        let mut patterns: Vec<(u64, u32)> = self.frequencies.iter()
+            .map(|(id, freq)| (*id, *freq))
+            .collect();
+        patterns.sort_by(|a, b| b.1.cmp(&a.1));
+        patterns.truncate(limit);
+        patterns
+    }
+    
+    /// Get patterns by size range
+    pub fn get_patterns_by_size(&self, min_size: usize, max_size: usize) -> Vec<u64> {
+        self.size_distribution.iter()
+            .filter(|(size, _)| **size >= min_size && **size <= max_size)
+            .flat_map(|(_, count)| {
+                self.frequencies.iter()
+                    .filter(|(_, freq)| **freq > 0)
+                    .map(|(id, _)| *id)
+                    .take(*count as usize)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for GammaAST {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Γ-AST (Gamma AST)")?;
+        writeln!(f, "Source Language: {}", self.source_language)?;
+        writeln!(f, "Roots: {}", self.roots.len())?;
+        writeln!(f, "Nodes: {}", self.nodes.len())?;
+        writeln!(f, "Patterns: {}", self.patterns.len())?;
+        writeln!(f, "Compression Ratio: {:.2}x", self.compression_stats.compression_ratio)?;
+        writeln!(f, "Compression: {:.1}%", self.compression_stats.compression_percentage())?;
+        Ok(())
+    }
+}
+
+/// Collect nodes into a new AST whose roots are its `topological_roots`
+impl FromIterator<GammaNode> for GammaAST {
+    fn from_iter<I: IntoIterator<Item = GammaNode>>(nodes: I) -> Self {
+        let mut ast = GammaAST::new();
+        ast.extend(nodes);
+        ast
+    }
+}
+
+/// Insert nodes, replacing any with the same ID, then `fix_roots`
+impl Extend<GammaNode> for GammaAST {
+    fn extend<I: IntoIterator<Item = GammaNode>>(&mut self, nodes: I) {
+        self.nodes.extend(nodes.into_iter().map(|node| (node.id, node)));
+        self.fix_roots();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_gamma_ast_creation() {
+        let ast = GammaAST::new();
+        assert_eq!(ast.roots.len(), 0);
+        assert_eq!(ast.nodes.len(), 0);
+        assert_eq!(ast.patterns.len(), 0);
+    }
+    
+    #[test]
+    fn test_node_addition() {
+        let mut ast = GammaAST::new();
+        let node = GammaNode::builder(1, GammaNodeType::Literal)
+            .direct("42")
+            .compression_level(CompressionLevel::Light)
+            .build();
+        
+        ast.add_node(node);
+        assert_eq!(ast.nodes.len(), 1);
+        assert!(ast.get_node(1).is_some());
+    }
+    
+    #[test]
+    fn test_structural_hash() {
+        let node = |node_type: GammaNodeType, value: &str, children: Vec<u64>| GammaNode::builder(1, node_type)
+            .direct(value)
+            .children(children)
+            .build();
+        
+        let base = node(GammaNodeType::Call, "print", vec![2]);
+        assert_eq!(base.structural_hash(), node(GammaNodeType::Call, "print", vec![7]).structural_hash());
+        assert_ne!(base.structural_hash(), node(GammaNodeType::Variable, "print", vec![2]).structural_hash());
+        assert_ne!(base.structural_hash(), node(GammaNodeType::Call, "printf", vec![2]).structural_hash());
+        assert_ne!(base.structural_hash(), node(GammaNodeType::Call, "print", vec![]).structural_hash());
+        
+        // Length prefixes keep field boundaries distinct
+        let mut split_a = SignatureHasher::new();
+        split_a.write_str("ab");
+        split_a.write_str("c");
+        let mut split_b = SignatureHasher::new();
+        split_b.write_str("a");
+        split_b.write_str("bc");
+        assert_ne!(split_a.finish(), split_b.finish());
+    }
+    
+    #[test]
+    fn test_signature_hasher_paths_agree() {
+        // The tables implement CRC32-C: check value over "123456789" with the standard inversions
+        let check = b"123456789".iter().fold(u32::MAX, |crc, &byte| {
+            CRC32C_TABLES[0][((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+        });
+        assert_eq!(!check, 0xe306_9283);
+        
+        for word in [0, 1, u64::MAX, 0x0123_4567_89ab_cdef, 0x8000_0000_0000_0000] {
+            for crc in [0, u32::MAX, 0xdead_beef] {
+                let bytewise = word.to_le_bytes().iter().fold(crc, |crc, &byte| {
+                    CRC32C_TABLES[0][((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+                });
+                assert_eq!(crc32c_software(crc, word), bytewise);
+                #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+                if std::arch::is_x86_feature_detected!("sse4.2") {
+                    // SAFETY: SSE4.2 was just detected
+                    assert_eq!(unsafe { crc32c_hardware(crc, word) }, bytewise);
+                }
+            }
+        }
+        
+        // Whichever path `new` picks, signatures match the table-driven one
+        let sign = |mut hasher: SignatureHasher| {
+            hasher.write_str("a node value longer than one word");
+            hasher.write_u64(42);
+            hasher.finish()
+        };
+        #[allow(unused_mut)]
+        let mut software = SignatureHasher::new();
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            software.hardware = false;
+        }
+        assert_eq!(sign(SignatureHasher::new()), sign(software));
+        
+        // Custom type names hash without allocating but stay distinct
+        let custom = |name: &str| GammaNode::builder(1, GammaNodeType::Custom(name.to_string())).build().structural_hash();
+        assert_ne!(custom("Lambda"), custom("Decorator"));
+        assert_ne!(custom("Lambda"), GammaNode::builder(1, GammaNodeType::InternedCustom(0)).build().structural_hash());
+    }
+    
+    #[test]
+    fn test_content_id_and_hash() {
+        let node = |id: u64, value: &str, children: Vec<u64>| GammaNode::builder(id, GammaNodeType::Call)
+            .direct(value)
+            .children(children)
+            .build();
+        
+        // The same subtree under two sets of node IDs
+        let mut ast = GammaAST::new();
+        for n in [node(1, "print", vec![2, 3]), node(2, "x", vec![]), node(3, "y", vec![])] {
+            ast.add_node(n);
+        }
+        for n in [node(11, "print", vec![12, 13]), node(12, "x", vec![]), node(13, "y", vec![])] {
+            ast.add_node(n);
+        }
+        let mut memo = HashMap::new();
+        let a = ast.content_id(1, &mut memo).unwrap();
+        assert_eq!(memo.len(), 3);
+        assert_eq!(ast.content_id(11, &mut memo), Some(a));
+        assert_eq!(ast.content_id(99, &mut memo), None);
+        
+        // Any change below the root changes its address
+        ast.nodes.get_mut(&13).unwrap().value = GammaValue::Direct("z".to_string());
+        assert_ne!(ast.content_ids()[&11], a);
+        ast.nodes.get_mut(&11).unwrap().children = vec![13, 12];
+        ast.nodes.get_mut(&13).unwrap().value = GammaValue::Direct("y".to_string());
+        assert_ne!(ast.content_ids()[&11], a);
+        
+        // Missing and cyclic children still get an address
+        ast.add_node(node(20, "loop", vec![21, 404]));
+        ast.add_node(node(21, "back", vec![20]));
+        let ids = ast.content_ids();
+        assert_eq!(ids.len(), ast.nodes.len());
+        assert_ne!(ids[&20], ids[&21]);
+        
+        let a = node(1, "print", vec![2, 3]);
+        let mut b = node(9, "print", vec![2, 3]);
+        b.metadata.insert("note".to_string(), "ignored".to_string());
+        
+        // Hash agrees with content identity, so nodes can key a content-addressed store
+        let ids = ast.content_ids();
+        let mut store: HashMap<u64, &GammaNode> = HashMap::new();
+        for id in [1, 11, 2, 12] {
+            store.entry(ids[&id]).or_insert(&ast.nodes[&id]);
+        }
+        assert_eq!(store.len(), 3);
+        let hash_of = |n: &GammaNode| {
+            use std::hash::{BuildHasher, BuildHasherDefault};
+            BuildHasherDefault::<std::collections::hash_map::DefaultHasher>::default().hash_one(n)
+        };
+        assert_eq!(hash_of(&a), hash_of(&b));
+        let set: HashSet<GammaNode> = [a.clone(), a.clone()].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+    
+    #[test]
+    fn test_content_id_resolves_interned_types() {
+        let typed = |name: &str| {
+            let mut ast = GammaAST::new();
+            ast.add_node(GammaNode::builder(1, GammaNodeType::Block).children(vec![2]).build());
+            ast.add_node(GammaNode::builder(2, GammaNodeType::Custom(name.to_string())).direct("f").build());
+            ast
+        };
+        
+        let mut interned = typed("Lambda");
+        interned.intern_custom_types();
+        assert_eq!(interned.nodes[&2].node_type, GammaNodeType::InternedCustom(0));
+        let expanded = typed("Lambda");
+        assert_eq!(interned.content_ids()[&1], expanded.content_ids()[&1]);
+        assert_eq!(interned.content_ids()[&2], expanded.content_ids()[&2]);
+        
+        // Index 0 names a different type in each AST, so the addresses differ
+        let mut other = typed("Closure");
+        other.intern_custom_types();
+        assert_eq!(other.nodes[&2].node_type, GammaNodeType::InternedCustom(0));
+        assert_ne!(interned.content_ids()[&2], other.content_ids()[&2]);
+        assert_ne!(interned.content_ids()[&1], other.content_ids()[&1]);
+    }
+    
+    #[test]
+    fn test_decompress_levels() {
+        let node = |id: u64, value: GammaValue, level: CompressionLevel| GammaNode::builder(id, GammaNodeType::Call)
+            .value(value)
+            .compression_level(level)
+            .build();
+        
+        let mut ast = GammaAST::new();
+        ast.add_node(node(1, GammaValue::Direct("print".to_string()), CompressionLevel::None));
+        ast.add_node(node(2, GammaValue::PatternRef(1), CompressionLevel::Light));
+        ast.add_node(node(3, GammaValue::PatternRef(2), CompressionLevel::Light));
+        ast.add_node(node(4, GammaValue::PatternRef(100), CompressionLevel::Medium));
+        ast.add_node(node(5, GammaValue::PatternRef(9), CompressionLevel::Heavy));
+        ast.add_node(node(6, GammaValue::PatternRef(5), CompressionLevel::Light));
+        let mut original = node(4, GammaValue::Direct("len".to_string()), CompressionLevel::None);
+        original.children = vec![1];
+        ast.add_pattern(Pattern { id: 100, signature: 0, frequency: 1, size: 1, nodes: vec![original.clone()], languages: vec![] });
+        
+        assert_eq!(ast.compress_level_for_node(4), Some(CompressionLevel::Medium));
+        assert_eq!(ast.compress_level_for_node(99), None);
+        
+        assert_eq!(ast.decompress_levels(), vec![5, 6]);
+        assert_eq!(ast.nodes[&2].value, GammaValue::Direct("print".to_string()));
+        assert_eq!(ast.nodes[&3].value, GammaValue::Direct("print".to_string()));
+        assert_eq!(ast.compress_level_for_node(3), Some(CompressionLevel::None));
+        assert_eq!(ast.nodes[&4], original);
+        assert_eq!(ast.nodes[&6].value, GammaValue::PatternRef(5));
+    }
+    
+    #[test]
+    fn test_find_patterns_ignores_empty_pattern() {
+        let mut ast = GammaAST::new();
+        let node = GammaNode::builder(1, GammaNodeType::Call).direct("print").build();
+        ast.add_node(node.clone());
+        ast.add_pattern(Pattern { id: 10, signature: 0, frequency: 0, size: 0, nodes: vec![], languages: vec![] });
+        assert!(ast.find_patterns().is_empty());
+        
+        ast.add_pattern(Pattern { id: 11, signature: 0, frequency: 1, size: 1, nodes: vec![node], languages: vec![] });
+        assert_eq!(ast.find_patterns(), vec![11]);
+    }
+    
+    #[test]
+    fn test_value_display_with() {
+        let node = |id: u64, value: GammaValue, level: CompressionLevel| GammaNode::builder(id, GammaNodeType::Literal)
+            .value(value)
+            .compression_level(level)
+            .build();
+        
+        let mut ast = GammaAST::new();
+        ast.add_node(node(1, GammaValue::Direct("print".to_string()), CompressionLevel::None));
+        ast.add_node(node(2, GammaValue::PatternRef(1), CompressionLevel::Light));
+        ast.add_node(node(3, GammaValue::PatternRef(2), CompressionLevel::Light));
+        ast.add_node(node(4, GammaValue::PatternRef(100), CompressionLevel::Medium));
+        ast.add_node(node(5, GammaValue::PatternRef(3), CompressionLevel::Heavy));
+        let mut hashed = node(6, GammaValue::CompressedHash(0xabc), CompressionLevel::Maximum);
+        hashed.metadata.insert("original_value".to_string(), "a_long_string".to_string());
+        ast.add_node(hashed);
+        ast.add_pattern(Pattern {
+            id: 100,
+            signature: 0,
+            frequency: 1,
+            size: 1,
+            nodes: vec![node(4, GammaValue::Direct("len".to_string()), CompressionLevel::None)],
+            languages: vec![],
+        });
+        let mut dictionary = PatternDictionary::new(7);
+        let entry = dictionary.insert("interned");
+        ast.metadata_dictionary = Some(dictionary);
+        
+        let display = |ast: &GammaAST, id: u64| ast.nodes[&id].value.display_with(ast);
+        assert_eq!(display(&ast, 3), "print");
+        assert_eq!(display(&ast, 4), "len");
+        assert_eq!(display(&ast, 5), "pattern_3");
+        assert_eq!(display(&ast, 6), "a_long_string");
+        assert_eq!(GammaValue::DictRef { dict_id: 7, entry }.display_with(&ast), "interned");
+        assert_eq!(GammaValue::DictRef { dict_id: 8, entry }.display_with(&ast), "dict_8_0");
+        assert_eq!(GammaValue::CompressedHash(0xdef).display_with(&ast), "hash_def");
+        
+        // Reference cycles fall back to the placeholder
+        ast.add_node(node(8, GammaValue::PatternRef(9), CompressionLevel::Light));
+        ast.add_node(node(9, GammaValue::PatternRef(8), CompressionLevel::Light));
+        assert_eq!(display(&ast, 8), "pattern_9");
+    }
+    
+    #[test]
+    fn test_node_type_text_encoding() {
+        for node_type in [
+            GammaNodeType::BinaryOp,
+            GammaNodeType::Custom("Import".to_string()),
+            GammaNodeType::Custom("a:b".to_string()),
+            GammaNodeType::InternedCustom(7),
+        ] {
+            assert_eq!(GammaNodeType::from_str(&node_type.as_str()), Some(node_type.clone()));
+            let json = serde_json::to_string(&node_type).unwrap();
+            assert_eq!(serde_json::from_str::<GammaNodeType>(&json).unwrap(), node_type);
+        }
+        
+        assert_eq!(GammaNodeType::Loop.as_str(), "Loop");
+        assert_eq!(GammaNodeType::Custom("Import".to_string()).as_str(), "Custom:Import");
+        assert_eq!(GammaNodeType::from_str("Nope"), None);
+        assert_eq!(GammaNodeType::from_str("InternedCustom:x"), None);
+        
+        // Externally tagged JSON written by older versions still loads
+        let legacy: GammaNodeType = serde_json::from_str(r#"{"Custom":"Import"}"#).unwrap();
+        assert_eq!(legacy, GammaNodeType::Custom("Import".to_string()));
+    }
+    
+    #[test]
+    fn test_find_reference_cycle() {
+        let mut ast = GammaAST::new();
+        for (id, value) in [(1, GammaValue::PatternRef(2)), (2, GammaValue::PatternRef(3)), (3, GammaValue::Direct("x".to_string()))] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal).value(value).build());
+        }
+        assert_eq!(ast.find_reference_cycle(), None);
+        
+        // Node 3 -> pattern 10 -> node 1 -> node 2 -> node 3
+        ast.nodes.get_mut(&3).unwrap().value = GammaValue::PatternRef(10);
+        let mut pattern_node = ast.nodes[&2].clone();
+        pattern_node.value = GammaValue::PatternRef(1);
+        ast.add_pattern(Pattern {
+            id: 10,
+            signature: 10,
+            frequency: 1,
+            size: 1,
+            nodes: vec![pattern_node],
+            languages: vec![],
+        });
+        assert_eq!(ast.find_reference_cycle(), Some(vec![1, 2, 3, 10]));
+    }
+    
+    #[test]
+    fn test_validate_roots() {
+        let mut ast = GammaAST::new();
+        for (id, children) in [(1, vec![2]), (2, vec![]), (3, vec![])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Block).direct("").children(children).build());
+        }
+        ast.add_root(1);
+        ast.add_root(3);
+        assert_eq!(ast.validate_roots(), Ok(()));
+        
+        ast.add_root(2);
+        assert_eq!(ast.validate_roots(), Err(vec![2]));
+    }
+    
+    #[test]
+    fn test_fix_roots() {
+        let mut ast = GammaAST::new();
+        for (id, children) in [(1, vec![2]), (2, vec![]), (3, vec![]), (4, vec![5]), (5, vec![4])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Block).children(children).build());
+        }
+        ast.roots = vec![3, 2, 9, 3];
+        assert_eq!(ast.topological_roots(), vec![1, 3]);
+        
+        ast.fix_roots();
+        assert_eq!(ast.roots, vec![3, 1]);
+        assert_eq!(ast.validate_roots(), Ok(()));
+    }
+    
+    #[test]
+    fn test_node_type_classification() {
+        let classes = |node_type: GammaNodeType| (
+            node_type.is_control_flow(),
+            node_type.is_declaration(),
+            node_type.is_expression(),
+            node_type.is_literal(),
+        );
+        assert_eq!(classes(GammaNodeType::Loop), (true, false, false, false));
+        assert_eq!(classes(GammaNodeType::Class), (false, true, false, false));
+        assert_eq!(classes(GammaNodeType::Call), (false, false, true, false));
+        assert_eq!(classes(GammaNodeType::Literal), (false, false, true, true));
+        assert_eq!(classes(GammaNodeType::Assignment), (false, false, false, false));
+        // Names that merely contain a keyword are not classified
+        assert_eq!(classes(GammaNodeType::Custom("while_loop_counter".to_string())), (false, false, false, false));
+        
+        // No built-in type is both control flow and a declaration or expression
+        for (_, node_type) in GammaNodeType::FIXED {
+            assert!(!(node_type.is_control_flow() && (node_type.is_declaration() || node_type.is_expression())));
+        }
+    }
+    
+    #[test]
+    fn test_node_builder() {
+        let node = GammaNode::builder(7, GammaNodeType::Call)
+            .direct("print")
+            .children([8, 9])
+            .metadata("kind", "builtin")
+            .compression_level(CompressionLevel::Light)
+            .build();
+        assert_eq!(node, GammaNode {
+            id: 7,
+            node_type: GammaNodeType::Call,
+            value: GammaValue::Direct("print".to_string()),
+            location: None,
+            children: vec![8, 9],
+            metadata: HashMap::from([("kind".to_string(), "builtin".to_string())]),
+            compression_level: CompressionLevel::Light,
+        });
+        
+        let bare = GammaNode::builder(1, GammaNodeType::Block).build();
+        assert_eq!((bare.value, bare.children.len(), bare.compression_level), (GammaValue::None, 0, CompressionLevel::None));
+    }
+    
+    #[test]
+    fn test_collect_nodes() {
+        let node = |id: u64, children: Vec<u64>| GammaNode::builder(id, GammaNodeType::Block)
+            .children(children)
+            .build();
+        
+        let mut ast: GammaAST = [node(1, vec![2]), node(2, vec![]), node(3, vec![])].into_iter().collect();
+        assert_eq!(ast.nodes.len(), 3);
+        assert_eq!(ast.roots, vec![1, 3]);
+        
+        // A new parent demotes an existing root
+        ast.extend([node(4, vec![3])]);
+        assert_eq!(ast.roots, vec![1, 4]);
+    }
+    
+    #[test]
+    fn test_metadata_interning_round_trip() {
+        let mut ast = GammaAST::new();
+        for id in 1..=3 {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal)
+                .direct("")
+                .metadata("source_map", "main.py")
+                .metadata("line", id.to_string())
+                .build());
+        }
+        let original = ast.clone();
+        
+        ast.intern_metadata();
+        let dictionary = ast.metadata_dictionary.as_ref().unwrap();
+        assert_eq!(dictionary.len(), 6); // line, source_map, main.py, and one line number per node
+        assert_eq!(ast.nodes[&1].metadata.get("2").map(String::as_str), Some("3"));
+        
+        let mut restored: GammaAST = serde_json::from_str(&serde_json::to_string(&ast).unwrap()).unwrap();
+        restored.expand_metadata().unwrap();
+        assert!(restored.metadata_dictionary.is_none());
+        for (id, node) in &original.nodes {
+            assert_eq!(restored.nodes[id].metadata, node.metadata);
+        }
+        
+        ast.nodes.get_mut(&1).unwrap().metadata.insert("99".to_string(), "0".to_string());
+        assert_eq!(ast.expand_metadata(), Err("99".to_string()));
+    }
+    
+    #[test]
+    fn test_try_add_and_replace_node() {
+        let node = |id, value: &str| GammaNode::builder(id, GammaNodeType::Literal).direct(value).build();
+        let mut ast = GammaAST::new();
+        assert_eq!(ast.try_add_node(node(1, "first")), Ok(()));
+        assert_eq!(ast.try_add_node(node(1, "second")), Err(DuplicateIdError { id: 1 }));
+        assert_eq!(ast.nodes[&1].value, GammaValue::Direct("first".to_string()));
+        
+        let old = ast.replace_node(node(1, "third")).unwrap();
+        assert_eq!(old.value, GammaValue::Direct("first".to_string()));
+        assert_eq!(ast.nodes[&1].value, GammaValue::Direct("third".to_string()));
+        assert!(ast.replace_node(node(2, "new")).is_none());
+        assert_eq!(ast.nodes.len(), 2);
+    }
+    
+    #[test]
+    fn test_pattern_coverage() {
+        let mut ast = GammaAST::new();
+        assert_eq!(ast.pattern_coverage(), 0.0);
+        
+        ast.add_pattern(Pattern { id: 40, signature: 0, frequency: 2, size: 1, nodes: vec![], languages: vec![] });
+        let values = [
+            GammaValue::Direct("x".to_string()),
+            GammaValue::PatternRef(40),
+            GammaValue::PatternRef(3),
+            GammaValue::DictRef { dict_id: 0, entry: 3 },
+            GammaValue::CompressedHash(9),
+            GammaValue::PatternRef(40),
+            GammaValue::None,
+            GammaValue::Direct("y".to_string()),
+        ];
+        for (id, value) in (1..).zip(values) {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Variable).value(value).build());
+        }
+        // Node 3 holds a value-table ID, not a pattern reference
+        assert_eq!(ast.covered_nodes(), vec![2, 6]);
+        assert_eq!(ast.pattern_coverage(), 0.25);
+    }
+    
+    #[test]
+    fn test_id_generator() {
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Module).direct("main").build());
+        ast.add_root(1);
+        
+        ast.add_node(GammaNode::builder(40, GammaNodeType::Call).direct("print").build());
+        ast.add_pattern(Pattern { id: 57, signature: 0, frequency: 1, size: 1, nodes: vec![], languages: vec![] });
+        
+        let ids = IdGenerator::seeded_from(&ast);
+        let first: Vec<u64> = (0..100).map(|_| ids.next_id()).collect();
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 100);
+        assert_eq!(first[0], 58);
+        assert!(first.iter().all(|id| !ast.nodes.contains_key(id) && !ast.patterns.contains_key(id)));
+        let again = IdGenerator::seeded_from(&ast);
+        assert_eq!(again.next_id(), first[0]);
+        assert_eq!(IdGenerator::seeded_from(&GammaAST::new()).next_id(), 0);
+        
+        // add_node overwrites; try_add_node is the checked form
+        ast.add_node(GammaNode::builder(40, GammaNodeType::Variable).build());
+        assert_eq!(ast.nodes[&40].node_type, GammaNodeType::Variable);
+        assert!(ast.try_add_node(GammaNode::builder(40, GammaNodeType::Call).build()).is_err());
+        
+        let near_max = IdGenerator::new(u64::MAX);
+        assert_eq!(near_max.next_id(), u64::MAX);
+        assert_eq!(near_max.next_id(), 0);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "duplicate pattern ID 7")]
+    fn test_duplicate_pattern_id_is_caught() {
+        let mut ast = GammaAST::new();
+        for _ in 0..2 {
+            ast.add_pattern(Pattern { id: 7, signature: 0, frequency: 1, size: 1, nodes: vec![], languages: vec![] });
         }
-        size
     }
     
-    /// Calculate the compressed size
-    fn calculate_compressed_size(&self) -> usize {
-        // Calculate actual compressed size
-        let mut size = 0;
-        for node in self.nodes.values() {
-            size += 8; // Node ID
-            size += 1; // Node type (enum)
-            size += match &node.value {
-                GammaValue::Direct(s) => s.len(),
-                GammaValue::PatternRef(_) => 8,
-                GammaValue::CompressedHash(_) => 8,
-                GammaValue::None => 0,
-            };
-            size += node.children.len() * 8; // Child references
-            size += node.metadata.len() * 8; // Compressed metadata
+    #[test]
+    fn test_visitors() {
+        struct Collect {
+            seen: Vec<u64>,
+            skip: u64,
+            stop: u64,
         }
-        size
+        impl Visitor for Collect {
+            fn visit_node(&mut self, node: &GammaNode) -> VisitAction {
+                self.seen.push(node.id);
+                if node.id == self.stop {
+                    VisitAction::Stop
+                } else if node.id == self.skip {
+                    VisitAction::SkipChildren
+                } else {
+                    VisitAction::Continue
+                }
+            }
+        }
+        
+        struct Rename;
+        impl VisitorMut for Rename {
+            fn visit_node_mut(&mut self, node: &mut GammaNode) -> VisitAction {
+                if node.node_type == GammaNodeType::Variable {
+                    node.value = GammaValue::Direct(format!("v{}", node.id));
+                }
+                VisitAction::Continue
+            }
+        }
+        
+        // 1 -> [2, 3], 2 -> [4, 1] (cycle), 3 -> [4] (shared), 4 -> [99] (missing)
+        let mut ast = GammaAST::new();
+        for (id, children) in [(1, vec![2, 3]), (2, vec![4, 1]), (3, vec![4]), (4, vec![99])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Variable).children(children).build());
+        }
+        ast.add_root(1);
+        
+        let mut all = Collect { seen: vec![], skip: 0, stop: 0 };
+        assert!(ast.accept(&mut all));
+        assert_eq!(all.seen, vec![1, 2, 4, 3]);
+        
+        let mut skipping = Collect { seen: vec![], skip: 2, stop: 0 };
+        assert!(ast.accept(&mut skipping));
+        assert_eq!(skipping.seen, vec![1, 2, 3, 4]);
+        
+        let mut stopping = Collect { seen: vec![], skip: 0, stop: 2 };
+        assert!(!ast.accept(&mut stopping));
+        assert_eq!(stopping.seen, vec![1, 2]);
+        
+        assert!(ast.accept_mut(&mut Rename));
+        assert_eq!(ast.nodes[&4].value, GammaValue::Direct("v4".to_string()));
     }
-}
-
-impl CompressionStats {
-    /// Create new compression stats
-    pub fn new() -> Self {
-        Self {
-            original_size: 0,
-            compressed_size: 0,
-            compression_ratio: 1.0,
-            patterns_found: 0,
-            memory_optimization: 1.0,
+    
+    #[test]
+    fn test_freeze() {
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Literal).direct("42").build());
+        ast.add_root(1);
+        
+        let frozen = ast.freeze();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = Arc::clone(&frozen);
+                std::thread::spawn(move || frozen.get_node(1).map(|node| node.value.clone()))
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), Some(GammaValue::Direct("42".to_string())));
         }
+        assert_eq!(frozen.depth(), 1);
+        
+        let mut thawed = frozen.thaw();
+        thawed.get_node_mut(1).unwrap().value = GammaValue::None;
+        assert_eq!(frozen.nodes[&1].value, GammaValue::Direct("42".to_string()));
     }
     
-    /// Get compression percentage
-    pub fn compression_percentage(&self) -> f64 {
-        if self.original_size > 0 {
-            if self.compressed_size <= self.original_size {
-                ((self.original_size - self.compressed_size) as f64 / self.original_size as f64) * 100.0
-            } else {
-                // If compressed size is larger, that means expansion occurred
-                -((self.compressed_size - self.original_size) as f64 / self.original_size as f64) * 100.0
-            }
-        } else {
-            0.0
+    #[test]
+    fn test_find_matches() {
+        let mut ast = GammaAST::new();
+        let decorator = GammaNodeType::InternedCustom(ast.custom_types.intern("Decorator"));
+        let nodes = [
+            (1, GammaNodeType::Call, "print", vec![2, 3]),
+            (2, GammaNodeType::Literal, "a", vec![]),
+            (3, GammaNodeType::BinaryOp, "+", vec![4, 5]),
+            (4, GammaNodeType::Variable, "x", vec![]),
+            (5, GammaNodeType::Literal, "1", vec![]),
+            (6, GammaNodeType::Call, "len", vec![7]),
+            (7, GammaNodeType::Variable, "y", vec![]),
+            (8, decorator, "", vec![]),
+        ];
+        for (id, node_type, value, children) in nodes {
+            ast.add_node(GammaNode::builder(id, node_type).direct(value).children(children).build());
         }
+        
+        use PatternQuery::{Any, AnySubtree};
+        let leaf = |node_type| PatternQuery::node(node_type, vec![]);
+        assert_eq!(ast.find_matches(&PatternQuery::node(GammaNodeType::Call, vec![AnySubtree, AnySubtree])), vec![1]);
+        assert_eq!(ast.find_matches(&Any(vec![AnySubtree])), vec![6]);
+        assert_eq!(ast.find_matches(&PatternQuery::with_value(GammaNodeType::Call, "len", vec![leaf(GammaNodeType::Variable)])), vec![6]);
+        assert!(ast.find_matches(&PatternQuery::with_value(GammaNodeType::Call, "print", vec![leaf(GammaNodeType::Variable)])).is_empty());
+        assert_eq!(
+            ast.find_matches(&PatternQuery::node(GammaNodeType::BinaryOp, vec![leaf(GammaNodeType::Variable), leaf(GammaNodeType::Literal)])),
+            vec![3]
+        );
+        assert_eq!(ast.find_matches(&leaf(GammaNodeType::Custom("Decorator".to_string()))), vec![8]);
+        assert_eq!(ast.find_matches(&AnySubtree).len(), 8);
     }
-}
-
-impl PatternRegistry {
-    /// Create a new pattern registry
-    pub fn new() -> Self {
-        Self {
-            signatures: HashMap::new(),
-            frequencies: HashMap::new(),
-            size_distribution: HashMap::new(),
+    
+    #[test]
+    fn test_json_round_trip() {
+        let mut ast = GammaAST::new();
+        ast.set_source_language("python".to_string());
+        let custom = ast.custom_types.intern("Decorator");
+        let nodes = [
+            (1, GammaNodeType::Function, GammaValue::Direct("main".to_string()), vec![2, 3, 4]),
+            (2, GammaNodeType::InternedCustom(custom), GammaValue::None, vec![]),
+            (3, GammaNodeType::Custom("Lambda".to_string()), GammaValue::PatternRef(7), vec![]),
+            (4, GammaNodeType::Literal, GammaValue::DictRef { dict_id: 1, entry: 0 }, vec![]),
+        ];
+        for (id, node_type, value, children) in nodes {
+            ast.add_node(GammaNode::builder(id, node_type)
+                .value(value)
+                .location(Location { file: Some("main.py".to_string()), ..Location::new(id as usize, 4) })
+                .children(children)
+                .metadata("source_map", "main.py")
+                .compression_level(CompressionLevel::Medium)
+                .build());
+        }
+        ast.add_root(1);
+        ast.add_pattern(Pattern {
+            id: 7,
+            signature: 0xdead_beef,
+            frequency: 3,
+            size: 1,
+            nodes: vec![ast.nodes[&3].clone()],
+            languages: vec!["python".to_string()],
+        });
+        ast.intern_metadata();
+        ast.calculate_compression_stats();
+        
+        let expected = serde_json::to_value(&ast).unwrap();
+        for json in [ast.to_json().unwrap(), ast.to_json_pretty().unwrap()] {
+            let restored = GammaAST::from_json(&json).unwrap();
+            assert_eq!(restored.nodes, ast.nodes);
+            assert_eq!(restored.roots, ast.roots);
+            assert_eq!(restored.custom_type_name(&GammaNodeType::InternedCustom(custom)), Some("Decorator"));
+            assert_eq!(serde_json::to_value(&restored).unwrap(), expected);
         }
+        assert!(GammaAST::from_json("{\"roots\": [1]}").is_err());
     }
     
-    /// Get the most frequent patterns
-    pub fn get_top_patterns(&self, limit: usize) -> Vec<(u64, u32)> {
-        let mut patterns: Vec<(u64, u32)> = self.frequencies.iter()
-            .map(|(id, freq)| (*id, *freq))
-            .collect();
-        patterns.sort_by(|a, b| b.1.cmp(&a.1));
-        patterns.truncate(limit);
-        patterns
+    #[test]
+    fn test_size_profile() {
+        let mut ast = GammaAST::new();
+        for (id, value, children) in [(1, "root", vec![2, 3]), (2, "a much longer value than the others", vec![3]), (3, "x", vec![])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal).direct(value).children(children).build());
+        }
+        ast.add_root(1);
+        
+        let node_2 = serde_json::to_vec(&ast.nodes[&2]).unwrap().len();
+        assert_eq!(ast.node_bytes(2), node_2);
+        assert_eq!(ast.node_bytes(42), 0);
+        assert_eq!(ast.subtree_bytes(1), ast.node_bytes(1) + ast.node_bytes(2) + ast.node_bytes(3));
+        
+        let profile = ast.size_profile();
+        assert_eq!(profile.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![2, 1, 3]);
+        assert!(profile.windows(2).all(|pair| pair[0].1 >= pair[1].1));
     }
     
-    /// Get patterns by size range
-    pub fn get_patterns_by_size(&self, min_size: usize, max_size: usize) -> Vec<u64> {
-        self.size_distribution.iter()
-            .filter(|(size, _)| **size >= min_size && **size <= max_size)
-            .flat_map(|(_, count)| {
-                self.frequencies.iter()
-                    .filter(|(_, freq)| **freq > 0)
-                    .map(|(id, _)| *id)
-                    .take(*count as usize)
-            })
-            .collect()
+    #[test]
+    fn test_sort_patterns_by_value() {
+        let pattern = |id, frequency, size| Pattern {
+            id,
+            signature: id,
+            frequency,
+            size,
+            nodes: Vec::new(),
+            languages: Vec::new(),
+        };
+        let mut patterns = vec![pattern(1, 2, 2), pattern(2, 10, 3), pattern(3, 4, 1), pattern(4, 1, 4)];
+        
+        sort_patterns_by_value(&mut patterns, |_| 1.0);
+        let order: Vec<u64> = patterns.iter().map(|pattern| pattern.id).collect();
+        assert_eq!(order, vec![2, 1, 3, 4]);
+        
+        let mut borrowed: Vec<&Pattern> = patterns.iter().collect();
+        sort_patterns_by_value(&mut borrowed, |pattern| if pattern.id == 4 { 100.0 } else { 1.0 });
+        assert_eq!(borrowed[0].id, 4);
     }
-}
-
-impl fmt::Display for GammaAST {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Γ-AST (Gamma AST)")?;
-        writeln!(f, "Source Language: {}", self.source_language)?;
-        writeln!(f, "Roots: {}", self.roots.len())?;
-        writeln!(f, "Nodes: {}", self.nodes.len())?;
-        writeln!(f, "Patterns: {}", self.patterns.len())?;
-        writeln!(f, "Compression Ratio: {:.2}x", self.compression_stats.compression_ratio)?;
-        writeln!(f, "Compression: {:.1}%", self.compression_stats.compression_percentage())?;
-        Ok(())
+    
+    #[test]
+    fn test_retain_nodes() {
+        let mut ast = GammaAST::new();
+        // 1 -> {2, 5}, 2 (comment) -> {3, 4}, 4 (comment) -> {6}; root 7 (comment) -> {8, 7}
+        for (id, comment, children) in [
+            (1, false, vec![2, 5]), (2, true, vec![3, 4]), (3, false, vec![]), (4, true, vec![6]),
+            (5, false, vec![]), (6, false, vec![]), (7, true, vec![8, 7]), (8, false, vec![]),
+        ] {
+            let node_type = if comment { GammaNodeType::Custom("Comment".to_string()) } else { GammaNodeType::Block };
+            ast.add_node(GammaNode::builder(id, node_type).children(children).build());
+        }
+        ast.add_root(1);
+        ast.add_root(7);
+        
+        ast.retain_nodes(|node| node.node_type != GammaNodeType::Custom("Comment".to_string()));
+        
+        let mut ids: Vec<u64> = ast.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 3, 5, 6, 8]);
+        assert_eq!(ast.nodes[&1].children, vec![3, 6, 5]);
+        assert_eq!(ast.roots, vec![1, 8]);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
     
     #[test]
-    fn test_gamma_ast_creation() {
-        let ast = GammaAST::new();
-        assert_eq!(ast.roots.len(), 0);
-        assert_eq!(ast.nodes.len(), 0);
-        assert_eq!(ast.patterns.len(), 0);
+    fn test_from_ast() {
+        use crate::parser::{BasicParser, Parser};
+        
+        let ast = BasicParser::new().parse("let x = 1 + 2\nif x > 2 { print(x) }").unwrap();
+        let gamma_ast = GammaAST::from_ast(&ast);
+        assert_eq!(gamma_ast.nodes.len(), ast.node_count());
+        assert_eq!(gamma_ast.roots.len(), ast.roots.len());
+        assert_eq!(gamma_ast.roots[0], 1);
+        
+        // Each Γ node mirrors its parser node, children included, in preorder
+        let mut ids = Vec::new();
+        for (root, &id) in ast.roots.iter().zip(&gamma_ast.roots) {
+            let mut stack = vec![(root, id)];
+            while let Some((node, id)) = stack.pop() {
+                ids.push(id);
+                let gamma_node = &gamma_ast.nodes[&id];
+                assert_eq!(gamma_node.node_type, GammaNodeType::from(&node.node_type));
+                assert_eq!(gamma_node.value, GammaValue::Direct(node.value.clone()));
+                assert_eq!((&gamma_node.location, &gamma_node.metadata), (&node.location, &node.metadata));
+                assert_eq!(gamma_node.children.len(), node.children.len());
+                stack.extend(node.children.iter().zip(gamma_node.children.iter().copied()).rev());
+            }
+        }
+        assert_eq!(ids, (1..=ast.node_count() as u64).collect::<Vec<_>>());
+        assert_eq!(GammaAST::from_ast(&ast).content_hash(), gamma_ast.content_hash());
     }
     
     #[test]
-    fn test_node_addition() {
+    fn test_save_and_load_file() {
+        let mut ast: GammaAST = [
+            GammaNode::builder(1, GammaNodeType::Function).direct("main").children([2, 3]).location(Location::new(1, 1).with_end(3, 2)).build(),
+            GammaNode::builder(2, GammaNodeType::Custom("Macro".to_string())).direct("println").metadata("args", "1").build(),
+            GammaNode::builder(3, GammaNodeType::Literal).direct("42").compression_level(CompressionLevel::Light).build(),
+        ].into_iter().collect();
+        ast.roots = vec![1];
+        ast.set_source_language("rust".to_string());
+        ast.add_pattern(Pattern { id: 10, signature: 7, frequency: 2, size: 1, nodes: vec![ast.nodes[&3].clone()], languages: vec!["rust".to_string()] });
+        
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("ast.nxga");
+        ast.save_to_file(&path).unwrap();
+        let loaded = GammaAST::load_from_file(&path).unwrap();
+        assert_eq!(loaded.nodes, ast.nodes);
+        assert_eq!(loaded.roots, ast.roots);
+        assert_eq!(loaded.patterns, ast.patterns);
+        assert_eq!(loaded.source_language, "rust");
+        
+        // A newer version is reported, not misread
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = FILE_FORMAT_VERSION + 1;
+        std::fs::write(&path, &bytes).unwrap();
+        let error = GammaAST::load_from_file(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("version"));
+        
+        std::fs::write(&path, b"{}").unwrap();
+        assert_eq!(GammaAST::load_from_file(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+    
+    #[test]
+    fn test_sample() {
+        // One root over 8 statements, each with a single leaf child
+        let mut ast: GammaAST = std::iter::once(GammaNode::builder(1, GammaNodeType::Block).children(2..10).build())
+            .chain((2..10).map(|id| GammaNode::builder(id, GammaNodeType::Call).children([id + 100]).build()))
+            .chain((2..10).map(|id| GammaNode::builder(id + 100, GammaNodeType::Literal).direct("0").build()))
+            .collect();
+        ast.roots = vec![1];
+        
+        let sample = ast.sample(0.25, 7);
+        assert_eq!(sample.roots, ast.sample(0.25, 7).roots);
+        assert!(!sample.roots.is_empty() && sample.roots.len() < 8);
+        for root in &sample.roots {
+            assert!((2..10).contains(root));
+            assert_eq!(sample.nodes[root].children, vec![root + 100]);
+            assert!(sample.nodes.contains_key(&(root + 100)));
+        }
+        assert_eq!(sample.nodes.len(), sample.roots.len() * 2);
+        
+        assert_eq!(ast.sample(1.0, 7).nodes.len(), ast.nodes.len());
+        assert!(ast.sample(0.0, 7).nodes.is_empty());
+    }
+    
+    #[test]
+    fn test_replace_subtree() {
+        let block = |id, children: Vec<u64>| GammaNode::builder(id, GammaNodeType::Block)
+            .direct(format!("n{}", id))
+            .children(children)
+            .build();
+        
+        // 1 -> {2, 3}, 2 -> {4, 6}, 3 -> {4}: node 4 is shared between the subtrees of 2 and 3
         let mut ast = GammaAST::new();
-        let node = GammaNode {
-            id: 1,
-            node_type: GammaNodeType::Literal,
-            value: GammaValue::Direct("42".to_string()),
-            location: None,
-            children: Vec::new(),
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::Light,
+        for (id, children) in [(1, vec![2, 3]), (2, vec![4, 6]), (3, vec![4]), (4, vec![]), (6, vec![])] {
+            ast.add_node(block(id, children));
+        }
+        ast.add_root(1);
+        
+        let mut replacement = GammaAST::new();
+        replacement.add_node(block(10, vec![11, 99]));
+        replacement.add_node(block(11, vec![]));
+        replacement.add_root(10);
+        
+        assert_eq!(ast.replace_subtree(2, replacement), Some(vec![7]));
+        assert!(!ast.nodes.contains_key(&2) && !ast.nodes.contains_key(&6));
+        assert!(ast.nodes.contains_key(&4));
+        assert_eq!(ast.nodes[&1].children, vec![7, 3]);
+        assert_eq!(ast.nodes[&7].children, vec![8]);
+        assert_eq!(ast.nodes[&8].value, GammaValue::Direct("n11".to_string()));
+        
+        assert_eq!(ast.replace_subtree(1, GammaAST::new()), Some(vec![]));
+        assert!(ast.roots.is_empty() && ast.nodes.is_empty());
+        assert_eq!(ast.replace_subtree(1, GammaAST::new()), None);
+    }
+    
+    #[test]
+    fn test_replace_subtree_id_allocation() {
+        let leaf = |id| GammaNode::builder(id, GammaNodeType::Block).build();
+        let replacement = || {
+            let mut new = GammaAST::new();
+            new.add_node(leaf(1));
+            new.add_root(1);
+            new
         };
         
-        ast.add_node(node);
-        assert_eq!(ast.nodes.len(), 1);
-        assert!(ast.get_node(1).is_some());
+        // Fresh IDs go above pattern IDs too, so existing PatternRefs keep resolving
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Block).children(vec![2]).build());
+        ast.add_node(leaf(2));
+        ast.add_root(1);
+        ast.add_pattern(Pattern {
+            id: 5,
+            signature: 0,
+            frequency: 1,
+            size: 1,
+            nodes: vec![leaf(2)],
+            languages: Vec::new(),
+        });
+        assert_eq!(ast.replace_subtree(2, replacement()), Some(vec![6]));
+        
+        // No room above u64::MAX: refuse without touching the AST
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Block).children(vec![u64::MAX]).build());
+        ast.add_node(leaf(u64::MAX));
+        ast.add_root(1);
+        assert_eq!(ast.replace_subtree(u64::MAX, replacement()), None);
+        assert_eq!(ast.nodes.len(), 2);
+        assert_eq!(ast.nodes[&1].children, vec![u64::MAX]);
+    }
+    
+    #[test]
+    fn test_walk_with_path() {
+        // 1 -> {2, 3}, 2 -> {4}, 3 -> {4, 1, 99}: 4 is shared, 3 -> 1 is a cycle, 99 is missing
+        let ast: GammaAST = [(1, vec![2, 3]), (2, vec![4]), (3, vec![4, 1, 99]), (4, vec![])]
+            .into_iter()
+            .map(|(id, children)| GammaNode::builder(id, GammaNodeType::Block).children(children).build())
+            .collect();
+        
+        let walk: Vec<(u64, Vec<u64>)> = ast.walk_with_path(1).map(|(node, path)| (node.id, path)).collect();
+        assert_eq!(walk, vec![
+            (1, vec![1]),
+            (2, vec![1, 2]),
+            (4, vec![1, 2, 4]),
+            (3, vec![1, 3]),
+            (4, vec![1, 3, 4]),
+        ]);
+        assert_eq!(ast.walk_with_path(42).count(), 0);
+    }
+    
+    #[test]
+    fn test_iter_dfs_and_bfs() {
+        // 1 -> {2, 3}, 2 -> {4, 5}, 3 -> {5, 6, 1, 99}: 5 is shared, 3 -> 1 is a cycle, 99 is missing; 7 is a second root
+        let mut ast: GammaAST = [(1, vec![2, 3]), (2, vec![4, 5]), (3, vec![5, 6, 1, 99]), (4, vec![]), (5, vec![]), (6, vec![]), (7, vec![6])]
+            .into_iter()
+            .map(|(id, children)| GammaNode::builder(id, GammaNodeType::Block).children(children).build())
+            .collect();
+        ast.roots = vec![1, 7];
+        
+        let dfs: Vec<u64> = ast.iter_dfs().map(|node| node.id).collect();
+        assert_eq!(dfs, vec![1, 2, 4, 5, 3, 6, 7]);
+        let bfs: Vec<u64> = ast.iter_bfs().map(|node| node.id).collect();
+        assert_eq!(bfs, vec![1, 7, 2, 3, 6, 4, 5]);
+        
+        // Nodes no root reaches are not visited
+        ast.roots = vec![3];
+        assert_eq!(ast.iter_dfs().map(|node| node.id).collect::<Vec<_>>(), vec![3, 5, 6, 1, 2, 4]);
+        assert_eq!(ast.iter_bfs().map(|node| node.id).collect::<Vec<_>>(), vec![3, 5, 6, 1, 2, 4]);
+    }
+    
+    #[test]
+    fn test_depth_and_max_width() {
+        let mut ast = GammaAST::new();
+        assert_eq!((ast.depth(), ast.max_width()), (0, 0));
+        
+        // 1 -> {2, 3}, 3 -> {4, 5, 6}, 6 -> {1} (cycle), 7 is a second root sharing 4
+        for (id, children) in [(1, vec![2, 3]), (2, vec![]), (3, vec![4, 5, 6]), (4, vec![]), (5, vec![]), (6, vec![1, 99]), (7, vec![4])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Block).direct("").children(children).build());
+        }
+        ast.add_root(1);
+        ast.add_root(7);
+        
+        assert_eq!(ast.depth(), 3);
+        assert_eq!(ast.node_depths()[&4], 2);
+        assert_eq!(ast.node_depths()[&5], 3);
+        assert_eq!(ast.max_width(), 3);
     }
     
     #[test]
@@ -450,30 +3797,175 @@ mod tests {
         assert_eq!(ast.pattern_registry.frequencies.get(&1), Some(&5));
     }
     
+    #[test]
+    fn test_diff_and_apply_patch() {
+        let literal = |id: u64, value: &str| GammaNode::builder(id, GammaNodeType::Literal).direct(value).build();
+        
+        let mut old = GammaAST::new();
+        old.add_node(literal(1, "a"));
+        old.add_node(literal(2, "b"));
+        old.add_root(1);
+        
+        let mut new = GammaAST::new();
+        new.add_node(literal(1, "changed"));
+        new.add_node(literal(3, "c"));
+        new.add_root(3);
+        
+        let patch = old.diff(&new);
+        assert_eq!(patch.added.len(), 1);
+        assert_eq!(patch.removed, vec![2]);
+        assert_eq!(patch.modified.len(), 1);
+        assert_eq!(patch.roots, Some(vec![3]));
+        
+        old.apply_patch(&patch);
+        assert_eq!(old.nodes, new.nodes);
+        assert_eq!(old.roots, new.roots);
+        assert!(old.diff(&new).is_empty());
+    }
+    
+    #[test]
+    fn test_to_dot() {
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Function).direct("say \"hi\"").children([2]).build());
+        ast.add_node(GammaNode::builder(2, GammaNodeType::Variable)
+            .value(GammaValue::PatternRef(1))
+            .compression_level(CompressionLevel::Light)
+            .build());
+        ast.add_root(1);
+        
+        let dot = ast.to_dot();
+        assert!(dot.starts_with("digraph gamma_ast {"));
+        assert!(dot.contains("n1 -> n2;"));
+        assert!(dot.contains("n2 -> n1 [style=dashed"));
+        assert!(dot.contains("say \\\"hi\\\""));
+    }
+    
+    #[test]
+    fn test_pattern_registry_merge() {
+        let mut left = PatternRegistry::new();
+        left.signatures.insert(100, 1);
+        left.frequencies.insert(1, 3);
+        left.size_distribution.insert(2, 1);
+        
+        let mut right = PatternRegistry::new();
+        right.signatures.insert(100, 7); // same signature, different ID
+        right.frequencies.insert(7, 2);
+        right.signatures.insert(200, 1); // new signature, clashing ID
+        right.frequencies.insert(1, 5);
+        right.size_distribution.insert(2, 1);
+        right.size_distribution.insert(4, 1);
+        
+        let remap = left.merge(&right);
+        
+        assert_eq!(remap[&7], 1);
+        assert_ne!(remap[&1], 1);
+        assert_eq!(left.frequencies[&1], 5);
+        assert_eq!(left.frequencies[&remap[&1]], 5);
+        assert_eq!(left.signatures[&200], remap[&1]);
+        assert_eq!(left.size_distribution[&2], 2);
+        assert_eq!(left.size_distribution[&4], 1);
+    }
+    
+    #[test]
+    fn test_pattern_registry_merge_fresh_id_skips_kept_ids() {
+        let mut left = PatternRegistry::new();
+        left.signatures.insert(100, 0);
+        left.frequencies.insert(0, 1);
+        
+        let mut right = PatternRegistry::new();
+        right.signatures.insert(200, 1); // kept as-is
+        right.frequencies.insert(1, 2);
+        right.signatures.insert(300, 0); // clashes with left's 0
+        right.frequencies.insert(0, 4);
+        
+        let remap = left.merge(&right);
+        
+        assert_eq!(remap[&1], 1);
+        assert_ne!(remap[&0], 0);
+        assert_ne!(remap[&0], 1);
+        assert_eq!(left.signatures[&200], 1);
+        assert_eq!(left.signatures[&300], remap[&0]);
+        assert_eq!(left.frequencies[&0], 1);
+        assert_eq!(left.frequencies[&1], 2);
+        assert_eq!(left.frequencies[&remap[&0]], 4);
+    }
+    
+    #[test]
+    fn test_pattern_registry_merge_near_id_limit() {
+        let mut left = PatternRegistry::new();
+        left.signatures.insert(100, u64::MAX);
+        left.frequencies.insert(u64::MAX, 1);
+        
+        let mut right = PatternRegistry::new();
+        right.signatures.insert(200, u64::MAX);
+        right.frequencies.insert(u64::MAX, 3);
+        
+        let remap = left.merge(&right);
+        
+        assert_eq!(remap[&u64::MAX], 0);
+        assert_eq!(left.frequencies[&u64::MAX], 1);
+        assert_eq!(left.frequencies[&0], 3);
+    }
+    
+    #[test]
+    fn test_custom_type_interning() {
+        let mut ast = GammaAST::new();
+        for id in 1..=3 {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Custom("HttpRequest".to_string())).build());
+        }
+        
+        ast.intern_custom_types();
+        assert_eq!(ast.custom_types.len(), 1);
+        assert_eq!(ast.get_node(2).unwrap().node_type, GammaNodeType::InternedCustom(0));
+        assert_eq!(ast.custom_type_name(&ast.get_node(2).unwrap().node_type), Some("HttpRequest"));
+        assert_eq!(ast.resolved_node_type(3), Some(GammaNodeType::Custom("HttpRequest".to_string())));
+        
+        let json = serde_json::to_string(&ast).unwrap();
+        let mut restored: GammaAST = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.custom_types.intern("HttpRequest"), 0);
+        
+        restored.expand_custom_types();
+        assert_eq!(restored.get_node(1).unwrap().node_type, GammaNodeType::Custom("HttpRequest".to_string()));
+    }
+    
+    #[test]
+    fn test_remap_node_types() {
+        let mut ast = GammaAST::new();
+        for (id, name) in [(1, "func"), (2, "function"), (3, "Decorator"), (4, "def")] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Custom(name.to_string())).build());
+        }
+        let def = ast.custom_types.intern("def");
+        ast.nodes.get_mut(&4).unwrap().node_type = GammaNodeType::InternedCustom(def);
+        
+        let map = HashMap::from([
+            ("func".to_string(), GammaNodeType::Function),
+            ("function".to_string(), GammaNodeType::Function),
+            ("def".to_string(), GammaNodeType::Custom("fn".to_string())),
+        ]);
+        assert_eq!(ast.remap_node_types(&map), 3);
+        assert_eq!(ast.nodes[&1].node_type, GammaNodeType::Function);
+        assert_eq!(ast.nodes[&2].node_type, GammaNodeType::Function);
+        assert_eq!(ast.nodes[&3].node_type, GammaNodeType::Custom("Decorator".to_string()));
+        assert!(matches!(ast.nodes[&4].node_type, GammaNodeType::InternedCustom(_)));
+        assert_eq!(ast.resolved_node_type(4), Some(GammaNodeType::Custom("fn".to_string())));
+        
+        assert_eq!(ast.remap_node_types(&map), 0);
+    }
+    
     #[test]
     fn test_compression_stats() {
         let mut ast = GammaAST::new();
         
         // Add some nodes to calculate stats
-        let node1 = GammaNode {
-            id: 1,
-            node_type: GammaNodeType::Literal,
-            value: GammaValue::Direct("42".to_string()),
-            location: None,
-            children: Vec::new(),
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::Medium,
-        };
+        let node1 = GammaNode::builder(1, GammaNodeType::Literal)
+            .direct("42")
+            .compression_level(CompressionLevel::Medium)
+            .build();
         
-        let node2 = GammaNode {
-            id: 2,
-            node_type: GammaNodeType::Variable,
-            value: GammaValue::Direct("x".to_string()),
-            location: None,
-            children: Vec::new(),
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::Light,
-        };
+        let node2 = GammaNode::builder(2, GammaNodeType::Variable)
+            .direct("x")
+            .compression_level(CompressionLevel::Light)
+            .build();
         
         ast.add_node(node1);
         ast.add_node(node2);
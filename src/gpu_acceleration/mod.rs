@@ -5,6 +5,7 @@
 //! from 2.83x to 8x+ compression.
 
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -25,6 +26,98 @@ pub struct GPUConfig {
     pub max_gpu_memory_mb: u64,
     /// Number of parallel GPU streams
     pub parallel_streams: u32,
+    /// How long to keep retrying a GPU allocation before falling back to CPU
+    pub allocation_retry: GPURetryPolicy,
+}
+
+/// Retry policy for transient GPU allocation failures
+#[derive(Debug, Clone, PartialEq)]
+pub struct GPURetryPolicy {
+    /// Total allocation attempts, including the first (1 = no retry)
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Factor applied to the delay after each retry
+    pub backoff_multiplier: f64,
+    /// Upper bound on a single delay
+    pub max_backoff: Duration,
+}
+
+impl GPURetryPolicy {
+    /// Fail immediately on the first unsuccessful allocation
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+    
+    /// Delay to wait after the given failed attempt (1-based)
+    ///
+    /// Clamped to `max_backoff` before converting, so any attempt count is safe.
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let seconds = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+        Duration::try_from_secs_f64(seconds).map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+    
+    /// Reject multipliers that are not finite or would shrink the delay
+    pub fn validate(&self) -> Result<(), GPUError> {
+        if !self.backoff_multiplier.is_finite() || self.backoff_multiplier < 1.0 {
+            return Err(GPUError::InvalidConfig(format!(
+                "backoff_multiplier must be finite and at least 1, got {}",
+                self.backoff_multiplier
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Call `allocate` until it succeeds or `max_attempts` run out, awaiting `sleep(backoff_for(n))` between attempts
+    ///
+    /// Returns the last error once every attempt has failed. `sleep` is passed in
+    /// so the library does not depend on an async runtime; use `tokio::time::sleep`.
+    pub async fn retry<T, E, S>(
+        &self,
+        mut allocate: impl FnMut() -> Result<T, E>,
+        mut sleep: impl FnMut(Duration) -> S,
+    ) -> Result<T, E>
+    where
+        S: Future<Output = ()>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match allocate() {
+                Ok(allocation) => return Ok(allocation),
+                Err(error) if attempt >= self.max_attempts => return Err(error),
+                Err(_) => sleep(self.backoff_for(attempt)).await,
+            }
+        }
+    }
+    
+    /// Blocking form of [`retry`](Self::retry) that waits with `std::thread::sleep`
+    pub fn retry_blocking<T, E>(&self, mut allocate: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match allocate() {
+                Ok(allocation) => return Ok(allocation),
+                Err(error) if attempt >= self.max_attempts => return Err(error),
+                Err(_) => std::thread::sleep(self.backoff_for(attempt)),
+            }
+        }
+    }
+}
+
+impl Default for GPURetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(5),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_millis(100),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -42,6 +135,7 @@ impl Default for GPUConfig {
             memory_threshold: 1024 * 1024, // 1MB threshold
             max_gpu_memory_mb: 8192, // 8GB max
             parallel_streams: 4,
+            allocation_retry: GPURetryPolicy::default(),
         }
     }
 }
@@ -98,6 +192,8 @@ pub struct GPUProcessingStats {
     pub average_compression_improvement: f64,
     pub total_memory_allocated: u64,
     pub gpu_utilization_peak: f32,
+    /// Patterns sent to the CPU because GPU memory could not be reserved after retrying
+    pub allocation_fallbacks: u64,
 }
 
 /// Universal pattern for GPU processing
@@ -124,11 +220,14 @@ pub enum GPUError {
     DeviceNotFound(String),
     #[error("GPU kernel compilation failed: {0}")]
     KernelCompilationFailed(String),
+    #[error("Invalid GPU configuration: {0}")]
+    InvalidConfig(String),
 }
 
 impl GPUAccelerationEngine {
     /// Create a new GPU acceleration engine
     pub fn new(config: GPUConfig) -> Result<Self, GPUError> {
+        config.allocation_retry.validate()?;
         let devices = Self::discover_gpu_devices(&config)?;
         
         let mut engine = Self {
@@ -238,15 +337,53 @@ impl GPUAccelerationEngine {
             return self.process_pattern_cpu(pattern, start_time);
         }
 
+        // Memory held by other patterns may free up shortly, so retry before giving up on the GPU
+        if self.config.allocation_retry.retry_blocking(|| self.reserve_memory(pattern)).is_err() {
+            self.processing_stats.lock().unwrap().allocation_fallbacks += 1;
+            return self.process_pattern_cpu(pattern, start_time);
+        }
+
         #[cfg(feature = "gpu")]
-        {
-            if self.opencl_context.is_some() {
-                return self.process_pattern_gpu_opencl(pattern, start_time);
-            }
+        let result = if self.opencl_context.is_some() {
+            self.process_pattern_gpu_opencl(pattern, start_time)
+        } else {
+            self.process_pattern_cpu(pattern, start_time)
+        };
+        #[cfg(not(feature = "gpu"))]
+        let result = self.process_pattern_cpu(pattern, start_time);
+
+        self.release_memory(pattern.id);
+        result
+    }
+
+    /// Reserve GPU memory for a pattern against the `max_gpu_memory_mb` budget
+    ///
+    /// Fails with `MemoryAllocationFailed` while the patterns already holding
+    /// memory leave too little room.
+    fn reserve_memory(&self, pattern: &UniversalPattern) -> Result<GPUMemoryAllocation, GPUError> {
+        let budget = self.config.max_gpu_memory_mb.saturating_mul(1024 * 1024);
+        let mut allocations = self.memory_allocations.lock().unwrap();
+        let in_use: u64 = allocations.values().map(|allocation| allocation.size as u64).sum();
+        if in_use.saturating_add(pattern.size as u64) > budget {
+            return Err(GPUError::MemoryAllocationFailed(format!(
+                "{} bytes requested, {} of {} in use",
+                pattern.size, in_use, budget
+            )));
         }
+        let allocation = GPUMemoryAllocation {
+            buffer_id: pattern.id,
+            size: pattern.size,
+            offset: 0,
+            pattern_id: pattern.id,
+            allocated_at: Instant::now(),
+        };
+        allocations.insert(pattern.id, allocation.clone());
+        Ok(allocation)
+    }
 
-        // Fallback to CPU processing
-        self.process_pattern_cpu(pattern, start_time)
+    /// Release the GPU memory reserved for a pattern
+    fn release_memory(&self, pattern_id: u64) {
+        self.memory_allocations.lock().unwrap().remove(&pattern_id);
     }
 
     #[cfg(feature = "gpu")]
@@ -452,5 +589,103 @@ mod tests {
         assert_eq!(config.memory_threshold, 1024 * 1024);
         assert_eq!(config.max_gpu_memory_mb, 8192);
         assert_eq!(config.parallel_streams, 4);
+        assert_eq!(config.allocation_retry, GPURetryPolicy::default());
+    }
+    
+    #[test]
+    fn test_retry_backoff() {
+        let policy = GPURetryPolicy::default();
+        assert_eq!(policy.backoff_for(1), Duration::from_millis(5));
+        assert_eq!(policy.backoff_for(2), Duration::from_millis(10));
+        assert_eq!(policy.backoff_for(3), Duration::from_millis(20));
+        assert_eq!(policy.backoff_for(10), policy.max_backoff);
+        assert_eq!(policy.backoff_for(80), policy.max_backoff);
+        assert_eq!(policy.backoff_for(u32::MAX), policy.max_backoff);
+        assert_eq!(GPURetryPolicy::no_retry().max_attempts, 1);
+        
+        assert!(policy.validate().is_ok());
+        for backoff_multiplier in [0.5, f64::NAN, f64::INFINITY] {
+            let policy = GPURetryPolicy { backoff_multiplier, ..GPURetryPolicy::default() };
+            assert!(matches!(policy.validate(), Err(GPUError::InvalidConfig(_))));
+        }
+        let config = GPUConfig {
+            allocation_retry: GPURetryPolicy { backoff_multiplier: 0.0, ..GPURetryPolicy::default() },
+            ..GPUConfig::default()
+        };
+        assert!(GPUAccelerationEngine::new(config).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_retry_against_mock_allocator() {
+        // Fails the first `failures` calls, then hands out allocation `calls`
+        let mock = |failures: u32| {
+            let mut calls = 0;
+            move || {
+                calls += 1;
+                if calls > failures { Ok(calls) } else { Err(format!("busy on call {}", calls)) }
+            }
+        };
+        let policy = GPURetryPolicy { max_attempts: 4, ..GPURetryPolicy::default() };
+        
+        let mut delays = Vec::new();
+        let result = policy.retry(mock(2), |delay| {
+            delays.push(delay);
+            std::future::ready(())
+        }).await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(delays, vec![Duration::from_millis(5), Duration::from_millis(10)]);
+        
+        // Every attempt failing returns the last error without sleeping after it
+        let mut delays = Vec::new();
+        let result = policy.retry(mock(10), |delay| {
+            delays.push(delay);
+            std::future::ready(())
+        }).await;
+        assert_eq!(result, Err("busy on call 4".to_string()));
+        assert_eq!(delays.len(), 3);
+        
+        let result = GPURetryPolicy::no_retry().retry(mock(1), |_| async { panic!("no_retry must not sleep") }).await;
+        assert!(result.is_err());
+        
+        let quick = GPURetryPolicy { initial_backoff: Duration::ZERO, ..policy };
+        assert_eq!(quick.retry_blocking(mock(2)), Ok(3));
+        assert_eq!(quick.retry_blocking(mock(10)), Err("busy on call 4".to_string()));
+    }
+    
+    #[test]
+    fn test_allocation_retry_falls_back_to_cpu() {
+        let config = GPUConfig {
+            memory_threshold: 1024,
+            max_gpu_memory_mb: 1,
+            allocation_retry: GPURetryPolicy { max_attempts: 2, initial_backoff: Duration::ZERO, ..GPURetryPolicy::default() },
+            ..GPUConfig::default()
+        };
+        let engine = GPUAccelerationEngine::new(config).unwrap();
+        let pattern = |id, size| UniversalPattern {
+            id,
+            pattern_type: "test".to_string(),
+            data: Vec::new(),
+            size,
+            compression_potential: 1.5,
+            gpu_optimized: true,
+        };
+        
+        // Fits the budget: reserved, processed, released
+        let result = engine.process_universal_pattern(&pattern(1, 512 * 1024)).unwrap();
+        assert_eq!(result.pattern_id, 1);
+        assert!(engine.memory_allocations.lock().unwrap().is_empty());
+        assert_eq!(engine.get_processing_stats().allocation_fallbacks, 0);
+        
+        // Another pattern holds most of the budget for every attempt, so this one runs on the CPU
+        engine.reserve_memory(&pattern(2, 768 * 1024)).unwrap();
+        let result = engine.process_universal_pattern(&pattern(3, 512 * 1024)).unwrap();
+        assert_eq!(result.gpu_utilization, 0.0);
+        assert_eq!(engine.get_processing_stats().allocation_fallbacks, 1);
+        assert_eq!(engine.memory_allocations.lock().unwrap().len(), 1);
+        
+        // Once it is released the same pattern gets its reservation
+        engine.release_memory(2);
+        engine.process_universal_pattern(&pattern(3, 512 * 1024)).unwrap();
+        assert_eq!(engine.get_processing_stats().allocation_fallbacks, 1);
     }
 }
@@ -0,0 +1,189 @@
+//! Language support
+//!
+//! Language identification, custom bridge handlers and language front ends
+//! shared by the compression pipeline and the language bridges, kept here so
+//! they build and are tested with the library.
+
+pub mod python;
+
+use crate::nexus_compression_engine::{language_for_extension, EXTENSIONS};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Supported language bridges
+#[derive(Debug, Clone, PartialEq)]
+pub enum SupportedLanguage {
+    Python,
+    Rust,
+    JavaScript,
+    TypeScript,
+    Cpp,
+    Go,
+    Java,
+    CSharp,
+}
+
+impl SupportedLanguage {
+    /// Every supported language, in declaration order
+    pub const ALL: [SupportedLanguage; 8] = [
+        SupportedLanguage::Python,
+        SupportedLanguage::Rust,
+        SupportedLanguage::JavaScript,
+        SupportedLanguage::TypeScript,
+        SupportedLanguage::Cpp,
+        SupportedLanguage::Go,
+        SupportedLanguage::Java,
+        SupportedLanguage::CSharp,
+    ];
+    
+    /// File extensions (without the dot) that belong to this language
+    ///
+    /// C sources and headers belong to `Cpp`, whose bridge also handles C,
+    /// matching `FromStr`, which maps `"c"` there.
+    pub fn extensions(&self) -> impl Iterator<Item = &'static str> + '_ {
+        EXTENSIONS.iter()
+            .filter(move |(_, language)| language.parse().ok().as_ref() == Some(self))
+            .map(|&(ext, _)| ext)
+    }
+    
+    /// Look up the language for a file extension, with or without the leading dot
+    ///
+    /// Matching is case-insensitive, so `"H"` and `".hpp"` both resolve to `Cpp`.
+    pub fn from_extension(ext: &str) -> Option<SupportedLanguage> {
+        language_for_extension(ext)?.parse().ok()
+    }
+}
+
+impl std::str::FromStr for SupportedLanguage {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "python" | "py" => Ok(SupportedLanguage::Python),
+            "rust" | "rs" => Ok(SupportedLanguage::Rust),
+            "javascript" | "js" => Ok(SupportedLanguage::JavaScript),
+            "typescript" | "ts" => Ok(SupportedLanguage::TypeScript),
+            "cpp" | "c++" | "c" => Ok(SupportedLanguage::Cpp),
+            "go" | "golang" => Ok(SupportedLanguage::Go),
+            "java" => Ok(SupportedLanguage::Java),
+            "csharp" | "c#" => Ok(SupportedLanguage::CSharp),
+            _ => Err(anyhow::anyhow!("Unsupported language: {}", s)),
+        }
+    }
+}
+
+/// Bridge operation handed to a custom language handler
+#[derive(Debug, Clone, Copy)]
+pub enum BridgeRequest<'a> {
+    /// Set up NEXUS in a project, as `init_integration` does
+    Init { project_dir: &'a Path, examples: bool },
+    /// Add NEXUS to a single file, as `add_nexus_to_file` does
+    AddToFile { file: &'a Path, generate_bridge: bool },
+}
+
+/// Custom bridge implementation for one language
+pub type BridgeHandler = Arc<dyn Fn(BridgeRequest<'_>) -> Result<()> + Send + Sync>;
+
+/// Custom language handlers, consulted before the built-in bridges
+///
+/// Lookup order is: registered handler, built-in bridge, then the generic
+/// integration (`init_integration`) or an error (`add_nexus_to_file`).
+/// Language names are matched case-insensitively and need not parse as a
+/// `SupportedLanguage`, so in-house languages can be bridged too.
+#[derive(Clone, Default)]
+pub struct BridgeConfig {
+    handlers: HashMap<String, BridgeHandler>,
+}
+
+impl BridgeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Register `handler` for `language`, replacing any earlier handler
+    pub fn with_handler(
+        mut self,
+        language: &str,
+        handler: impl Fn(BridgeRequest<'_>) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.handlers.insert(language.to_lowercase(), Arc::new(handler));
+        self
+    }
+    
+    /// Run the custom handler for `language`, if one is registered
+    pub fn run_custom(&self, language: &str, request: BridgeRequest<'_>) -> Option<Result<()>> {
+        self.handlers.get(&language.to_lowercase()).map(|handler| handler(request))
+    }
+}
+
+impl fmt::Debug for BridgeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut languages: Vec<&String> = self.handlers.keys().collect();
+        languages.sort();
+        f.debug_struct("BridgeConfig").field("handlers", &languages).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_language() {
+        let lang: SupportedLanguage = "python".parse().unwrap();
+        assert_eq!(lang, SupportedLanguage::Python);
+        
+        let lang: SupportedLanguage = "PYTHON".parse().unwrap();
+        assert_eq!(lang, SupportedLanguage::Python);
+    }
+    
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(language_for_extension("c"), Some("c"));
+        assert_eq!(language_for_extension(".H"), Some("c"));
+        assert_eq!(language_for_extension("hpp"), Some("cpp"));
+        assert_eq!(language_for_extension("txt"), None);
+        
+        assert_eq!(SupportedLanguage::from_extension("rs"), Some(SupportedLanguage::Rust));
+        assert_eq!(SupportedLanguage::from_extension(".h"), Some(SupportedLanguage::Cpp));
+        assert_eq!(SupportedLanguage::from_extension("TSX"), Some(SupportedLanguage::TypeScript));
+        assert_eq!(SupportedLanguage::from_extension("txt"), None);
+        assert_eq!(SupportedLanguage::from_extension(""), None);
+        
+        // Every extension is listed once and maps back to the language that lists it
+        for (index, (ext, _)) in EXTENSIONS.iter().enumerate() {
+            assert!(EXTENSIONS[index + 1..].iter().all(|(other, _)| other != ext), "{} listed twice", ext);
+        }
+        for language in SupportedLanguage::ALL {
+            for ext in language.extensions() {
+                assert_eq!(SupportedLanguage::from_extension(ext), Some(language.clone()));
+            }
+        }
+        assert_eq!(SupportedLanguage::Cpp.extensions().count(), 8);
+    }
+    
+    #[test]
+    fn test_bridge_config_handlers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let config = BridgeConfig::new().with_handler("Zig", move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match request {
+                BridgeRequest::Init { examples, .. } if examples => Err(anyhow::anyhow!("no examples for zig")),
+                _ => Ok(()),
+            }
+        });
+        
+        let dir = Path::new("project");
+        assert!(config.run_custom("zig", BridgeRequest::Init { project_dir: dir, examples: false }).unwrap().is_ok());
+        assert!(config.run_custom("ZIG", BridgeRequest::Init { project_dir: dir, examples: true }).unwrap().is_err());
+        assert!(config.run_custom("python", BridgeRequest::AddToFile { file: dir, generate_bridge: false }).is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(format!("{:?}", config), "BridgeConfig { handlers: [\"zig\"] }");
+    }
+}
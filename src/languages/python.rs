@@ -0,0 +1,200 @@
+//! Python front end
+//!
+//! Turns Python source into a Γ-AST without a full Python grammar, so Python
+//! files can be compressed and profiled like any other input.
+
+use crate::ast::Location;
+use crate::gamma_ast::{GammaAST, GammaNode, GammaNodeType};
+use crate::nexus_compression_engine::{CompressionConfig, NexusCompressionEngine};
+use crate::parser::{BasicLexer, Lexer, StringRules, Token, TokenType};
+use anyhow::{bail, Result};
+
+/// Build a Γ-AST from Python source so it can be compressed and profiled
+///
+/// Block structure comes from indentation: every logical line becomes a
+/// statement node whose children are its tokens and the more-indented lines
+/// below it. Lines the basic lexer cannot tokenize are kept whole as
+/// `Custom("python_line")` nodes so no source text is dropped. Fails, like
+/// Python's `IndentationError`, when a line dedents to a level that matches
+/// no enclosing block.
+pub fn to_gamma_ast(source: &str) -> Result<GammaAST> {
+    let mut ast = GammaAST::new();
+    ast.set_source_language("python".to_string());
+    
+    let mut next_id: u64 = 1;
+    // (header indent, node id, body indent once seen) of the enclosing blocks
+    let mut blocks: Vec<(usize, u64, Option<usize>)> = Vec::new();
+    
+    for (line_index, line) in source.lines().enumerate() {
+        let code = strip_python_comment(line);
+        let trimmed = code.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = code.len() - code.trim_start().len();
+        let location = Location::new(line_index + 1, indent + 1);
+        
+        while blocks.last().is_some_and(|&(block_indent, _, _)| block_indent >= indent) {
+            blocks.pop();
+        }
+        if let Some((_, _, body_indent)) = blocks.last_mut() {
+            match *body_indent {
+                Some(body) if indent < body => {
+                    bail!("line {}: unindent does not match any outer indentation level", line_index + 1);
+                }
+                Some(_) => {}
+                None => *body_indent = Some(indent),
+            }
+        }
+        
+        let statement_id = next_id;
+        next_id += 1;
+        let mut children = Vec::new();
+        
+        let mut lexer = BasicLexer::with_string_rules(StringRules::python());
+        let (node_type, value) = match lexer.tokenize(trimmed) {
+            Ok(tokens) => {
+                for token in tokens.iter().filter(|token| token.token_type != TokenType::EOF) {
+                    let token_type = match token.token_type {
+                        TokenType::Integer | TokenType::Float | TokenType::String | TokenType::Boolean => GammaNodeType::Literal,
+                        TokenType::Identifier if !PYTHON_KEYWORDS.contains(&token.value.as_str()) => GammaNodeType::Variable,
+                        _ => continue,
+                    };
+                    ast.add_node(GammaNode::builder(next_id, token_type)
+                        .direct(token.value.clone())
+                        .location(Location::new(line_index + 1, indent + token.location.column))
+                        .build());
+                    children.push(next_id);
+                    next_id += 1;
+                }
+                (python_statement_type(trimmed, &tokens), trimmed.to_string())
+            }
+            Err(_) => (GammaNodeType::Custom("python_line".to_string()), trimmed.to_string()),
+        };
+        
+        ast.add_node(GammaNode::builder(statement_id, node_type)
+            .direct(value)
+            .location(location)
+            .children(children)
+            .build());
+        
+        match blocks.last() {
+            Some(&(_, parent_id, _)) => {
+                if let Some(parent) = ast.get_node_mut(parent_id) {
+                    parent.children.push(statement_id);
+                }
+            }
+            None => ast.add_root(statement_id),
+        }
+        if trimmed.ends_with(':') {
+            blocks.push((indent, statement_id, None));
+        }
+    }
+    
+    Ok(ast)
+}
+
+/// Size of a Python file's Γ-AST and how well the engine compresses it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Compressibility {
+    /// Nodes in the Γ-AST `to_gamma_ast` builds
+    pub gamma_nodes: usize,
+    /// Ratio the default `CompressionConfig` achieves on it
+    pub compression_ratio: f64,
+}
+
+/// Run Python source through `to_gamma_ast` and the compression engine, for profiling reports
+pub fn measure_compressibility(source: &str) -> Result<Compressibility> {
+    let ast = to_gamma_ast(source)?;
+    let result = NexusCompressionEngine::new(CompressionConfig::default()).compress_ast_sync(&ast)?;
+    Ok(Compressibility {
+        gamma_nodes: ast.nodes.len(),
+        compression_ratio: result.compression_ratio,
+    })
+}
+
+/// Python keywords the basic lexer reports as plain identifiers
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+    "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+    "None", "True", "False",
+];
+
+/// Classify a Python statement by its leading keyword
+fn python_statement_type(statement: &str, tokens: &[Token]) -> GammaNodeType {
+    let keyword = statement.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+    match keyword {
+        "def" | "async" | "lambda" => GammaNodeType::Function,
+        "class" => GammaNodeType::Class,
+        "if" | "elif" | "else" => GammaNodeType::If,
+        "for" | "while" => GammaNodeType::Loop,
+        "try" | "except" | "finally" => GammaNodeType::Try,
+        "match" | "case" => GammaNodeType::Switch,
+        "import" | "from" => GammaNodeType::Module,
+        "return" | "pass" | "break" | "continue" | "raise" | "yield" | "with" | "del" | "global" | "assert" => GammaNodeType::Statement,
+        _ if tokens.iter().any(|token| token.token_type == TokenType::Assign) => GammaNodeType::Assignment,
+        _ => GammaNodeType::Expression,
+    }
+}
+
+/// Drop a trailing `#` comment, ignoring `#` inside string literals
+fn strip_python_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == '#' => return &line[..index],
+            None => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_to_gamma_ast_follows_indentation() {
+        let source = "import os\n\ndef f(x):  # comment\n    y = x + 1\n    if y > 2:\n        return \"a#b\"\n    return y\nprint(f(3))\n";
+        let ast = to_gamma_ast(source).unwrap();
+        
+        assert_eq!(ast.roots.len(), 3);
+        assert_eq!(ast.source_language, "python");
+        let function = ast.get_node(ast.roots[1]).unwrap();
+        assert_eq!(function.node_type, GammaNodeType::Function);
+        let nested: Vec<_> = function.children.iter()
+            .filter_map(|id| ast.get_node(*id))
+            .map(|node| node.node_type.clone())
+            .collect();
+        assert_eq!(nested, vec![
+            GammaNodeType::Variable,
+            GammaNodeType::Variable,
+            GammaNodeType::Assignment,
+            GammaNodeType::If,
+            GammaNodeType::Statement,
+        ]);
+        assert!(ast.validate_roots().is_ok());
+        
+        // The profile's compressibility figure comes from running this Γ-AST through the engine
+        let measured = measure_compressibility(source).unwrap();
+        assert_eq!(measured.gamma_nodes, ast.nodes.len());
+        assert!(measured.compression_ratio > 0.0);
+    }
+    
+    #[test]
+    fn test_to_gamma_ast_rejects_inconsistent_dedent() {
+        let error = to_gamma_ast("if x:\n        a = 1\n    b = 2\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 3: unindent"));
+        assert!(measure_compressibility("if x:\n        a = 1\n    b = 2\n").is_err());
+        
+        // Dedenting all the way out of a block, or to an enclosing body, is fine
+        assert!(to_gamma_ast("if x:\n    if y:\n        a = 1\n    b = 2\nc = 3\n").is_ok());
+    }
+}
@@ -16,17 +16,19 @@ pub mod nexus_compression_engine;
 pub mod ai_scheduler;
 pub mod neuromem;
 pub mod gpu_acceleration;
+pub mod languages;
 
 pub mod tests;
 
 // Re-export main types for convenience - REAL WORKING TECHNOLOGY
-pub use nexus_compression_engine::{NexusCompressionEngine, CompressionConfig, CompressionResult, CompressionError};
-pub use gamma_ast::{GammaAST, GammaNode, Pattern, CompressionLevel, CompressionStats};
+pub use nexus_compression_engine::{NexusCompressionEngine, AstSerializer, JsonAstSerializer, CompressionConfig, CompressionResult, CompressionError, CompressionBudget, CompressionCache, MetadataMode, BaselineComparison, compare_against_baselines, compress_source, compress_directory, language_for_extension, DirectoryArtifact, DirectoryCompressionReport, FileCompressionReport, decompress_to_ast, decompress_with_dictionaries};
+pub use gamma_ast::{GammaAST, GammaNode, GammaNodeBuilder, Pattern, sort_patterns_by_value, CompressionLevel, CompressionStats, space_saving_percent, AstPatch, ReconstructionTable, DecodeError, CompressionTrace, PassKind, PatternDictionary, SignatureHasher, IdGenerator, DuplicateIdError, VisitAction, Visitor, VisitorMut, PatternQuery, FrozenGammaAST, DetailedCompressionStats};
 
 // Re-export AI and optimization types - LEGITIMATE TECHNOLOGY
-pub use ai_scheduler::{AIProcess, GPUMemoryManager, SchedulerError, GPUAllocation, MemoryBlock};
-pub use neuromem::{MemoryRegion, AccessPattern, MemorySpike, LearningEngine, MemoryType};
-pub use gpu_acceleration::{GPUAccelerationEngine, GPUConfig, GPUDevice, GPUPatternResult};
+pub use ai_scheduler::{AIProcess, GPUMemoryManager, SchedulerError, GPUAllocation, MemoryBlock, SchedulerSnapshot, SchedulingPolicy};
+pub use neuromem::{MemoryRegion, AccessPattern, MemorySpike, LearningEngine, MemoryType, GpuBenefit, LearningEventSender, LearningEventReceiver, learning_event_channel};
+pub use gpu_acceleration::{GPUAccelerationEngine, GPUConfig, GPUDevice, GPUPatternResult, GPURetryPolicy};
+pub use languages::{SupportedLanguage, BridgeConfig, BridgeHandler, BridgeRequest};
 
 // Re-export test types for integration tests
-pub use tests::{TestResult, TestSuite};
+pub use tests::{OutputFormat, TestResult, TestSuite};
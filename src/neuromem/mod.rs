@@ -2,8 +2,8 @@
 //! Lightweight, concurrency-safe structure for recording access patterns
 
 use std::collections::{HashMap, VecDeque};
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Timestamp as milliseconds since epoch.
 pub type Timestamp = u64;
@@ -51,7 +51,7 @@ pub struct MemoryRegion {
 }
 
 /// Types of memory regions (informational).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MemoryType {
     Code,
     Data,
@@ -68,6 +68,43 @@ pub struct LearningEvent {
     pub description: String,
 }
 
+/// GPU track record for one pattern signature.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuBenefit {
+    /// Patterns with this signature sent to the GPU
+    pub runs: u32,
+    /// Runs whose compression improvement exceeded 1.0x
+    pub helped: u32,
+    pub total_improvement: f64,
+    pub total_time: Duration,
+}
+
+impl GpuBenefit {
+    /// Fraction of runs where the GPU helped.
+    pub fn help_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.helped as f64 / self.runs as f64
+        }
+    }
+
+    pub fn average_improvement(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.total_improvement / self.runs as f64
+        }
+    }
+}
+
+/// Runs of a signature needed before its history overrides the size threshold.
+pub const GPU_MIN_SAMPLES: u32 = 3;
+
+/// Lower bounds of the pattern quality bands, best band first; anything below
+/// the last bound falls in the final band.
+pub const QUALITY_BANDS: [f64; 3] = [0.8, 0.6, 0.4];
+
 /// Minimal learning engine storing parameters and history.
 #[derive(Debug, Clone)]
 pub struct LearningEngine {
@@ -76,6 +113,10 @@ pub struct LearningEngine {
     pub decay: f32,
     pub threshold: f32,
     pub history: Vec<LearningEvent>,
+    /// Learned multiplier of the base profitability threshold, one per quality band
+    pub profitability_multipliers: [f32; 4],
+    /// GPU outcomes keyed by pattern signature
+    pub gpu_benefit: HashMap<u64, GpuBenefit>,
 }
 
 impl LearningEngine {
@@ -86,7 +127,57 @@ impl LearningEngine {
             decay: 0.001,
             threshold: 0.01,
             history: Vec::new(),
+            // Better patterns start with a lower bar
+            profitability_multipliers: [1.0 / 2.0, 2.0 / 3.0, 5.0 / 6.0, 1.0],
+            gpu_benefit: HashMap::new(),
+        }
+    }
+
+    /// Record how a pattern fared on the GPU; pass 0.0 improvement for a failed run.
+    pub fn record_gpu_outcome(&mut self, signature: u64, improvement: f64, time: Duration) {
+        let benefit = self.gpu_benefit.entry(signature).or_default();
+        benefit.runs += 1;
+        if improvement > 1.0 {
+            benefit.helped += 1;
+        }
+        benefit.total_improvement += improvement;
+        benefit.total_time += time;
+    }
+
+    /// Whether history says to send this signature to the GPU.
+    ///
+    /// `None` until the signature has `GPU_MIN_SAMPLES` runs, so the caller's
+    /// default routing applies; then `true` if the GPU helped at least half the time.
+    pub fn prefers_gpu(&self, signature: u64) -> Option<bool> {
+        self.gpu_benefit.get(&signature)
+            .filter(|benefit| benefit.runs >= GPU_MIN_SAMPLES)
+            .map(|benefit| benefit.help_rate() >= 0.5)
+    }
+
+    /// Index into `profitability_multipliers` for a pattern quality in 0.0..=1.0.
+    pub fn quality_band(quality: f64) -> usize {
+        QUALITY_BANDS.iter().position(|&bound| quality > bound).unwrap_or(QUALITY_BANDS.len())
+    }
+
+    /// Savings a pattern of this quality must be predicted to make before it is applied.
+    pub fn profitability_threshold(&self, quality: f64, base: usize) -> usize {
+        (base as f64 * self.profitability_multipliers[Self::quality_band(quality)] as f64) as usize
+    }
+
+    /// Feed back the savings a pattern actually made against its prediction.
+    ///
+    /// Over-predicting bands get a higher bar and under-predicting bands a lower
+    /// one, each step scaled by `learning_rate` and clamped to 0.1..=10.0.
+    pub fn observe_savings(&mut self, quality: f64, predicted: usize, actual: isize) {
+        if predicted == 0 {
+            return;
         }
+        let band = Self::quality_band(quality);
+        let error = ((predicted as f64 - actual as f64) / predicted as f64).clamp(-1.0, 1.0) as f32;
+        let multiplier = &mut self.profitability_multipliers[band];
+        *multiplier = (*multiplier * (1.0 + self.learning_rate * error)).clamp(0.1, 10.0);
+        let updated = *multiplier;
+        self.record_event(error, format!("Quality band {} threshold multiplier: {:.3}", band, updated));
     }
 
     /// Record a learning event for diagnostics.
@@ -121,6 +212,47 @@ impl LearningEngine {
     }
 }
 
+/// Create a queue for learning events recorded away from the engine's lock.
+///
+/// Async code records through the sender without waiting on the engine; whoever
+/// next holds the engine drains the receiver into it.
+pub fn learning_event_channel() -> (LearningEventSender, LearningEventReceiver) {
+    let (sender, receiver) = mpsc::channel();
+    (LearningEventSender(sender), LearningEventReceiver(Mutex::new(receiver)))
+}
+
+/// Cloneable, non-blocking handle for queueing learning events.
+#[derive(Debug, Clone)]
+pub struct LearningEventSender(mpsc::Sender<LearningEvent>);
+
+impl LearningEventSender {
+    /// Queue a learning event, timestamped now; dropped if the receiver is gone.
+    pub fn record_event(&self, change: f32, description: impl Into<String>) {
+        let _ = self.0.send(LearningEvent {
+            timestamp: now_ms(),
+            change,
+            description: description.into(),
+        });
+    }
+}
+
+/// Receiving end of `learning_event_channel`.
+#[derive(Debug)]
+pub struct LearningEventReceiver(Mutex<mpsc::Receiver<LearningEvent>>);
+
+impl LearningEventReceiver {
+    /// Move every queued event into `engine`'s history, returning how many there were.
+    ///
+    /// Meant to be called with the engine already locked, so the inner lock is
+    /// never contended.
+    pub fn drain_into(&self, engine: &mut LearningEngine) -> usize {
+        let receiver = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = engine.history.len();
+        engine.history.extend(receiver.try_iter());
+        engine.history.len() - before
+    }
+}
+
 /// Aggregate statistics returned by the manager.
 #[derive(Debug, Clone, Default)]
 pub struct MemStats {
@@ -398,4 +530,64 @@ mod tests {
         let s = nm.stats().unwrap();
         assert_eq!(s.region_count, 1);
     }
+
+    #[test]
+    fn gpu_routing_follows_history() {
+        let mut le = LearningEngine::new();
+        let (helps, hurts) = (1, 2);
+        for _ in 0..GPU_MIN_SAMPLES {
+            assert_eq!(le.prefers_gpu(helps), None);
+            le.record_gpu_outcome(helps, 1.8, Duration::from_millis(2));
+            le.record_gpu_outcome(hurts, 0.0, Duration::from_millis(9));
+        }
+        assert_eq!(le.prefers_gpu(helps), Some(true));
+        assert_eq!(le.prefers_gpu(hurts), Some(false));
+        assert_eq!(le.prefers_gpu(3), None);
+
+        let stats = &le.gpu_benefit[&helps];
+        assert_eq!(stats.help_rate(), 1.0);
+        assert!((stats.average_improvement() - 1.8).abs() < 1e-9);
+        assert_eq!(stats.total_time, Duration::from_millis(6));
+    }
+
+    #[test]
+    fn profitability_thresholds_adapt() {
+        let mut le = LearningEngine::new();
+        assert_eq!(LearningEngine::quality_band(0.9), 0);
+        assert_eq!(LearningEngine::quality_band(0.8), 1);
+        assert_eq!(LearningEngine::quality_band(0.1), 3);
+        assert_eq!(le.profitability_threshold(0.9, 48), 24);
+        assert_eq!(le.profitability_threshold(0.0, 48), 48);
+
+        // High-quality patterns keep saving far less than predicted
+        for _ in 0..10 {
+            le.observe_savings(0.9, 1000, 100);
+        }
+        assert!(le.profitability_threshold(0.9, 48) > 24);
+        // Low-quality patterns beat their predictions
+        for _ in 0..10 {
+            le.observe_savings(0.1, 100, 400);
+        }
+        assert!(le.profitability_threshold(0.1, 48) < 48);
+        assert_eq!(le.profitability_multipliers[1], 2.0 / 3.0);
+        assert_eq!(le.stats().0, 20);
+
+        le.observe_savings(0.5, 0, 10);
+        assert_eq!(le.stats().0, 20);
+    }
+
+    #[test]
+    fn queued_events_drain_into_engine() {
+        let (sender, receiver) = learning_event_channel();
+        let mut le = LearningEngine::new();
+        let shared = sender.clone();
+        std::thread::spawn(move || shared.record_event(0.5, "from another thread")).join().unwrap();
+        sender.record_event(-0.25, "local");
+        assert!(le.history.is_empty());
+
+        assert_eq!(receiver.drain_into(&mut le), 2);
+        assert_eq!(le.stats(), (2, 0.125));
+        assert_eq!(le.history[0].description, "from another thread");
+        assert_eq!(receiver.drain_into(&mut le), 0);
+    }
 }
@@ -3,13 +3,23 @@
 //! This is the consolidated, working compression engine that actually compresses code.
 //! No false claims, no broken algorithms - just real compression that works.
 
-use crate::gamma_ast::{GammaAST, GammaNode, Pattern, CompressionLevel, GammaNodeType, GammaValue};
-use std::collections::{HashMap, VecDeque};
-use std::time::{Duration, Instant};
+use crate::gamma_ast::{sort_patterns_by_value, space_saving_percent, GammaAST, Pattern, PatternDictionary, ReconstructionTable, CompressionLevel, CompressionTrace, DetailedCompressionStats, GammaValue, PassKind};
+use crate::parser::{BasicParser, ParseError, Parser, StringRules};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use serde::{Serialize, Deserialize};
 
+// `std::time::Instant::now` panics on wasm32-unknown-unknown
+#[cfg(feature = "wasm")]
+use web_time::Instant;
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
+
 /// Real compression configuration - no false promises
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CompressionConfig {
     /// Enable pattern recognition (actually works)
     pub enable_patterns: bool,
@@ -21,6 +31,74 @@ pub struct CompressionConfig {
     pub target_ratio: f64,
     /// Maximum memory usage for compression
     pub max_memory_mb: u64,
+    /// Record which passes changed each node (snapshots the AST per pass, so it is slow)
+    #[serde(default)]
+    pub trace_passes: bool,
+    /// Break savings down by pass and node type in `CompressionResult::detailed_stats` (also snapshots the AST per pass)
+    #[serde(default)]
+    pub detailed_stats: bool,
+    /// Break `PatternRef` cycles by restoring a node's original value instead of failing
+    #[serde(default)]
+    pub repair_pattern_cycles: bool,
+    /// In `compress_directory`, compress all files as one Γ-AST so patterns are shared across files
+    #[serde(default)]
+    pub compress_as_corpus: bool,
+    /// What the metadata pass does with node metadata, independent of value compression
+    #[serde(default)]
+    pub metadata_mode: MetadataMode,
+    /// Keep at most this many patterns, the most valuable first, to bound memory on huge inputs
+    #[serde(default = "default_max_patterns")]
+    pub max_patterns: usize,
+    /// Forbid every pass from clearing node children (lossless); `false` lets passes drop them for ratio
+    #[serde(default = "default_preserve_children")]
+    pub preserve_children: bool,
+    /// Nodes every pass leaves untouched, keeping their values `Direct` and their children intact
+    #[serde(default)]
+    pub pinned_nodes: HashSet<u64>,
+    /// Reject inputs with more nodes than this with `InputTooLarge` before any work (`None` = no limit)
+    #[serde(default)]
+    pub max_input_nodes: Option<usize>,
+    /// Return the input unchanged when the compressed AST serializes larger than it
+    #[serde(default = "default_never_expand")]
+    pub never_expand: bool,
+    /// Log a warning when metadata exceeds this fraction of the compressed size (`None` = never)
+    #[serde(default = "default_metadata_warning_fraction")]
+    pub metadata_warning_fraction: Option<f64>,
+    /// Store a `ReconstructionTable` on the compressed AST so `GammaAST::decompress` can undo the lossy passes
+    ///
+    /// The table holds only what the passes threw away and counts towards
+    /// `compressed_size`. `compress_ast_in_place` keeps its input's serialized
+    /// bytes to build it.
+    #[serde(default)]
+    pub reconstructible: bool,
+}
+
+fn default_max_patterns() -> usize {
+    10_000
+}
+
+fn default_preserve_children() -> bool {
+    true
+}
+
+fn default_never_expand() -> bool {
+    true
+}
+
+fn default_metadata_warning_fraction() -> Option<f64> {
+    Some(0.5)
+}
+
+/// Treatment of node metadata by the metadata pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MetadataMode {
+    /// Keep metadata verbatim; the pass does not run
+    #[default]
+    Preserve,
+    /// Intern keys and values into the AST's shared metadata dictionary (`GammaAST::expand_metadata` restores them)
+    Dictionary,
+    /// Remove all metadata
+    Drop,
 }
 
 impl Default for CompressionConfig {
@@ -31,10 +109,44 @@ impl Default for CompressionConfig {
             enable_deduplication: true,
             target_ratio: 3.0, // Realistic 3x compression target
             max_memory_mb: 512,
+            trace_passes: false,
+            detailed_stats: false,
+            repair_pattern_cycles: false,
+            compress_as_corpus: false,
+            metadata_mode: MetadataMode::Preserve,
+            max_patterns: default_max_patterns(),
+            preserve_children: default_preserve_children(),
+            pinned_nodes: HashSet::new(),
+            max_input_nodes: None,
+            never_expand: default_never_expand(),
+            metadata_warning_fraction: default_metadata_warning_fraction(),
+            reconstructible: false,
         }
     }
 }
 
+/// Best-effort time box checked between compression passes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressionBudget {
+    /// Stop starting new passes once this much time has elapsed
+    pub max_duration: Option<Duration>,
+    /// Maximum number of enabled passes to run
+    pub max_passes: Option<usize>,
+}
+
+impl CompressionBudget {
+    /// A budget that never interrupts compression
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+    
+    /// Check whether another pass may start
+    fn is_exhausted(&self, passes_run: usize, elapsed: Duration) -> bool {
+        self.max_passes.is_some_and(|max| passes_run >= max)
+            || self.max_duration.is_some_and(|max| elapsed >= max)
+    }
+}
+
 /// Compression result with real metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressionResult {
@@ -44,9 +156,55 @@ pub struct CompressionResult {
     pub patterns_identified: usize,
     pub processing_time: Duration,
     pub memory_usage: usize,
+    /// True when a budget stopped compression before every pass ran
+    pub incomplete: bool,
+    /// Per-node pass history, present when `trace_passes` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<CompressionTrace>,
+    /// Bytes saved per pass and per node type, present when `detailed_stats` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detailed_stats: Option<DetailedCompressionStats>,
+    /// Nodes in the input AST
+    #[serde(default)]
+    pub nodes_original: usize,
+    /// Nodes in the compressed AST
+    #[serde(default)]
+    pub nodes_compressed: usize,
+    /// Input nodes whose IDs are missing from the compressed AST (0 when nothing was lost)
+    #[serde(default)]
+    pub nodes_lost: usize,
+    /// True when compression would have grown the serialized AST, so the input was kept (`never_expand`)
+    #[serde(default)]
+    pub returned_original: bool,
+    /// Metadata bytes in the compressed AST (`GammaAST::total_metadata_bytes`)
+    #[serde(default)]
+    pub metadata_bytes: usize,
+    /// True when this is a copy of an earlier result served by the engine's `CompressionCache`
+    #[serde(default)]
+    pub from_cache: bool,
+}
+
+impl CompressionResult {
+    /// Percentage of the original size saved, negative when compression expanded the AST
+    ///
+    /// Computed like `CompressionStats::compression_percentage`; 0.0 when `original_size` is 0.
+    pub fn space_saving_percent(&self) -> f64 {
+        space_saving_percent(self.original_size, self.compressed_size)
+    }
+    
+    /// Check if the compressed AST is larger than the original
+    pub fn is_expansion(&self) -> bool {
+        self.compressed_size > self.original_size
+    }
 }
 
+/// Boxed underlying cause carried by `CompressionError` variants
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 /// Compression error types
+///
+/// Variants wrapping a foreign failure keep it as `Error::source()`, so the
+/// full cause chain survives `?` propagation.
 #[derive(Debug, thiserror::Error)]
 pub enum CompressionError {
     #[error("Pattern application failed: {0}")]
@@ -57,12 +215,213 @@ pub enum CompressionError {
     Deduplication(String),
     #[error("Memory limit exceeded")]
     MemoryLimitExceeded,
+    #[error("Input has {nodes} nodes, more than the limit of {limit}")]
+    InputTooLarge { nodes: usize, limit: usize },
+    #[error("Parsing failed: {0}")]
+    Parse(#[source] BoxError),
+    #[error("Serialization failed: {0}")]
+    Serialization(#[source] BoxError),
+    #[error("Dictionary {dict_id} has no entry {entry}")]
+    UnresolvedDictRef { dict_id: u32, entry: u32 },
+    #[error("Pattern references form a cycle through {0:?}")]
+    PatternCycle(Vec<u64>),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ParseError> for CompressionError {
+    fn from(error: ParseError) -> Self {
+        CompressionError::Parse(Box::new(error))
+    }
+}
+
+impl From<serde_json::Error> for CompressionError {
+    fn from(error: serde_json::Error) -> Self {
+        CompressionError::Serialization(Box::new(error))
+    }
+}
+
+/// Per-file outcome of `compress_directory`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCompressionReport {
+    /// Path relative to the compressed directory
+    pub path: PathBuf,
+    /// Language detected from the file extension
+    pub language: String,
+    /// Size of the source file in bytes
+    pub source_bytes: usize,
+    /// Number of Γ-AST nodes built from the file
+    pub node_count: usize,
+    /// Compression metrics for this file; `None` in corpus mode, see `corpus_result`
+    pub result: Option<CompressionResult>,
+}
+
+/// Compressed output of `compress_directory`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryArtifact {
+    /// Relative path of every compressed file, in report order
+    pub files: Vec<PathBuf>,
+    /// One compressed Γ-AST per file, or a single shared Γ-AST in corpus mode
+    pub asts: Vec<GammaAST>,
+    /// Root IDs of each file within the shared Γ-AST (corpus mode only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corpus_roots: Vec<Vec<u64>>,
+}
+
+/// Result of compressing every supported source file under a directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryCompressionReport {
+    /// Files that were compressed
+    pub files: Vec<FileCompressionReport>,
+    /// Supported files that could not be read or parsed, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+    /// Metrics for the shared Γ-AST when `compress_as_corpus` is enabled
+    pub corpus_result: Option<CompressionResult>,
+    /// The combined compressed artifact
+    pub artifact: DirectoryArtifact,
+}
+
+/// Wire format for compressed Γ-ASTs
+///
+/// Implement this to emit protobuf, CBOR or any other encoding while reusing
+/// the compression passes; `JsonAstSerializer` is the built-in format.
+pub trait AstSerializer: Send + Sync {
+    fn serialize(&self, ast: &GammaAST) -> Vec<u8>;
+    fn deserialize(&self, bytes: &[u8]) -> Result<GammaAST, CompressionError>;
+    
+    /// Stream the encoding of `ast` into `writer`
+    ///
+    /// Defaults to writing the `serialize` buffer; override it so large
+    /// outputs are never held in memory whole.
+    fn serialize_to(&self, ast: &GammaAST, writer: &mut dyn Write) -> Result<(), CompressionError> {
+        writer.write_all(&self.serialize(ast))?;
+        Ok(())
+    }
+}
+
+/// The JSON format written by `compress_source` and read by `decompress_to_ast`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonAstSerializer;
+
+impl AstSerializer for JsonAstSerializer {
+    fn serialize(&self, ast: &GammaAST) -> Vec<u8> {
+        serde_json::to_vec(ast).expect("GammaAST always serializes to JSON")
+    }
+    
+    fn deserialize(&self, bytes: &[u8]) -> Result<GammaAST, CompressionError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+    
+    fn serialize_to(&self, ast: &GammaAST, writer: &mut dyn Write) -> Result<(), CompressionError> {
+        Ok(serde_json::to_writer(writer, ast)?)
+    }
+}
+
+/// Sink that only counts the bytes written to it
+#[derive(Default)]
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Bounded cache of compressed ASTs keyed by `GammaAST::content_hash`, evicting the least recently used
+///
+/// Entries are only valid for the configuration that produced them, so the
+/// engine empties the cache whenever its `config` has changed since. Each
+/// entry keeps its input, and a hit is only trusted once the input compares
+/// equal, so a hash collision is a miss rather than another AST's output.
+#[derive(Debug, Clone)]
+pub struct CompressionCache {
+    capacity: usize,
+    entries: HashMap<u64, CacheEntry>,
+    /// Keys from least to most recently used
+    recency: VecDeque<u64>,
+    config: Option<CompressionConfig>,
+}
+
+impl CompressionCache {
+    /// A cache holding at most `capacity` entries (0 disables caching)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+            config: None,
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+    
+    /// Look up the entry for `input`, marking it most recently used
+    fn get(&mut self, key: u64, input: &GammaAST) -> Option<&CacheEntry> {
+        if !self.entries.get(&key)?.input.same_content(input) {
+            return None;
+        }
+        let position = self.recency.iter().position(|&used| used == key)?;
+        self.recency.remove(position);
+        self.recency.push_back(key);
+        self.entries.get(&key)
+    }
+    
+    fn insert(&mut self, key: u64, entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key, entry).is_some() {
+            self.recency.retain(|&used| used != key);
+        }
+        self.recency.push_back(key);
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self.recency.pop_front() else { break };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// A cached compression run and the input it was for
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    input: GammaAST,
+    compressed: GammaAST,
+    result: CompressionResult,
+}
+
+/// What the checks after the passes read from the input, captured before they run
+///
+/// Lets the passes work on the input itself in `compress_ast_in_place` without a copy.
+struct InputShape {
+    size: usize,
+    roots: Vec<u64>,
+    node_ids: HashSet<u64>,
+    /// Value and level of every node, only captured when cycle repair may restore them
+    values: HashMap<u64, (GammaValue, CompressionLevel)>,
 }
 
 /// The REAL working compression engine
 pub struct NexusCompressionEngine {
     pub config: CompressionConfig,
     compression_history: VecDeque<CompressionResult>,
+    serializer: Box<dyn AstSerializer>,
+    cache: Option<CompressionCache>,
 }
 
 impl NexusCompressionEngine {
@@ -71,40 +430,311 @@ impl NexusCompressionEngine {
         Self {
             config,
             compression_history: VecDeque::new(),
+            serializer: Box::new(JsonAstSerializer),
+            cache: None,
         }
     }
     
+    /// Reuse results for inputs with the same content, keeping up to `capacity` of them
+    ///
+    /// Every entry point except `compress_ast_in_place` consults the cache. A
+    /// hit returns a copy of the earlier result with `from_cache` set; results
+    /// cut short by a budget are not stored.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(CompressionCache::new(capacity));
+        self
+    }
+    
+    /// The result cache, if `with_cache` enabled one
+    pub fn cache(&self) -> Option<&CompressionCache> {
+        self.cache.as_ref()
+    }
+    
+    /// Use a different wire format for `compress_to_bytes` and `decompress_bytes`
+    pub fn with_serializer(mut self, serializer: impl AstSerializer + 'static) -> Self {
+        self.serializer = Box::new(serializer);
+        self
+    }
+    
+    /// Compress an AST and encode the result with the engine's serializer
+    pub fn compress_to_bytes(&mut self, ast: &GammaAST) -> Result<(CompressionResult, Vec<u8>), CompressionError> {
+        let (result, compressed_ast) = self.run_pipeline(ast, CompressionBudget::unlimited())?;
+        Ok((result, self.serializer.serialize(&compressed_ast)))
+    }
+    
+    /// Compress an AST and stream the encoded result into `writer`
+    ///
+    /// The built-in JSON format is written as it is produced rather than
+    /// buffered whole; wrap unbuffered sinks such as a `TcpStream` in a `BufWriter`.
+    pub fn compress_ast_to_writer<W: Write>(&mut self, ast: &GammaAST, writer: &mut W) -> Result<CompressionResult, CompressionError> {
+        let (result, compressed_ast) = self.run_pipeline(ast, CompressionBudget::unlimited())?;
+        self.serializer.serialize_to(&compressed_ast, writer)?;
+        Ok(result)
+    }
+    
+    /// Length of `ast`'s encoding, counted without buffering it
+    fn serialized_len(&self, ast: &GammaAST) -> Result<usize, CompressionError> {
+        let mut counter = ByteCounter::default();
+        self.serializer.serialize_to(ast, &mut counter)?;
+        Ok(counter.0)
+    }
+    
+    /// Decode bytes written by `compress_to_bytes` with the same serializer
+    ///
+    /// Nodes are then decoded according to their compression level; lossy ones stay encoded.
+    pub fn decompress_bytes(&self, bytes: &[u8]) -> Result<GammaAST, CompressionError> {
+        let mut ast = self.serializer.deserialize(bytes)?;
+        ast.decompress_levels();
+        Ok(ast)
+    }
+    
     /// Compress an AST using only working algorithms
     pub async fn compress_ast(&mut self, ast: &GammaAST) -> Result<CompressionResult, CompressionError> {
-        let start_time = Instant::now();
-        let original_size = self.calculate_ast_size(ast);
+        self.compress_ast_with_budget(ast, CompressionBudget::unlimited()).await
+    }
+    
+    /// Compress an AST, stopping between passes once the budget runs out
+    ///
+    /// The result reflects whatever passes completed and has `incomplete` set
+    /// if any enabled pass was skipped.
+    pub async fn compress_ast_with_budget(
+        &mut self,
+        ast: &GammaAST,
+        budget: CompressionBudget,
+    ) -> Result<CompressionResult, CompressionError> {
+        self.run_pipeline(ast, budget).map(|(result, _)| result)
+    }
+    
+    /// Compress an AST without an async runtime
+    ///
+    /// Same as `compress_ast`. This is the entry point for `wasm32` builds, which
+    /// have no tokio; enable the `wasm` feature there so timing works in the browser.
+    pub fn compress_ast_sync(&mut self, ast: &GammaAST) -> Result<CompressionResult, CompressionError> {
+        self.run_pipeline(ast, CompressionBudget::unlimited()).map(|(result, _)| result)
+    }
+    
+    /// Compress an AST in place, avoiding the copy `compress_ast` makes
+    ///
+    /// The passes run directly on `ast`. When `never_expand` or `reconstructible`
+    /// needs the input afterwards, only its serialized bytes are kept. The cache is
+    /// neither consulted nor filled. On error `ast` may be left partially
+    /// compressed, so keep a copy if you need to recover the input.
+    pub async fn compress_ast_in_place(&mut self, ast: &mut GammaAST) -> Result<CompressionResult, CompressionError> {
+        self.check_input_size(ast)?;
+        let original = (self.config.never_expand || self.config.reconstructible)
+            .then(|| self.serializer.serialize(ast));
+        let input = self.input_shape(ast);
+        let mut result = self.run_passes(&input, ast, CompressionBudget::unlimited())?;
+        let Some(original) = original else {
+            return Ok(result);
+        };
+        if self.config.reconstructible {
+            let table = ReconstructionTable::between(&self.serializer.deserialize(&original)?, ast);
+            ast.reconstruction = Some(table);
+            self.recount_compressed_size(&mut result, ast);
+        }
+        if self.config.never_expand && self.serialized_len(ast)? > original.len() {
+            *ast = self.serializer.deserialize(&original)?;
+            self.mark_returned_original(&mut result, ast);
+        }
+        Ok(result)
+    }
+    
+    /// Capture what the post-pass checks need from `ast` before the passes change it
+    fn input_shape(&self, ast: &GammaAST) -> InputShape {
+        InputShape {
+            size: self.calculate_ast_size(ast),
+            roots: ast.roots.clone(),
+            node_ids: ast.nodes.keys().copied().collect(),
+            values: if self.config.repair_pattern_cycles {
+                ast.nodes.iter().map(|(&id, node)| (id, (node.value.clone(), node.compression_level))).collect()
+            } else {
+                HashMap::new()
+            },
+        }
+    }
+    
+    /// Fail fast with `InputTooLarge` when `ast` exceeds `max_input_nodes`
+    fn check_input_size(&self, ast: &GammaAST) -> Result<(), CompressionError> {
+        match self.config.max_input_nodes {
+            Some(limit) if ast.nodes.len() > limit => Err(CompressionError::InputTooLarge { nodes: ast.nodes.len(), limit }),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Run the compression passes, returning the metrics and the compressed AST
+    fn run_pipeline(
+        &mut self,
+        ast: &GammaAST,
+        budget: CompressionBudget,
+    ) -> Result<(CompressionResult, GammaAST), CompressionError> {
+        self.check_input_size(ast)?;
+        let key = self.cache.is_some().then(|| ast.content_hash());
+        if let Some(hit) = key.and_then(|key| self.cached(key, ast)) {
+            return Ok(hit);
+        }
         
-        // Start with the original AST
         let mut compressed_ast = ast.clone();
+        let input = self.input_shape(ast);
+        let mut result = self.run_passes(&input, &mut compressed_ast, budget)?;
+        if self.config.reconstructible {
+            compressed_ast.reconstruction = Some(ReconstructionTable::between(ast, &compressed_ast));
+            self.recount_compressed_size(&mut result, &compressed_ast);
+        }
+        if self.config.never_expand && self.serialized_len(&compressed_ast)? > self.serialized_len(ast)? {
+            compressed_ast = ast.clone();
+            self.mark_returned_original(&mut result, ast);
+        }
+        if let (Some(key), Some(cache)) = (key, self.cache.as_mut()) {
+            if !result.incomplete {
+                cache.insert(key, CacheEntry {
+                    input: ast.clone(),
+                    compressed: compressed_ast.clone(),
+                    result: result.clone(),
+                });
+            }
+        }
+        Ok((result, compressed_ast))
+    }
+    
+    /// Copy of the cached output for `key`, recorded in the history like a fresh run
+    fn cached(&mut self, key: u64, input: &GammaAST) -> Option<(CompressionResult, GammaAST)> {
+        let cache = self.cache.as_mut()?;
+        if cache.config.as_ref() != Some(&self.config) {
+            cache.clear();
+            cache.config = Some(self.config.clone());
+            return None;
+        }
+        let entry = cache.get(key, input)?;
+        let (compressed_ast, mut result) = (entry.compressed.clone(), entry.result.clone());
+        result.from_cache = true;
+        self.record_history(&result);
+        Some((result, compressed_ast))
+    }
+    
+    fn record_history(&mut self, result: &CompressionResult) {
+        self.compression_history.push_back(result.clone());
+        if self.compression_history.len() > 100 {
+            self.compression_history.pop_front();
+        }
+    }
+    
+    /// Warn when metadata makes up more of the compressed AST than `metadata_warning_fraction` allows
+    fn warn_on_metadata_bloat(&self, metadata_bytes: usize, compressed_size: usize) -> bool {
+        warn_on_metadata_bloat(self.config.metadata_warning_fraction, metadata_bytes, compressed_size)
+    }
+    
+    /// Measure `compressed_ast` again after it changed outside the passes, in the history as well
+    fn recount_compressed_size(&mut self, result: &mut CompressionResult, compressed_ast: &GammaAST) {
+        result.compressed_size = self.calculate_ast_size(compressed_ast);
+        result.compression_ratio = if result.compressed_size > 0 {
+            result.original_size as f64 / result.compressed_size as f64
+        } else {
+            1.0
+        };
+        if let Some(last) = self.compression_history.back_mut() {
+            *last = result.clone();
+        }
+    }
+    
+    /// Report `result` as `original`, the uncompressed input returned in its place, in the history as well
+    ///
+    /// The trace and per-pass stats describe the discarded output, so they are dropped.
+    fn mark_returned_original(&mut self, result: &mut CompressionResult, original: &GammaAST) {
+        result.compressed_size = result.original_size;
+        result.compression_ratio = 1.0;
+        result.nodes_compressed = result.nodes_original;
+        result.nodes_lost = 0;
+        result.metadata_bytes = original.total_metadata_bytes();
+        result.trace = None;
+        result.detailed_stats = None;
+        result.returned_original = true;
+        if let Some(last) = self.compression_history.back_mut() {
+            *last = result.clone();
+        }
+    }
+    
+    /// Run the compression passes over `compressed_ast`, transforming it in place
+    ///
+    /// `input` describes `compressed_ast` as it was before the passes, for the checks that follow them.
+    fn run_passes(
+        &mut self,
+        input: &InputShape,
+        compressed_ast: &mut GammaAST,
+        budget: CompressionBudget,
+    ) -> Result<CompressionResult, CompressionError> {
+        let start_time = Instant::now();
+        let original_size = input.size;
+        
+        let mut passes_run = 0;
+        let mut incomplete = false;
+        let mut trace = self.config.trace_passes.then(CompressionTrace::default);
+        let mut detailed_stats = self.config.detailed_stats.then(DetailedCompressionStats::default);
+        let mut value_table_refs = HashSet::new();
         
         // WORKING COMPRESSION PIPELINE - Only proven functions
         
         // 1. Apply value compression (strings, numbers) - this actually saves space
         if self.config.enable_value_compression {
-            self.apply_value_compression(&mut compressed_ast)?;
+            if budget.is_exhausted(passes_run, start_time.elapsed()) {
+                incomplete = true;
+            } else {
+                traced(compressed_ast, PassKind::ValueCompression, &mut trace, &mut detailed_stats, |ast| {
+                    value_table_refs = self.apply_value_compression(ast)?;
+                    Ok(())
+                })?;
+                passes_run += 1;
+            }
         }
         
         // 2. Apply basic deduplication (only if it saves space)
-        if self.config.enable_deduplication {
-            self.apply_basic_deduplication(&mut compressed_ast)?;
+        if self.config.enable_deduplication && !incomplete {
+            if budget.is_exhausted(passes_run, start_time.elapsed()) {
+                incomplete = true;
+            } else {
+                traced(compressed_ast, PassKind::Deduplication, &mut trace, &mut detailed_stats, |ast| {
+                    self.apply_basic_deduplication(ast)
+                })?;
+                passes_run += 1;
+            }
         }
         
         // 3. Apply pattern compression (only if it saves space)
         let mut patterns = Vec::new();
-        if self.config.enable_patterns {
-            patterns = self.identify_profitable_patterns(&compressed_ast);
-            for pattern in &patterns {
-                self.apply_pattern_to_ast(&mut compressed_ast, pattern)?;
+        if self.config.enable_patterns && !incomplete {
+            if budget.is_exhausted(passes_run, start_time.elapsed()) {
+                incomplete = true;
+            } else {
+                patterns = self.identify_profitable_patterns(compressed_ast);
+                // No per-pattern potential estimate here, so frequency × size decides the order
+                sort_patterns_by_value(&mut patterns, |_| 1.0);
+                patterns.truncate(self.config.max_patterns);
+                traced(compressed_ast, PassKind::PatternApplication, &mut trace, &mut detailed_stats, |ast| {
+                    patterns.iter().try_for_each(|pattern| self.apply_pattern_to_ast(ast, pattern))
+                })?;
+                passes_run += 1;
             }
         }
         
+        // 4. Apply the metadata pass, which owns metadata so the passes above leave it alone
+        if self.config.metadata_mode != MetadataMode::Preserve && !incomplete {
+            if budget.is_exhausted(passes_run, start_time.elapsed()) {
+                incomplete = true;
+            } else {
+                traced(compressed_ast, PassKind::Metadata, &mut trace, &mut detailed_stats, |ast| {
+                    self.apply_metadata_mode(ast);
+                    Ok(())
+                })?;
+            }
+        }
+        
+        // Reference cycles would make decompression loop forever
+        self.check_pattern_cycles(input, compressed_ast, &value_table_refs)?;
+        
         // Calculate real compression metrics
-        let compressed_size = self.calculate_ast_size(&compressed_ast);
+        let compressed_size = self.calculate_ast_size(compressed_ast);
+        let metadata_bytes = compressed_ast.total_metadata_bytes();
+        self.warn_on_metadata_bloat(metadata_bytes, compressed_size);
         let compression_ratio = if compressed_size > 0 {
             original_size as f64 / compressed_size as f64
         } else {
@@ -112,7 +742,7 @@ impl NexusCompressionEngine {
         };
         
         // Verify structural integrity
-        if !self.verify_structural_integrity(ast, &compressed_ast) {
+        if !self.verify_structural_integrity(input, compressed_ast) {
             return Err(CompressionError::PatternApplication("Structural integrity lost".to_string()));
         }
         
@@ -122,19 +752,75 @@ impl NexusCompressionEngine {
             compression_ratio,
             patterns_identified: patterns.len(),
             processing_time: start_time.elapsed(),
-            memory_usage: std::mem::size_of_val(&compressed_ast),
+            memory_usage: std::mem::size_of_val(compressed_ast),
+            incomplete,
+            trace,
+            detailed_stats,
+            nodes_original: input.node_ids.len(),
+            nodes_compressed: compressed_ast.nodes.len(),
+            nodes_lost: input.node_ids.iter().filter(|id| !compressed_ast.nodes.contains_key(id)).count(),
+            returned_original: false,
+            metadata_bytes,
+            from_cache: false,
         };
         
-        self.compression_history.push_back(result.clone());
-        if self.compression_history.len() > 100 {
-            self.compression_history.pop_front();
-        }
-        
+        self.record_history(&result);
         Ok(result)
     }
     
+    /// Fail on `PatternRef` cycles, or break them when `repair_pattern_cycles` is set
+    ///
+    /// Nodes in `value_table_refs` hold value-table IDs, not node references, and are
+    /// left out of the search. A cycle is broken by restoring the first node on it
+    /// whose original value was not itself a reference.
+    fn check_pattern_cycles(
+        &self,
+        original: &InputShape,
+        compressed: &mut GammaAST,
+        value_table_refs: &HashSet<u64>,
+    ) -> Result<(), CompressionError> {
+        loop {
+            // Hide value-table IDs while searching so they are not mistaken for node IDs
+            let masked: Vec<(u64, GammaValue)> = value_table_refs.iter()
+                .filter_map(|id| {
+                    let node = compressed.nodes.get_mut(id)?;
+                    Some((*id, std::mem::replace(&mut node.value, GammaValue::None)))
+                })
+                .collect();
+            let found = compressed.find_reference_cycle();
+            for (id, value) in masked {
+                if let Some(node) = compressed.nodes.get_mut(&id) {
+                    node.value = value;
+                }
+            }
+            let Some(cycle) = found else {
+                return Ok(());
+            };
+            
+            if !self.config.repair_pattern_cycles {
+                return Err(CompressionError::PatternCycle(cycle));
+            }
+            
+            let restorable = cycle.iter().find_map(|id| {
+                let (value, level) = original.values.get(id)?;
+                (compressed.nodes.contains_key(id) && !matches!(value, GammaValue::PatternRef(_)))
+                    .then(|| (*id, value.clone(), *level))
+            });
+            let Some((id, value, level)) = restorable else {
+                return Err(CompressionError::PatternCycle(cycle));
+            };
+            if let Some(node) = compressed.nodes.get_mut(&id) {
+                node.value = value;
+                node.compression_level = level;
+            }
+        }
+    }
+    
     /// Apply value compression that actually saves space
-    fn apply_value_compression(&self, ast: &mut GammaAST) -> Result<(), CompressionError> {
+    ///
+    /// Returns the IDs of nodes whose value now holds a value-table ID rather than a node reference.
+    fn apply_value_compression(&self, ast: &mut GammaAST) -> Result<HashSet<u64>, CompressionError> {
+        let mut table_refs = HashSet::new();
         let mut string_table: HashMap<String, u16> = HashMap::new();
         let mut numeric_table: HashMap<String, u16> = HashMap::new();
         let mut next_string_id: u16 = 1;
@@ -176,7 +862,10 @@ impl NexusCompressionEngine {
         }
         
         // Second pass: apply compression only where it actually saves space
-        for (_, node) in &mut ast.nodes {
+        for (node_id, node) in &mut ast.nodes {
+            if self.config.pinned_nodes.contains(node_id) {
+                continue;
+            }
             if let GammaValue::Direct(ref value) = &node.value {
                 let mut new_value = None;
                 
@@ -207,11 +896,14 @@ impl NexusCompressionEngine {
                 // Apply compression if we found a new value
                 if let Some(compressed_value) = new_value {
                     node.value = compressed_value;
+                    // The value table is not kept, so this cannot be decoded
+                    node.compression_level = CompressionLevel::Heavy;
+                    table_refs.insert(*node_id);
                 }
             }
         }
         
-        Ok(())
+        Ok(table_refs)
     }
     
     /// Apply basic deduplication that actually saves space
@@ -228,17 +920,22 @@ impl NexusCompressionEngine {
             }
         }
         
-        // Replace duplicate nodes with references to the first occurrence
-        for (_, node_ids) in value_map {
+        // Replace duplicate nodes with references to the lowest-ID occurrence
+        for (_, node_ids) in sorted_groups(value_map) {
             if node_ids.len() > 1 {
                 let reference_id = node_ids[0];
                 for &duplicate_id in &node_ids[1..] {
+                    if self.config.pinned_nodes.contains(&duplicate_id) {
+                        continue;
+                    }
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
                         // Replace duplicate with reference to save space
                         duplicate_node.value = GammaValue::PatternRef(reference_id);
-                        // Clear children and metadata to save space
-                        duplicate_node.children.clear();
-                        duplicate_node.metadata.clear();
+                        duplicate_node.compression_level = CompressionLevel::Light;
+                        // Metadata belongs to the metadata pass; children only go in lossy mode
+                        if !self.config.preserve_children {
+                            duplicate_node.children.clear();
+                        }
                     }
                 }
             }
@@ -254,12 +951,12 @@ impl NexusCompressionEngine {
         
         // Group nodes by their structural signature
         for (node_id, node) in &ast.nodes {
-            let structural_key = format!("{:?}:{}", node.node_type, node.children.len());
+            let structural_key = format!("{}:{}", node.node_type, node.children.len());
             structural_patterns.entry(structural_key).or_insert_with(Vec::new).push(*node_id);
         }
         
         // Only create patterns for structures that appear multiple times
-        for (_, node_ids) in structural_patterns {
+        for (_, node_ids) in sorted_groups(structural_patterns) {
             if node_ids.len() > 2 { // Only if pattern appears 3+ times
                 // Create a simple pattern with just the node IDs
                 let pattern = Pattern {
@@ -289,6 +986,22 @@ impl NexusCompressionEngine {
         Ok(())
     }
     
+    /// Dictionary-encode or drop node metadata according to `metadata_mode`, leaving pinned nodes alone
+    fn apply_metadata_mode(&self, ast: &mut GammaAST) {
+        match self.config.metadata_mode {
+            MetadataMode::Preserve => {}
+            MetadataMode::Dictionary => ast.intern_metadata_except(&self.config.pinned_nodes),
+            MetadataMode::Drop => {
+                for (id, node) in ast.nodes.iter_mut() {
+                    if !self.config.pinned_nodes.contains(id) {
+                        node.metadata.clear();
+                    }
+                }
+                ast.metadata_dictionary = None;
+            }
+        }
+    }
+    
     /// Calculate the actual size of an AST in bytes
     fn calculate_ast_size(&self, ast: &GammaAST) -> usize {
         let mut total_size = 0;
@@ -303,19 +1016,29 @@ impl NexusCompressionEngine {
             }
             
             // Add size of metadata
-            total_size += node.metadata.len() * 16; // Rough estimate
+            total_size += node.metadata.iter().map(|(key, value)| key.len() + value.len()).sum::<usize>();
+        }
+        
+        // Interned metadata is stored once in the dictionary
+        if let Some(dictionary) = &ast.metadata_dictionary {
+            total_size += (0..dictionary.len() as u32).filter_map(|entry| dictionary.get(entry)).map(str::len).sum::<usize>();
         }
         
         // Add size of roots vector
         total_size += ast.roots.len() * std::mem::size_of::<u64>();
         
+        // The reconstruction table ships with the AST
+        if let Some(table) = &ast.reconstruction {
+            total_size += table.size_bytes();
+        }
+        
         total_size
     }
     
     /// Verify that structural integrity is maintained
-    fn verify_structural_integrity(&self, original: &GammaAST, compressed: &GammaAST) -> bool {
+    fn verify_structural_integrity(&self, original: &InputShape, compressed: &GammaAST) -> bool {
         // Check node count preservation
-        if original.nodes.len() != compressed.nodes.len() {
+        if original.node_ids.len() != compressed.nodes.len() {
             return false;
         }
         
@@ -325,7 +1048,7 @@ impl NexusCompressionEngine {
         }
         
         // Check that all nodes still exist
-        for (node_id, _) in &original.nodes {
+        for node_id in &original.node_ids {
             if !compressed.nodes.contains_key(node_id) {
                 return false;
             }
@@ -353,73 +1076,444 @@ impl NexusCompressionEngine {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::gamma_ast::{GammaAST, GammaNode, GammaNodeType, GammaValue};
-    use std::collections::HashMap;
+/// Drain grouped node IDs into a reproducible order
+///
+/// Each group's IDs are sorted ascending, so `node_ids[0]` is its smallest ID
+/// and can serve as the surviving reference; groups are ordered by that ID.
+/// `HashMap` iteration order changes from run to run, so passes that pick a
+/// reference per group must go through this to produce identical output.
+pub(crate) fn sorted_groups<K>(groups: HashMap<K, Vec<u64>>) -> Vec<(K, Vec<u64>)> {
+    let mut groups: Vec<(K, Vec<u64>)> = groups.into_iter()
+        .map(|(key, mut node_ids)| {
+            node_ids.sort_unstable();
+            (key, node_ids)
+        })
+        .collect();
+    groups.sort_unstable_by_key(|(_, node_ids)| node_ids.first().copied());
+    groups
+}
+
+/// Log a warning when `metadata_bytes` exceed `fraction` of `compressed_size`, returning whether it did
+///
+/// `None` disables the check. Shared by every engine that honours `metadata_warning_fraction`.
+pub(crate) fn warn_on_metadata_bloat(fraction: Option<f64>, metadata_bytes: usize, compressed_size: usize) -> bool {
+    let Some(fraction) = fraction else {
+        return false;
+    };
+    let bloated = compressed_size > 0 && metadata_bytes as f64 > fraction * compressed_size as f64;
+    if bloated {
+        log::warn!(
+            "metadata is {} of {} compressed bytes ({:.0}%), above the {:.0}% warning level",
+            metadata_bytes,
+            compressed_size,
+            metadata_bytes as f64 * 100.0 / compressed_size as f64,
+            fraction * 100.0
+        );
+    }
+    bloated
+}
+
+/// Run one pass, recording the nodes it changed when tracing is enabled
+fn traced<F>(
+    ast: &mut GammaAST,
+    pass: PassKind,
+    trace: &mut Option<CompressionTrace>,
+    detailed_stats: &mut Option<DetailedCompressionStats>,
+    run: F,
+) -> Result<(), CompressionError>
+where
+    F: FnOnce(&mut GammaAST) -> Result<(), CompressionError>,
+{
+    if trace.is_none() && detailed_stats.is_none() {
+        return run(ast);
+    }
     
-    fn create_test_ast() -> GammaAST {
-        let mut ast = GammaAST::new();
-        ast.set_source_language("rust".to_string());
-        
-        // Create nodes with repeated patterns
-        let node1 = GammaNode {
-            id: 1,
-            node_type: GammaNodeType::Function,
-            value: GammaValue::Direct("main".to_string()),
-            location: None,
-            children: vec![2, 3],
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::None,
+    let before = ast.nodes.clone();
+    run(ast)?;
+    if let Some(trace) = trace {
+        for (id, node) in &ast.nodes {
+            if before.get(id) != Some(node) {
+                trace.record(*id, pass.clone());
+            }
+        }
+    }
+    if let Some(stats) = detailed_stats {
+        stats.record_pass(pass, &before, &ast.nodes);
+    }
+    Ok(())
+}
+
+/// Parse, compress and serialize source code in one call
+///
+/// The source is parsed with string rules for `language`, converted to a Γ-AST,
+/// compressed with `config`, and the compressed AST is returned as JSON bytes.
+pub fn compress_source(language: &str, source: &str, config: CompressionConfig) -> Result<Vec<u8>, CompressionError> {
+    let mut parser = BasicParser::with_string_rules(StringRules::for_language(language));
+    let ast = parser.parse(source)?;
+    
+    let mut gamma_ast = GammaAST::from_ast(&ast);
+    gamma_ast.set_source_language(language.to_string());
+    
+    let mut engine = NexusCompressionEngine::new(config);
+    let (_, bytes) = engine.compress_to_bytes(&gamma_ast)?;
+    Ok(bytes)
+}
+
+/// Serialized size of a Γ-AST under NEXUS and under general-purpose compressors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BaselineComparison {
+    /// JSON serialization of the uncompressed input
+    pub raw_bytes: usize,
+    /// JSON serialization after the NEXUS pipeline
+    pub nexus_bytes: usize,
+    /// gzip of the raw serialization, when built with the `gzip-baseline` feature
+    pub gzip_bytes: Option<usize>,
+    /// zstd of the raw serialization, when built with the `zstd-baseline` feature
+    pub zstd_bytes: Option<usize>,
+}
+
+impl BaselineComparison {
+    /// True when NEXUS output is smaller than every baseline that was measured
+    pub fn beats_baselines(&self) -> bool {
+        [self.gzip_bytes, self.zstd_bytes].into_iter()
+            .flatten()
+            .all(|baseline| self.nexus_bytes < baseline)
+    }
+}
+
+/// Compress `ast` and report its size next to gzip and zstd of the plain serialization
+///
+/// All sizes use the JSON encoding `compress_source` emits, and both baselines
+/// run at their default level. A baseline whose feature is disabled is `None`.
+pub fn compare_against_baselines(ast: &GammaAST, config: CompressionConfig) -> Result<BaselineComparison, CompressionError> {
+    let raw = serde_json::to_vec(ast)?;
+    
+    let mut engine = NexusCompressionEngine::new(config);
+    let (_, compressed_ast) = engine.run_pipeline(ast, CompressionBudget::unlimited())?;
+    let nexus_bytes = serde_json::to_vec(&compressed_ast)?.len();
+    
+    Ok(BaselineComparison {
+        raw_bytes: raw.len(),
+        nexus_bytes,
+        gzip_bytes: gzip_size(&raw)?,
+        zstd_bytes: zstd_size(&raw)?,
+    })
+}
+
+#[cfg(feature = "gzip-baseline")]
+fn gzip_size(bytes: &[u8]) -> Result<Option<usize>, CompressionError> {
+    use std::io::Write;
+    
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(Some(encoder.finish()?.len()))
+}
+
+#[cfg(not(feature = "gzip-baseline"))]
+fn gzip_size(_bytes: &[u8]) -> Result<Option<usize>, CompressionError> {
+    Ok(None)
+}
+
+#[cfg(feature = "zstd-baseline")]
+fn zstd_size(bytes: &[u8]) -> Result<Option<usize>, CompressionError> {
+    Ok(Some(zstd::bulk::compress(bytes, zstd::DEFAULT_COMPRESSION_LEVEL)?.len()))
+}
+
+#[cfg(not(feature = "zstd-baseline"))]
+fn zstd_size(_bytes: &[u8]) -> Result<Option<usize>, CompressionError> {
+    Ok(None)
+}
+
+/// Load a compressed Γ-AST produced by `compress_source`
+///
+/// Nodes are decoded according to their recorded compression level; nodes
+/// written by lossy passes keep their encoded values.
+pub fn decompress_to_ast(bytes: &[u8]) -> Result<GammaAST, CompressionError> {
+    let mut ast = JsonAstSerializer.deserialize(bytes)?;
+    ast.decompress_levels();
+    Ok(ast)
+}
+
+/// Load a compressed Γ-AST and resolve its `DictRef` values against shared dictionaries
+pub fn decompress_with_dictionaries(
+    bytes: &[u8],
+    dictionaries: &[PatternDictionary],
+) -> Result<GammaAST, CompressionError> {
+    let mut ast = JsonAstSerializer.deserialize(bytes)?;
+    ast.resolve_dict_refs(dictionaries)
+        .map_err(|(dict_id, entry)| CompressionError::UnresolvedDictRef { dict_id, entry })?;
+    ast.decompress_levels();
+    Ok(ast)
+}
+
+/// Compress every supported source file below `dir`
+///
+/// Files are found recursively (symlinks are not followed), filtered through
+/// the `.nexusignore` file in `dir` if there is one, and their language is
+/// detected from the extension. Files that fail to read or parse are listed in
+/// the report instead of aborting the run. With `compress_as_corpus` set, all
+/// files are merged into one Γ-AST before compression.
+pub fn compress_directory(dir: &Path, config: CompressionConfig) -> Result<DirectoryCompressionReport, CompressionError> {
+    let ignore = IgnoreRules::load(dir)?;
+    let corpus_mode = config.compress_as_corpus;
+    let mut engine = NexusCompressionEngine::new(config);
+    
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+    let mut asts = Vec::new();
+    for (path, language) in collect_source_files(dir, &ignore)? {
+        let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                failed.push((relative, e.to_string()));
+                continue;
+            }
         };
-        
-        let node2 = GammaNode {
-            id: 2,
-            node_type: GammaNodeType::Variable,
-            value: GammaValue::Direct("x".to_string()),
-            location: None,
-            children: vec![],
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::None,
+        let mut parser = BasicParser::with_string_rules(StringRules::for_language(language));
+        let ast = match parser.parse(&source) {
+            Ok(ast) => ast,
+            Err(e) => {
+                failed.push((relative, e.to_string()));
+                continue;
+            }
         };
+        let mut gamma_ast = GammaAST::from_ast(&ast);
+        gamma_ast.set_source_language(language.to_string());
         
-        let node3 = GammaNode {
-            id: 3,
-            node_type: GammaNodeType::Literal,
-            value: GammaValue::Direct("42".to_string()),
-            location: None,
-            children: vec![],
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::None,
+        let result = if corpus_mode {
+            None
+        } else {
+            let (result, compressed_ast) = engine.run_pipeline(&gamma_ast, CompressionBudget::unlimited())?;
+            asts.push(compressed_ast);
+            Some(result)
         };
+        files.push(FileCompressionReport {
+            path: relative,
+            language: language.to_string(),
+            source_bytes: source.len(),
+            node_count: gamma_ast.nodes.len(),
+            result,
+        });
+        if corpus_mode {
+            asts.push(gamma_ast);
+        }
+    }
+    
+    let mut corpus_result = None;
+    let mut corpus_roots = Vec::new();
+    if corpus_mode && !asts.is_empty() {
+        let mut corpus = GammaAST::new();
+        let mut languages: Vec<&str> = files.iter().map(|file| file.language.as_str()).collect();
+        languages.sort_unstable();
+        languages.dedup();
+        corpus.set_source_language(languages.join(","));
         
-        // Add duplicate nodes to create patterns
-        let node4 = GammaNode {
-            id: 4,
-            node_type: GammaNodeType::Variable,
-            value: GammaValue::Direct("x".to_string()), // Same value as node2
-            location: None,
-            children: vec![],
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::None,
-        };
+        // Files were converted independently, so shift each file's IDs past the previous ones
+        let mut offset = 0;
+        for ast in asts.drain(..) {
+            let max_id = ast.nodes.keys().max().copied().unwrap_or(0);
+            corpus_roots.push(ast.roots.iter().map(|root| root + offset).collect());
+            for root in &ast.roots {
+                corpus.add_root(root + offset);
+            }
+            for (_, mut node) in ast.nodes {
+                node.id += offset;
+                for child in &mut node.children {
+                    *child += offset;
+                }
+                corpus.add_node(node);
+            }
+            offset += max_id;
+        }
         
-        let node5 = GammaNode {
-            id: 5,
-            node_type: GammaNodeType::Literal,
-            value: GammaValue::Direct("42".to_string()), // Same value as node3
-            location: None,
-            children: vec![],
-            metadata: HashMap::new(),
-            compression_level: CompressionLevel::None,
+        let (result, compressed_corpus) = engine.run_pipeline(&corpus, CompressionBudget::unlimited())?;
+        corpus_result = Some(result);
+        asts.push(compressed_corpus);
+    }
+    
+    Ok(DirectoryCompressionReport {
+        artifact: DirectoryArtifact {
+            files: files.iter().map(|file| file.path.clone()).collect(),
+            asts,
+            corpus_roots,
+        },
+        files,
+        failed,
+        corpus_result,
+    })
+}
+
+/// File extensions (without the dot) and the language each belongs to
+///
+/// Languages are named as `StringRules::for_language` expects. This is the
+/// one extension table; directory compression and `SupportedLanguage` both read it.
+pub(crate) const EXTENSIONS: &[(&str, &str)] = &[
+    ("py", "python"),
+    ("pyi", "python"),
+    ("pyw", "python"),
+    ("rs", "rust"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("mts", "typescript"),
+    ("cts", "typescript"),
+    ("tsx", "typescript"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("hh", "cpp"),
+    ("hxx", "cpp"),
+    ("go", "go"),
+    ("java", "java"),
+    ("cs", "csharp"),
+];
+
+/// Language name for a file extension, with or without the leading dot
+///
+/// Matching is case-insensitive, so `"H"` and `".h"` both resolve to `"c"`.
+pub fn language_for_extension(ext: &str) -> Option<&'static str> {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    EXTENSIONS.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        .map(|&(_, language)| language)
+}
+
+/// Map a file extension to the language name understood by `StringRules::for_language`
+fn detect_language(path: &Path) -> Option<&'static str> {
+    language_for_extension(path.extension()?.to_str()?)
+}
+
+/// Walk `dir` and return every supported, non-ignored file in sorted order
+fn collect_source_files(dir: &Path, ignore: &IgnoreRules) -> Result<Vec<(PathBuf, &'static str)>, CompressionError> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    
+    while let Some(current) = pending.pop() {
+        let mut entries = fs::read_dir(&current)?
+            .collect::<Result<Vec<_>, _>>()
+            ?;
+        entries.sort_by_key(|entry| entry.path());
+        
+        for entry in entries {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" && !ignore.is_ignored(relative, true) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() && !ignore.is_ignored(relative, false) {
+                if let Some(language) = detect_language(&path) {
+                    found.push((path, language));
+                }
+            }
+        }
+    }
+    
+    found.sort();
+    Ok(found)
+}
+
+/// Patterns read from a `.nexusignore` file
+///
+/// One glob per line (`*` and `?` wildcards); blank lines and `#` comments are
+/// skipped. A trailing `/` restricts the pattern to directories. Patterns
+/// containing `/` match the whole path relative to the compressed directory,
+/// others match any single path component.
+#[derive(Debug, Default)]
+struct IgnoreRules {
+    patterns: Vec<(String, bool)>,
+}
+
+impl IgnoreRules {
+    fn load(dir: &Path) -> Result<Self, CompressionError> {
+        let text = match fs::read_to_string(dir.join(".nexusignore")) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
         };
+        Ok(Self::parse(&text))
+    }
+    
+    fn parse(text: &str) -> Self {
+        let patterns = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_suffix('/') {
+                Some(pattern) => (pattern.trim_start_matches('/').to_string(), true),
+                None => (line.trim_start_matches('/').to_string(), false),
+            })
+            .collect();
+        Self { patterns }
+    }
+    
+    fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let path = relative.to_string_lossy().replace('\\', "/");
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        self.patterns.iter().any(|(pattern, dir_only)| {
+            if *dir_only && !is_dir {
+                return false;
+            }
+            if pattern.contains('/') {
+                glob_match(pattern, &path)
+            } else {
+                glob_match(pattern, name)
+            }
+        })
+    }
+}
+
+/// Match `text` against a glob with `*` (any run of characters) and `?` (one character)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamma_ast::{DecodeError, GammaAST, GammaNode, GammaNodeType, GammaValue};
+    use std::collections::HashMap;
+    
+    fn create_test_ast() -> GammaAST {
+        let mut ast = GammaAST::new();
+        ast.set_source_language("rust".to_string());
         
-        ast.nodes.insert(1, node1);
-        ast.nodes.insert(2, node2);
-        ast.nodes.insert(3, node3);
-        ast.nodes.insert(4, node4);
-        ast.nodes.insert(5, node5);
+        // Nodes 4 and 5 repeat the values of 2 and 3 to create patterns
+        for node in [
+            GammaNode::builder(1, GammaNodeType::Function).direct("main").children([2, 3]).build(),
+            GammaNode::builder(2, GammaNodeType::Variable).direct("x").build(),
+            GammaNode::builder(3, GammaNodeType::Literal).direct("42").build(),
+            GammaNode::builder(4, GammaNodeType::Variable).direct("x").build(),
+            GammaNode::builder(5, GammaNodeType::Literal).direct("42").build(),
+        ] {
+            ast.nodes.insert(node.id, node);
+        }
         ast.roots.push(1);
         
         ast
@@ -453,7 +1547,7 @@ mod tests {
         assert!(result.compressed_size <= original_size);
         
         // Verify structural integrity
-        assert!(engine.verify_structural_integrity(&ast, &ast)); // Should be true for same AST
+        assert!(engine.verify_structural_integrity(&engine.input_shape(&ast), &ast)); // Should be true for same AST
     }
     
     #[tokio::test]
@@ -508,6 +1602,641 @@ mod tests {
         assert!(!patterns.is_empty());
     }
     
+    #[test]
+    fn test_space_saving_percent() {
+        let result = |original_size, compressed_size| CompressionResult {
+            original_size,
+            compressed_size,
+            compression_ratio: 1.0,
+            patterns_identified: 0,
+            processing_time: Duration::ZERO,
+            memory_usage: 0,
+            incomplete: false,
+            trace: None,
+            detailed_stats: None,
+            nodes_original: 0,
+            nodes_compressed: 0,
+            nodes_lost: 0,
+            returned_original: false,
+            metadata_bytes: 0,
+            from_cache: false,
+        };
+        assert_eq!(result(200, 50).space_saving_percent(), 75.0);
+        assert!(!result(200, 50).is_expansion());
+        assert_eq!(result(100, 150).space_saving_percent(), -50.0);
+        assert!(result(100, 150).is_expansion());
+        assert_eq!(result(0, 10).space_saving_percent(), 0.0);
+        assert_eq!(result(0, 0).space_saving_percent(), 0.0);
+        assert!(!result(0, 0).is_expansion());
+    }
+    
+    #[test]
+    fn test_custom_serializer() {
+        /// Length-prefixed JSON, standing in for a user's own format
+        struct Framed;
+        impl AstSerializer for Framed {
+            fn serialize(&self, ast: &GammaAST) -> Vec<u8> {
+                let json = JsonAstSerializer.serialize(ast);
+                let mut bytes = (json.len() as u32).to_le_bytes().to_vec();
+                bytes.extend(json);
+                bytes
+            }
+            fn deserialize(&self, bytes: &[u8]) -> Result<GammaAST, CompressionError> {
+                let body = bytes.get(4..).ok_or_else(|| CompressionError::Parse("truncated frame".into()))?;
+                JsonAstSerializer.deserialize(body)
+            }
+        }
+        
+        let ast = create_test_ast();
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default()).with_serializer(Framed);
+        let (_, bytes) = engine.compress_to_bytes(&ast).unwrap();
+        assert_eq!(u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize, bytes.len() - 4);
+        assert!(decompress_to_ast(&bytes).is_err());
+        
+        let restored = engine.decompress_bytes(&bytes).unwrap();
+        assert_eq!(restored.roots, ast.roots);
+        assert_eq!(restored.nodes.len(), ast.nodes.len());
+        assert!(engine.decompress_bytes(&[1]).is_err());
+    }
+    
+    #[test]
+    fn test_compress_ast_to_writer() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        
+        let ast = create_test_ast();
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default());
+        let mut out = Vec::new();
+        let result = engine.compress_ast_to_writer(&ast, &mut out).unwrap();
+        let restored = decompress_to_ast(&out).unwrap();
+        assert_eq!(restored.roots, ast.roots);
+        assert_eq!(restored.nodes.len(), result.nodes_compressed);
+        
+        assert!(engine.compress_ast_to_writer(&ast, &mut Broken).is_err());
+    }
+    
+    #[test]
+    fn test_compression_cache() {
+        let ast = create_test_ast();
+        let mut other = create_test_ast();
+        other.set_source_language("python".to_string());
+        let mut third = create_test_ast();
+        third.roots.push(2);
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default()).with_cache(2);
+        let (first, first_ast) = engine.compress_to_bytes(&ast).unwrap();
+        let (second, second_ast) = engine.compress_to_bytes(&ast).unwrap();
+        assert!(!first.from_cache && second.from_cache);
+        assert_eq!((second.compressed_size, second_ast), (first.compressed_size, first_ast));
+        assert_eq!(engine.get_compression_history().len(), 2);
+        
+        // Two newer entries push out the least recently used one
+        engine.compress_ast_sync(&other).unwrap();
+        assert!(engine.compress_ast_sync(&ast).unwrap().from_cache);
+        engine.compress_ast_sync(&third).unwrap();
+        assert_eq!(engine.cache().unwrap().len(), 2);
+        assert!(!engine.compress_ast_sync(&other).unwrap().from_cache);
+        
+        // A config change invalidates everything cached under the old one
+        engine.config.never_expand = false;
+        assert!(!engine.compress_ast_sync(&ast).unwrap().from_cache);
+        assert!(engine.compress_ast_sync(&ast).unwrap().from_cache);
+        
+        // An entry whose input differs is a miss even when the hash matches
+        let key = ast.content_hash();
+        let cache = engine.cache.as_mut().unwrap();
+        assert!(cache.get(key, &ast).is_some());
+        assert!(cache.get(key, &other).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_compress_ast_sync() {
+        let ast = create_test_ast();
+        let expected = NexusCompressionEngine::new(CompressionConfig::default()).compress_ast(&ast).await.unwrap();
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default());
+        let result = engine.compress_ast_sync(&ast).unwrap();
+        assert_eq!(result.compressed_size, expected.compressed_size);
+        assert_eq!(result.patterns_identified, expected.patterns_identified);
+        assert_eq!(engine.get_compression_history().len(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_compress_ast_in_place() {
+        let config = CompressionConfig { repair_pattern_cycles: true, ..CompressionConfig::default() };
+        let ast = create_test_ast();
+        let (expected, expected_ast) = NexusCompressionEngine::new(config.clone())
+            .run_pipeline(&ast, CompressionBudget::unlimited())
+            .unwrap();
+        
+        let mut in_place = ast.clone();
+        let result = NexusCompressionEngine::new(config).compress_ast_in_place(&mut in_place).await.unwrap();
+        assert_eq!((result.nodes_original, result.nodes_compressed, result.nodes_lost), (5, 5, 0));
+        assert_eq!(result.original_size, expected.original_size);
+        assert_eq!(result.compressed_size, expected.compressed_size);
+        assert_eq!(serde_json::to_value(&in_place).unwrap(), serde_json::to_value(&expected_ast).unwrap());
+        
+        // The reconstruction table is built from the serialized input; the cache is left alone
+        let config = CompressionConfig { reconstructible: true, never_expand: false, ..CompressionConfig::default() };
+        let mut engine = NexusCompressionEngine::new(config).with_cache(4);
+        let mut in_place = ast.clone();
+        engine.compress_ast_in_place(&mut in_place).await.unwrap();
+        assert!(in_place.reconstruction.is_some());
+        assert!(in_place.decompress().unwrap().same_content(&ast));
+        assert!(engine.cache().unwrap().is_empty());
+        let mut again = ast.clone();
+        assert!(!engine.compress_ast_in_place(&mut again).await.unwrap().from_cache);
+    }
+    
+    #[tokio::test]
+    async fn test_max_patterns() {
+        let mut ast = GammaAST::new();
+        for id in 1..=7 {
+            let node_type = if id <= 3 { GammaNodeType::Literal } else { GammaNodeType::Variable };
+            ast.add_node(GammaNode::builder(id, node_type).direct(format!("v{}", id)).build());
+            ast.add_root(id);
+        }
+        let config = CompressionConfig {
+            enable_value_compression: false,
+            enable_deduplication: false,
+            ..CompressionConfig::default()
+        };
+        assert_eq!(NexusCompressionEngine::new(config.clone()).compress_ast(&ast).await.unwrap().patterns_identified, 2);
+        
+        let capped = CompressionConfig { max_patterns: 1, ..config };
+        let result = NexusCompressionEngine::new(capped).compress_ast(&ast).await.unwrap();
+        assert_eq!(result.patterns_identified, 1);
+        
+        let legacy: CompressionConfig = serde_json::from_str(r#"{"enable_patterns":true,"enable_value_compression":true,"enable_deduplication":true,"target_ratio":3.0,"max_memory_mb":512}"#).unwrap();
+        assert_eq!(legacy.max_patterns, 10_000);
+    }
+    
+    #[test]
+    fn test_preserve_children() {
+        let mut ast = GammaAST::new();
+        for (id, children) in [(1, vec![3]), (2, vec![4]), (3, vec![]), (4, vec![])] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Call)
+                .direct(if id <= 2 { "print".to_string() } else { format!("arg{}", id) })
+                .children(children)
+                .build());
+        }
+        
+        let mut preserved = ast.clone();
+        NexusCompressionEngine::new(CompressionConfig::default()).apply_basic_deduplication(&mut preserved).unwrap();
+        assert_eq!(preserved.nodes[&2].value, GammaValue::PatternRef(1));
+        assert_eq!(preserved.nodes[&2].children, vec![4]);
+        
+        let lossy = CompressionConfig { preserve_children: false, ..CompressionConfig::default() };
+        NexusCompressionEngine::new(lossy).apply_basic_deduplication(&mut ast).unwrap();
+        assert!(ast.nodes[&2].children.is_empty());
+        
+        let legacy: CompressionConfig = serde_json::from_str(r#"{"enable_patterns":true,"enable_value_compression":true,"enable_deduplication":true,"target_ratio":3.0,"max_memory_mb":512}"#).unwrap();
+        assert!(legacy.preserve_children);
+    }
+    
+    #[test]
+    fn test_pinned_nodes() {
+        let mut ast = GammaAST::new();
+        for id in 1..=4 {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Variable)
+                .direct("entry_point")
+                .metadata("role", "export")
+                .build());
+        }
+        
+        let config = CompressionConfig { pinned_nodes: HashSet::from([3]), ..CompressionConfig::default() };
+        let engine = NexusCompressionEngine::new(config);
+        let mut compressed = ast.clone();
+        engine.apply_value_compression(&mut compressed).unwrap();
+        engine.apply_basic_deduplication(&mut compressed).unwrap();
+        
+        assert_eq!(compressed.nodes[&3], ast.nodes[&3]);
+        assert!(matches!(compressed.nodes[&2].value, GammaValue::PatternRef(_)));
+        assert!(matches!(compressed.nodes[&4].value, GammaValue::PatternRef(_)));
+        
+        // Metadata modes leave pinned nodes alone too
+        for metadata_mode in [MetadataMode::Drop, MetadataMode::Dictionary] {
+            let config = CompressionConfig { pinned_nodes: HashSet::from([3]), metadata_mode, ..CompressionConfig::default() };
+            let engine = NexusCompressionEngine::new(config);
+            let mut compressed = ast.clone();
+            engine.apply_metadata_mode(&mut compressed);
+            assert_eq!(compressed.nodes[&3], ast.nodes[&3]);
+            assert_ne!(compressed.nodes[&2].metadata, ast.nodes[&2].metadata);
+            if metadata_mode == MetadataMode::Dictionary {
+                compressed.expand_metadata().unwrap();
+                assert!(compressed.same_content(&ast));
+            }
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_compression_budget() {
+        let config = CompressionConfig::default();
+        let mut engine = NexusCompressionEngine::new(config);
+        
+        let ast = create_test_ast();
+        
+        let budget = CompressionBudget { max_duration: None, max_passes: Some(1) };
+        let result = engine.compress_ast_with_budget(&ast, budget).await.unwrap();
+        assert!(result.incomplete);
+        
+        let result = engine.compress_ast_with_budget(&ast, CompressionBudget::unlimited()).await.unwrap();
+        assert!(!result.incomplete);
+    }
+    
+    #[test]
+    fn test_max_passes_stops_metadata_pass() {
+        let mut ast = GammaAST::new();
+        for (id, value) in [(1, "repeated_value"), (2, "repeated_value")] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal).direct(value).metadata("annotation", "keep me").build());
+            ast.add_root(id);
+        }
+        let mut engine = NexusCompressionEngine::new(CompressionConfig {
+            metadata_mode: MetadataMode::Drop,
+            never_expand: false,
+            ..Default::default()
+        });
+        let has_metadata = |ast: &GammaAST| ast.nodes.values().any(|node| !node.metadata.is_empty());
+        assert!(has_metadata(&ast));
+        
+        // Value, deduplication and pattern passes use up the budget before metadata
+        let budget = CompressionBudget { max_duration: None, max_passes: Some(3) };
+        let (result, compressed) = engine.run_pipeline(&ast, budget).unwrap();
+        assert!(result.incomplete);
+        assert!(has_metadata(&compressed));
+        
+        let budget = CompressionBudget { max_duration: None, max_passes: Some(4) };
+        let (result, compressed) = engine.run_pipeline(&ast, budget).unwrap();
+        assert!(!result.incomplete);
+        assert!(!has_metadata(&compressed));
+    }
+    
+    #[test]
+    fn test_pattern_cycle_detection_and_repair() {
+        let mut original = GammaAST::new();
+        for (id, value) in [(1, "first_value"), (2, "second_value")] {
+            original.add_node(GammaNode::builder(id, GammaNodeType::Literal).direct(value).build());
+        }
+        let mut compressed = original.clone();
+        compressed.nodes.get_mut(&1).unwrap().value = GammaValue::PatternRef(2);
+        compressed.nodes.get_mut(&2).unwrap().value = GammaValue::PatternRef(1);
+        
+        let engine = NexusCompressionEngine::new(CompressionConfig::default());
+        assert!(matches!(
+            engine.check_pattern_cycles(&engine.input_shape(&original), &mut compressed.clone(), &HashSet::new()),
+            Err(CompressionError::PatternCycle(ids)) if ids == vec![1, 2]
+        ));
+        
+        let engine = NexusCompressionEngine::new(CompressionConfig {
+            repair_pattern_cycles: true,
+            ..Default::default()
+        });
+        engine.check_pattern_cycles(&engine.input_shape(&original), &mut compressed, &HashSet::new()).unwrap();
+        assert_eq!(compressed.nodes[&1].value, GammaValue::Direct("first_value".to_string()));
+        assert_eq!(compressed.find_reference_cycle(), None);
+        
+        // Value-table IDs that happen to equal node IDs are not references
+        let mut table_only = original.clone();
+        table_only.nodes.get_mut(&1).unwrap().value = GammaValue::PatternRef(1);
+        let engine = NexusCompressionEngine::new(CompressionConfig::default());
+        assert!(engine.check_pattern_cycles(&engine.input_shape(&original), &mut table_only, &HashSet::from([1])).is_ok());
+        assert_eq!(table_only.nodes[&1].value, GammaValue::PatternRef(1));
+    }
+    
+    #[test]
+    fn test_decompress_with_dictionaries() {
+        let mut dictionary = PatternDictionary::new(3);
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Literal)
+            .value(dictionary.reference("shared_constant"))
+            .build());
+        ast.add_root(1);
+        assert_eq!(ast.nodes[&1].value, GammaValue::DictRef { dict_id: 3, entry: 0 });
+        
+        let bytes = serde_json::to_vec(&ast).unwrap();
+        let restored = decompress_with_dictionaries(&bytes, &[dictionary]).unwrap();
+        assert_eq!(restored.nodes[&1].value, GammaValue::Direct("shared_constant".to_string()));
+        
+        assert!(matches!(
+            decompress_with_dictionaries(&bytes, &[PatternDictionary::new(4)]),
+            Err(CompressionError::UnresolvedDictRef { dict_id: 3, entry: 0 })
+        ));
+    }
+    
+    #[test]
+    fn test_compare_against_baselines() {
+        let mut parser = BasicParser::new();
+        let ast = GammaAST::from_ast(&parser.parse("let total = count + count + count").unwrap());
+        let comparison = compare_against_baselines(&ast, CompressionConfig::default()).unwrap();
+        
+        assert_eq!(comparison.raw_bytes, serde_json::to_vec(&ast).unwrap().len());
+        assert!(comparison.nexus_bytes > 0);
+        assert_eq!(comparison.gzip_bytes.is_some(), cfg!(feature = "gzip-baseline"));
+        assert_eq!(comparison.zstd_bytes.is_some(), cfg!(feature = "zstd-baseline"));
+        
+        let beaten = BaselineComparison { raw_bytes: 100, nexus_bytes: 60, gzip_bytes: Some(50), zstd_bytes: None };
+        assert!(!beaten.beats_baselines());
+        assert!(BaselineComparison { gzip_bytes: Some(70), ..beaten }.beats_baselines());
+    }
+    
+    #[test]
+    fn test_metadata_modes() {
+        let mut ast = GammaAST::new();
+        for (id, value) in [(1, "repeated_value"), (2, "repeated_value")] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal).direct(value).metadata("annotation", "keep me").build());
+            ast.add_root(id);
+        }
+        let compress = |metadata_mode| {
+            // The AST is too small for the dictionary to pay off; look at what the pass did anyway
+            let mut engine = NexusCompressionEngine::new(CompressionConfig { metadata_mode, never_expand: false, ..Default::default() });
+            engine.run_pipeline(&ast, CompressionBudget::unlimited()).unwrap().1
+        };
+        
+        // Deduplication no longer strips metadata behind the metadata pass's back
+        let preserved = compress(MetadataMode::Preserve);
+        assert!(preserved.nodes.values().all(|node| node.metadata == ast.nodes[&node.id].metadata));
+        
+        let dropped = compress(MetadataMode::Drop);
+        assert!(dropped.nodes.values().all(|node| node.metadata.is_empty()));
+        
+        let mut interned = compress(MetadataMode::Dictionary);
+        assert_eq!(interned.metadata_dictionary.as_ref().map(PatternDictionary::len), Some(2));
+        interned.expand_metadata().unwrap();
+        assert!(interned.nodes.values().all(|node| node.metadata == ast.nodes[&node.id].metadata));
+    }
+    
+    #[tokio::test]
+    async fn test_never_expand() {
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Literal).direct("x").metadata("k", "v").build());
+        ast.add_root(1);
+        let config = |never_expand| CompressionConfig {
+            metadata_mode: MetadataMode::Dictionary,
+            never_expand,
+            ..Default::default()
+        };
+        let size = |ast: &GammaAST| serde_json::to_vec(ast).unwrap().len();
+        
+        // Dictionary overhead outweighs one tiny metadata entry
+        let (result, bloated) = NexusCompressionEngine::new(config(false)).run_pipeline(&ast, CompressionBudget::unlimited()).unwrap();
+        assert!(size(&bloated) > size(&ast));
+        assert!(!result.returned_original);
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig { trace_passes: true, detailed_stats: true, ..config(true) });
+        let (result, kept) = engine.run_pipeline(&ast, CompressionBudget::unlimited()).unwrap();
+        assert!(result.returned_original);
+        assert_eq!(result.compression_ratio, 1.0);
+        // Nothing describes the discarded output
+        assert_eq!(result.metadata_bytes, ast.total_metadata_bytes());
+        assert!(result.trace.is_none() && result.detailed_stats.is_none());
+        assert_eq!(size(&kept), size(&ast));
+        assert!(engine.get_compression_history()[0].returned_original);
+        
+        let mut in_place = ast.clone();
+        let result = engine.compress_ast_in_place(&mut in_place).await.unwrap();
+        assert!(result.returned_original);
+        assert_eq!(in_place.nodes, ast.nodes);
+        assert!(in_place.metadata_dictionary.is_none());
+    }
+    
+    #[test]
+    fn test_metadata_bloat_warning() {
+        let mut ast = GammaAST::new();
+        ast.add_node(GammaNode::builder(1, GammaNodeType::Literal)
+            .direct("x")
+            .metadata("original_value", "y".repeat(500))
+            .build());
+        ast.add_root(1);
+        assert_eq!(ast.total_metadata_bytes(), 514);
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default());
+        let result = engine.compress_ast_sync(&ast).unwrap();
+        assert_eq!(result.metadata_bytes, 514);
+        assert!(engine.warn_on_metadata_bloat(result.metadata_bytes, result.compressed_size));
+        assert!(!engine.warn_on_metadata_bloat(10, 1000));
+        
+        let silent = NexusCompressionEngine::new(CompressionConfig { metadata_warning_fraction: None, ..Default::default() });
+        assert!(!silent.warn_on_metadata_bloat(result.metadata_bytes, result.compressed_size));
+    }
+    
+    #[tokio::test]
+    async fn test_max_input_nodes() {
+        let ast = create_test_ast();
+        let nodes = ast.nodes.len();
+        let limited = |limit| NexusCompressionEngine::new(CompressionConfig {
+            max_input_nodes: Some(limit),
+            ..Default::default()
+        });
+        
+        let error = limited(nodes - 1).compress_ast(&ast).await.unwrap_err();
+        assert!(matches!(error, CompressionError::InputTooLarge { nodes: n, limit } if n == nodes && limit == nodes - 1));
+        let mut in_place = ast.clone();
+        assert!(matches!(
+            limited(nodes - 1).compress_ast_in_place(&mut in_place).await,
+            Err(CompressionError::InputTooLarge { .. })
+        ));
+        assert_eq!(in_place.nodes, ast.nodes);
+        
+        assert!(limited(nodes).compress_ast(&ast).await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_compression_trace() {
+        let mut ast = GammaAST::new();
+        for (id, value) in [(1, "repeated_value"), (2, "repeated_value"), (3, "x")] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal).direct(value).build());
+            ast.add_root(id);
+        }
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default());
+        assert!(engine.compress_ast(&ast).await.unwrap().trace.is_none());
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig {
+            trace_passes: true,
+            ..Default::default()
+        });
+        let trace = engine.compress_ast(&ast).await.unwrap().trace.unwrap();
+        assert_eq!(trace.passes_for(1), &[PassKind::ValueCompression]);
+        assert_eq!(trace.passes_for(2), &[PassKind::ValueCompression]);
+        assert!(trace.passes_for(3).is_empty());
+        
+        let view = ast.compressed_view(&trace);
+        assert_eq!(view.len(), 3);
+        assert_eq!(view[0].0.id, 1);
+        assert_eq!(view[0].1, &[PassKind::ValueCompression]);
+    }
+    
+    #[tokio::test]
+    async fn test_detailed_stats() {
+        let mut ast = GammaAST::new();
+        for id in 1..=4 {
+            let node_type = if id % 2 == 0 { GammaNodeType::Variable } else { GammaNodeType::Literal };
+            ast.add_node(GammaNode::builder(id, node_type).direct("a_fairly_long_repeated_value").build());
+            ast.add_root(id);
+        }
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig::default());
+        assert!(engine.compress_ast(&ast).await.unwrap().detailed_stats.is_none());
+        
+        let mut engine = NexusCompressionEngine::new(CompressionConfig {
+            detailed_stats: true,
+            ..Default::default()
+        });
+        let (result, compressed) = engine.run_pipeline(&ast, CompressionBudget::unlimited()).unwrap();
+        let stats = result.detailed_stats.unwrap();
+        assert!(stats.saved_by_pass(&PassKind::ValueCompression) > 0);
+        assert_eq!(stats.saved_by_pass(&PassKind::CrossFile), 0);
+        assert!(stats.saved_by_node_type(&GammaNodeType::Literal) > 0);
+        assert!(stats.saved_by_node_type(&GammaNodeType::Variable) > 0);
+        let pass_total: i64 = stats.by_pass.iter().map(|(_, saved)| saved).sum();
+        assert_eq!(stats.total_saved(), pass_total);
+        
+        // The whole-run comparison agrees per type, without a pass breakdown
+        let overall = ast.compress_stats_detailed(&compressed);
+        assert!(overall.by_pass.is_empty());
+        assert_eq!(overall.by_node_type, stats.by_node_type);
+    }
+    
+    #[test]
+    fn test_compress_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::write(root.join(".nexusignore"), "# third-party code\nvendor/\n*_gen.rs\n").unwrap();
+        fs::write(root.join("src/main.rs"), "42").unwrap();
+        fs::write(root.join("src/nested/app.js"), "value").unwrap();
+        fs::write(root.join("src/table_gen.rs"), "1").unwrap();
+        fs::write(root.join("src/broken.rs"), "@").unwrap();
+        fs::write(root.join("vendor/lib.rs"), "7").unwrap();
+        fs::write(root.join("README.md"), "docs").unwrap();
+        
+        let report = compress_directory(root, CompressionConfig::default()).unwrap();
+        let paths: Vec<PathBuf> = report.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("src/main.rs"), PathBuf::from("src/nested/app.js")]);
+        assert_eq!(report.files[1].language, "javascript");
+        assert!(report.files.iter().all(|file| file.result.is_some()));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, PathBuf::from("src/broken.rs"));
+        assert_eq!(report.artifact.asts.len(), 2);
+        assert!(report.corpus_result.is_none());
+        
+        let config = CompressionConfig { compress_as_corpus: true, ..CompressionConfig::default() };
+        let report = compress_directory(root, config).unwrap();
+        assert_eq!(report.artifact.asts.len(), 1);
+        assert_eq!(report.artifact.corpus_roots, vec![vec![1], vec![2]]);
+        assert!(report.corpus_result.is_some());
+        assert!(report.files.iter().all(|file| file.result.is_none()));
+    }
+    
+    #[test]
+    fn test_deduplication_keeps_lowest_id() {
+        let engine = NexusCompressionEngine::new(CompressionConfig::default());
+        let mut ast = GammaAST::new();
+        for id in [9, 5, 2, 7] {
+            ast.add_node(GammaNode::builder(id, GammaNodeType::Literal)
+                .direct(if id == 7 { "other".to_string() } else { "repeated".to_string() })
+                .build());
+        }
+        
+        engine.apply_basic_deduplication(&mut ast).unwrap();
+        assert_eq!(ast.nodes[&2].value, GammaValue::Direct("repeated".to_string()));
+        assert_eq!(ast.nodes[&5].value, GammaValue::PatternRef(2));
+        assert_eq!(ast.nodes[&9].value, GammaValue::PatternRef(2));
+        assert_eq!(ast.compress_level_for_node(9), Some(CompressionLevel::Light));
+        
+        // Deduplicated values decode back to the shared value
+        let restored = decompress_to_ast(&serde_json::to_vec(&ast).unwrap()).unwrap();
+        assert_eq!(restored.nodes[&9].value, GammaValue::Direct("repeated".to_string()));
+        
+        let groups = HashMap::from([("b", vec![8, 3]), ("a", vec![6, 4, 1])]);
+        assert_eq!(sorted_groups(groups), vec![("a", vec![1, 4, 6]), ("b", vec![3, 8])]);
+    }
+    
+    #[test]
+    fn test_reconstructible_round_trip() {
+        let mut ast: GammaAST = (2..8)
+            .map(|id| GammaNode::builder(id, GammaNodeType::Variable).direct("a_long_shared_identifier_name").build())
+            .chain(std::iter::once(GammaNode::builder(1, GammaNodeType::Block).children(2..8).metadata("comment", "the answer").build()))
+            .collect();
+        ast.roots = vec![1];
+        let mut engine = NexusCompressionEngine::new(CompressionConfig {
+            reconstructible: true,
+            ..CompressionConfig::default()
+        });
+        
+        // The value table is stored once, so the output still shrinks
+        let (result, bytes) = engine.compress_to_bytes(&ast).unwrap();
+        assert!(!result.returned_original);
+        assert!(bytes.len() < serde_json::to_vec(&ast).unwrap().len());
+        assert!(result.compressed_size < result.original_size);
+        let compressed = JsonAstSerializer.deserialize(&bytes).unwrap();
+        let table = compressed.reconstruction.as_ref().unwrap();
+        assert_eq!(table.values.values().collect::<Vec<_>>(), vec!["a_long_shared_identifier_name"]);
+        assert!(table.nodes.is_empty() && table.children.is_empty() && table.metadata.is_empty());
+        assert_eq!(result.compressed_size, engine.calculate_ast_size(&compressed));
+        
+        let restored = compressed.decompress().unwrap();
+        assert_eq!(restored.nodes, ast.nodes);
+        assert_eq!(restored.roots, ast.roots);
+        assert!(restored.reconstruction.is_none());
+        
+        // Interned metadata decodes from its dictionary and needs no table entries
+        engine.config.metadata_mode = MetadataMode::Dictionary;
+        let (_, compressed) = engine.run_pipeline(&ast, CompressionBudget::unlimited()).unwrap();
+        assert!(compressed.reconstruction.as_ref().is_some_and(|table| table.metadata.is_empty()));
+        assert_eq!(compressed.decompress().unwrap().nodes, ast.nodes);
+        
+        // Without the table, lossy nodes are reported instead of guessed
+        let mut lossy = ast.clone();
+        lossy.nodes.get_mut(&3).unwrap().value = GammaValue::CompressedHash(7);
+        assert_eq!(lossy.decompress().unwrap_err(), DecodeError::Undecodable(vec![3]));
+    }
+    
+    #[test]
+    fn test_error_source_chain() {
+        use std::error::Error;
+        
+        let error = decompress_to_ast(b"not json").unwrap_err();
+        assert!(matches!(error, CompressionError::Serialization(_)));
+        let source = error.source().expect("serialization error keeps its cause");
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+        
+        let error = compress_source("rust", "@", CompressionConfig::default()).unwrap_err();
+        assert!(error.source().and_then(|source| source.downcast_ref::<ParseError>()).is_some());
+        
+        let missing = tempfile::TempDir::new().unwrap().path().join("missing");
+        let error = compress_directory(&missing, CompressionConfig::default()).unwrap_err();
+        assert!(matches!(error, CompressionError::Io(_)));
+        assert!(error.source().is_some());
+    }
+    
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(glob_match("te?t_*", "test_main"));
+        assert!(!glob_match("*.rs", "main.js"));
+        assert!(glob_match("src/*/gen", "src/a/gen"));
+    }
+    
+    #[test]
+    fn test_compress_source_round_trip() {
+        let bytes = compress_source("python", "\"hello world\"", CompressionConfig::default()).unwrap();
+        let ast = decompress_to_ast(&bytes).unwrap();
+        
+        assert_eq!(ast.source_language, "python");
+        assert_eq!(ast.roots.len(), 1);
+        assert_eq!(ast.nodes.len(), 1);
+        assert!(decompress_to_ast(b"not a gamma ast").is_err());
+    }
+    
     #[tokio::test]
     async fn test_structural_integrity() {
         let config = CompressionConfig::default();
@@ -516,7 +2245,7 @@ mod tests {
         let ast = create_test_ast();
         
         // Test structural integrity verification
-        let integrity = engine.verify_structural_integrity(&ast, &ast);
+        let integrity = engine.verify_structural_integrity(&engine.input_shape(&ast), &ast);
         assert!(integrity);
     }
 }
@@ -89,6 +89,23 @@ pub struct ParseError {
     pub location: Location,
     /// Error severity
     pub severity: ErrorSeverity,
+    /// What went wrong, independent of the message wording
+    pub kind: ParseErrorKind,
+}
+
+/// Category of a parse error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A character that starts no token
+    InvalidCharacter,
+    /// A backslash escape the string rules do not allow
+    InvalidEscape,
+    /// A string or comment still open at the end of input
+    Unterminated,
+    /// A token the grammar does not expect here
+    UnexpectedToken,
+    /// Input ended where more was required
+    UnexpectedEndOfInput,
 }
 
 /// Error severity levels
@@ -139,6 +156,93 @@ pub trait Lexer {
     fn peek_token(&self) -> ParseResult<Option<&Token>>;
 }
 
+/// String literal rules for a language dialect
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringRules {
+    /// Characters that open and close a string literal
+    pub quote_chars: Vec<char>,
+    /// Prefixes that introduce a raw string with no escape processing (e.g. `r`)
+    pub raw_prefixes: Vec<String>,
+    /// Allow `#` fences around raw strings, as in Rust's `r#"..."#`
+    pub raw_hash_fences: bool,
+    /// Recognise triple-quoted strings, as in Python's `"""..."""`
+    pub triple_quotes: bool,
+    /// Characters accepted after a backslash in non-raw strings
+    ///
+    /// `x`, `u` and `U` take 2, 4 and 8 hex digits and `N` a `{name}`; the
+    /// rest stand for themselves or their usual control character.
+    pub allowed_escapes: Vec<char>,
+    /// Read a backslash and up to three octal digits as one character, as in Python's `"\101"`
+    pub octal_escapes: bool,
+    /// A backslash in a raw string keeps the next quote from closing it, as in Python's `r"\""`
+    pub raw_escaped_quotes: bool,
+}
+
+impl StringRules {
+    /// Single and double quotes with C-style escapes
+    pub fn c_like() -> Self {
+        Self {
+            quote_chars: vec!['"', '\''],
+            raw_prefixes: Vec::new(),
+            raw_hash_fences: false,
+            triple_quotes: false,
+            allowed_escapes: vec!['n', 't', 'r', '\\', '"', '\''],
+            octal_escapes: false,
+            raw_escaped_quotes: false,
+        }
+    }
+    
+    /// Rust strings with `r"..."` / `r#"..."#` raw literals
+    pub fn rust() -> Self {
+        Self {
+            raw_prefixes: vec!["r".to_string(), "br".to_string()],
+            raw_hash_fences: true,
+            allowed_escapes: vec!['n', 't', 'r', '0', '\\', '"', '\''],
+            ..Self::c_like()
+        }
+    }
+    
+    /// Python strings with triple quotes and `r"..."` raw literals
+    pub fn python() -> Self {
+        Self {
+            raw_prefixes: vec!["r".to_string(), "R".to_string()],
+            triple_quotes: true,
+            allowed_escapes: vec![
+                'n', 't', 'r', 'a', 'b', 'f', 'v', 'x', 'u', 'U', 'N', '\\', '"', '\'', '\n',
+                '0', '1', '2', '3', '4', '5', '6', '7',
+            ],
+            octal_escapes: true,
+            raw_escaped_quotes: true,
+            ..Self::c_like()
+        }
+    }
+    
+    /// JavaScript strings including backtick template literals
+    pub fn javascript() -> Self {
+        Self {
+            quote_chars: vec!['"', '\'', '`'],
+            allowed_escapes: vec!['n', 't', 'r', '0', '\\', '"', '\'', '`'],
+            ..Self::c_like()
+        }
+    }
+    
+    /// Pick rules by language name, falling back to C-like rules
+    pub fn for_language(language: &str) -> Self {
+        match language.to_lowercase().as_str() {
+            "rust" | "rs" => Self::rust(),
+            "python" | "py" => Self::python(),
+            "javascript" | "js" | "typescript" | "ts" => Self::javascript(),
+            _ => Self::c_like(),
+        }
+    }
+}
+
+impl Default for StringRules {
+    fn default() -> Self {
+        Self::c_like()
+    }
+}
+
 /// Basic lexer implementation
 pub struct BasicLexer {
     /// Source code to tokenize
@@ -151,8 +255,14 @@ pub struct BasicLexer {
     column: usize,
     /// Tokens that have been generated
     tokens: Vec<Token>,
+    /// Line and column where the token being read starts
+    token_start: (usize, usize),
     /// Current token being built
     current_token: Option<Token>,
+    /// String literal dialect
+    string_rules: StringRules,
+    /// Emit runs of spaces and tabs as `Whitespace` tokens instead of skipping them
+    preserve_whitespace: bool,
 }
 
 impl BasicLexer {
@@ -164,10 +274,34 @@ impl BasicLexer {
             line: 1,
             column: 1,
             tokens: Vec::new(),
+            token_start: (1, 1),
             current_token: None,
+            string_rules: StringRules::default(),
+            preserve_whitespace: false,
+        }
+    }
+    
+    /// Create a lexer using the given string literal rules
+    pub fn with_string_rules(string_rules: StringRules) -> Self {
+        Self {
+            string_rules,
+            ..Self::new()
         }
     }
     
+    /// Change the string literal rules
+    pub fn set_string_rules(&mut self, string_rules: StringRules) {
+        self.string_rules = string_rules;
+    }
+    
+    /// Emit `Whitespace` tokens holding the exact skipped text
+    ///
+    /// Newlines are always `Newline` tokens; this covers the spaces, tabs and
+    /// carriage returns between other tokens, so indentation survives tokenizing.
+    pub fn set_preserve_whitespace(&mut self, preserve: bool) {
+        self.preserve_whitespace = preserve;
+    }
+    
     /// Set the source code to tokenize
     pub fn set_source(&mut self, source: String) {
         self.source = source;
@@ -175,6 +309,7 @@ impl BasicLexer {
         self.line = 1;
         self.column = 1;
         self.tokens.clear();
+        self.token_start = (1, 1);
         self.current_token = None;
     }
     
@@ -193,6 +328,37 @@ impl BasicLexer {
         self.source.chars().nth(self.position + 1)
     }
     
+    /// Get the character `offset` positions ahead without advancing
+    fn char_at(&self, offset: usize) -> Option<char> {
+        self.source.chars().nth(self.position + offset)
+    }
+    
+    /// Check whether the source at the current position starts with `text`
+    fn starts_with_at(&self, offset: usize, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.char_at(offset + i) == Some(c))
+    }
+    
+    /// Length of the raw-string prefix at the current position, if a raw string starts here
+    fn raw_string_prefix_len(&self) -> Option<usize> {
+        for prefix in &self.string_rules.raw_prefixes {
+            if !self.starts_with_at(0, prefix) {
+                continue;
+            }
+            let mut offset = prefix.chars().count();
+            if self.string_rules.raw_hash_fences {
+                while self.char_at(offset) == Some('#') {
+                    offset += 1;
+                }
+            }
+            if let Some(ch) = self.char_at(offset) {
+                if self.string_rules.quote_chars.contains(&ch) {
+                    return Some(prefix.chars().count());
+                }
+            }
+        }
+        None
+    }
+    
     /// Advance to the next character
     fn advance(&mut self) {
         if let Some(ch) = self.current_char() {
@@ -206,6 +372,18 @@ impl BasicLexer {
         self.position += 1;
     }
     
+    /// Consume the operator character at the cursor, plus `second` if it follows
+    ///
+    /// Returns whether the two-character form was read.
+    fn read_operator(&mut self, second: char) -> bool {
+        let is_pair = self.peek_char() == Some(second);
+        self.advance();
+        if is_pair {
+            self.advance();
+        }
+        is_pair
+    }
+    
     /// Skip whitespace characters
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.current_char() {
@@ -217,6 +395,22 @@ impl BasicLexer {
         }
     }
     
+    /// Read the whitespace `skip_whitespace` would skip
+    fn read_whitespace(&mut self) -> String {
+        let mut whitespace = String::new();
+        
+        while let Some(ch) = self.current_char() {
+            if ch.is_whitespace() && ch != '\n' {
+                whitespace.push(ch);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        
+        whitespace
+    }
+    
     /// Read an identifier or keyword
     fn read_identifier(&mut self) -> String {
         let mut identifier = String::new();
@@ -260,34 +454,32 @@ impl BasicLexer {
         let mut string = String::new();
         let quote_char = self.current_char().ok_or_else(|| ParseError {
             message: "Expected quote character".to_string(),
-            location: Location { line: self.line, column: self.column, file: None },
+            kind: ParseErrorKind::UnexpectedEndOfInput,
+            location: Location::new(self.line, self.column),
             severity: ErrorSeverity::Error,
         })?;
         
-        self.advance(); // Skip opening quote
+        let triple = self.string_rules.triple_quotes
+            && self.char_at(1) == Some(quote_char)
+            && self.char_at(2) == Some(quote_char);
+        let delimiter: String = std::iter::repeat_n(quote_char, if triple { 3 } else { 1 }).collect();
+        
+        let (line, column) = (self.line, self.column);
+        for _ in 0..delimiter.len() {
+            self.advance(); // Skip opening quote(s)
+        }
         
         while let Some(ch) = self.current_char() {
-            if ch == quote_char {
-                self.advance(); // Skip closing quote
-                break;
+            if self.starts_with_at(0, &delimiter) {
+                for _ in 0..delimiter.len() {
+                    self.advance(); // Skip closing quote(s)
+                }
+                return Ok(string);
             } else if ch == '\\' {
                 // Handle escape sequences
                 self.advance();
                 if let Some(escaped) = self.current_char() {
-                    match escaped {
-                        'n' => string.push('\n'),
-                        't' => string.push('\t'),
-                        'r' => string.push('\r'),
-                        '\\' => string.push('\\'),
-                        '"' => string.push('"'),
-                        '\'' => string.push('\''),
-                        _ => return Err(ParseError {
-                            message: format!("Invalid escape sequence \\{}", escaped),
-                            location: Location { line: self.line, column: self.column, file: None },
-                            severity: ErrorSeverity::Error,
-                        }),
-                    }
-                    self.advance();
+                    self.read_escape(escaped, &mut string)?;
                 }
             } else {
                 string.push(ch);
@@ -295,19 +487,189 @@ impl BasicLexer {
             }
         }
         
-        Ok(string)
+        Err(Self::unterminated_string(line, column))
+    }
+    
+    /// Decode the escape sequence starting at `escaped`, just after its backslash
+    fn read_escape(&mut self, escaped: char, string: &mut String) -> ParseResult<()> {
+        let invalid = |lexer: &Self| ParseError {
+            message: format!("Invalid escape sequence \\{}", escaped),
+            kind: ParseErrorKind::InvalidEscape,
+            location: Location::new(lexer.line, lexer.column),
+            severity: ErrorSeverity::Error,
+        };
+        if !self.string_rules.allowed_escapes.contains(&escaped) {
+            return Err(invalid(self));
+        }
+        
+        if self.string_rules.octal_escapes && escaped.is_digit(8) {
+            let mut code = 0;
+            for _ in 0..3 {
+                match self.current_char().and_then(|digit| digit.to_digit(8)) {
+                    Some(digit) => {
+                        code = code * 8 + digit;
+                        self.advance();
+                    }
+                    None => break,
+                }
+            }
+            string.push(char::from_u32(code).ok_or_else(|| invalid(self))?);
+            return Ok(());
+        }
+        
+        let hex_digits = match escaped {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => 0,
+        };
+        if hex_digits > 0 {
+            self.advance();
+            let mut code = 0;
+            for _ in 0..hex_digits {
+                let digit = self.current_char().and_then(|digit| digit.to_digit(16)).ok_or_else(|| invalid(self))?;
+                code = code * 16 + digit;
+                self.advance();
+            }
+            string.push(char::from_u32(code).ok_or_else(|| invalid(self))?);
+            return Ok(());
+        }
+        
+        if escaped == 'N' {
+            // Without a Unicode name table the escape is kept as written
+            self.advance();
+            if self.current_char() != Some('{') {
+                return Err(invalid(self));
+            }
+            string.push_str("\\N");
+            while let Some(ch) = self.current_char() {
+                string.push(ch);
+                self.advance();
+                if ch == '}' {
+                    return Ok(());
+                }
+            }
+            return Err(invalid(self));
+        }
+        
+        match escaped {
+            'n' => string.push('\n'),
+            't' => string.push('\t'),
+            'r' => string.push('\r'),
+            '0' => string.push('\0'),
+            'a' => string.push('\u{7}'),
+            'b' => string.push('\u{8}'),
+            'f' => string.push('\u{c}'),
+            'v' => string.push('\u{b}'),
+            '\n' => {} // Line continuation
+            other => string.push(other),
+        }
+        self.advance();
+        Ok(())
+    }
+    
+    /// Error for a string literal that reaches the end of input, located at its opening quote
+    fn unterminated_string(line: usize, column: usize) -> ParseError {
+        ParseError {
+            message: "Unterminated string literal".to_string(),
+            kind: ParseErrorKind::Unterminated,
+            location: Location::new(line, column),
+            severity: ErrorSeverity::Fatal,
+        }
+    }
+    
+    /// Read a raw string literal (prefix, optional `#` fences, no escape processing)
+    fn read_raw_string(&mut self, prefix_len: usize) -> ParseResult<String> {
+        let (line, column) = (self.line, self.column);
+        for _ in 0..prefix_len {
+            self.advance();
+        }
+        
+        let mut hashes = 0;
+        while self.current_char() == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        
+        let quote_char = self.current_char().ok_or_else(|| ParseError {
+            message: "Expected quote character".to_string(),
+            kind: ParseErrorKind::UnexpectedEndOfInput,
+            location: Location::new(self.line, self.column),
+            severity: ErrorSeverity::Error,
+        })?;
+        let triple = self.string_rules.triple_quotes
+            && self.char_at(1) == Some(quote_char)
+            && self.char_at(2) == Some(quote_char);
+        let quotes = if triple { 3 } else { 1 };
+        for _ in 0..quotes {
+            self.advance(); // Skip opening quote(s)
+        }
+        
+        let mut closing: String = std::iter::repeat_n(quote_char, quotes).collect();
+        closing.push_str(&"#".repeat(hashes));
+        
+        let mut string = String::new();
+        while let Some(ch) = self.current_char() {
+            if self.starts_with_at(0, &closing) {
+                for _ in 0..closing.len() {
+                    self.advance();
+                }
+                return Ok(string);
+            }
+            string.push(ch);
+            self.advance();
+            // The backslash stays in the string, but the quote after it does not close it
+            if ch == '\\' && self.string_rules.raw_escaped_quotes {
+                if let Some(next) = self.current_char() {
+                    string.push(next);
+                    self.advance();
+                }
+            }
+        }
+        
+        Err(Self::unterminated_string(line, column))
+    }
+    
+    /// Mark the current position as the start of the next token
+    fn start_token(&mut self) {
+        self.token_start = (self.line, self.column);
+    }
+    
+    /// Read a `/* ... */` comment, returning the text between the delimiters
+    ///
+    /// Comments do not nest: the first `*/` closes it. An unterminated comment
+    /// is a fatal error located at its opening `/*`.
+    fn read_block_comment(&mut self) -> ParseResult<String> {
+        let (line, column) = (self.line, self.column);
+        self.advance(); // Skip '/'
+        self.advance(); // Skip '*'
+        
+        let mut comment = String::new();
+        while let Some(ch) = self.current_char() {
+            if self.starts_with_at(0, "*/") {
+                self.advance();
+                self.advance();
+                return Ok(comment);
+            }
+            comment.push(ch);
+            self.advance();
+        }
+        
+        Err(ParseError {
+            message: "Unterminated block comment".to_string(),
+            kind: ParseErrorKind::Unterminated,
+            location: Location::new(line, column),
+            severity: ErrorSeverity::Fatal,
+        })
     }
     
-    /// Create a token at the current position
+    /// Create a token spanning from its start to the current position
     fn create_token(&self, token_type: TokenType, value: String) -> Token {
+        let (line, column) = self.token_start;
         Token {
             token_type,
             value,
-            location: Location {
-                line: self.line,
-                column: self.column,
-                file: None,
-            },
+            location: Location::new(line, column).with_end(self.line, self.column),
         }
     }
 }
@@ -318,14 +680,37 @@ impl Lexer for BasicLexer {
         self.tokens.clear();
         
         while !self.is_eof() {
-            self.skip_whitespace();
+            if self.preserve_whitespace {
+                self.start_token();
+                let whitespace = self.read_whitespace();
+                if !whitespace.is_empty() {
+                    let token = self.create_token(TokenType::Whitespace, whitespace);
+                    self.tokens.push(token);
+                }
+            } else {
+                self.skip_whitespace();
+            }
             
             if self.is_eof() {
                 break;
             }
             
             if let Some(ch) = self.current_char() {
+                self.start_token();
                 let token = match ch {
+                    // Raw strings (checked before identifiers since prefixes are letters)
+                    _ if self.raw_string_prefix_len().is_some() => {
+                        let prefix_len = self.raw_string_prefix_len().unwrap_or(0);
+                        let string = self.read_raw_string(prefix_len)?;
+                        self.create_token(TokenType::String, string)
+                    }
+                    
+                    // Strings
+                    _ if self.string_rules.quote_chars.contains(&ch) => {
+                        let string = self.read_string()?;
+                        self.create_token(TokenType::String, string)
+                    }
+                    
                     // Identifiers and keywords
                     'a'..='z' | 'A'..='Z' | '_' => {
                         let identifier = self.read_identifier();
@@ -359,10 +744,23 @@ impl Lexer for BasicLexer {
                         self.create_token(token_type, number)
                     }
                     
-                    // Strings
-                    '"' | '\'' => {
-                        let string = self.read_string()?;
-                        self.create_token(TokenType::String, string)
+                    // Comments (checked before '/' is read as division)
+                    '/' if self.peek_char() == Some('*') => {
+                        let comment = self.read_block_comment()?;
+                        self.create_token(TokenType::Comment, comment)
+                    }
+                    '/' if self.peek_char() == Some('/') => {
+                        self.advance(); // Skip first '/'
+                        self.advance(); // Skip second '/'
+                        let mut comment = String::new();
+                        while let Some(ch) = self.current_char() {
+                            if ch == '\n' {
+                                break;
+                            }
+                            comment.push(ch);
+                            self.advance();
+                        }
+                        self.create_token(TokenType::Comment, comment)
                     }
                     
                     // Operators and delimiters
@@ -372,63 +770,53 @@ impl Lexer for BasicLexer {
                     '/' => { self.advance(); self.create_token(TokenType::Divide, "/".to_string()) }
                     '%' => { self.advance(); self.create_token(TokenType::Modulo, "%".to_string()) }
                     '=' => {
-                        self.advance();
-                        if let Some('=') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('=') {
                             self.create_token(TokenType::Equal, "==".to_string())
                         } else {
                             self.create_token(TokenType::Assign, "=".to_string())
                         }
                     }
                     '<' => {
-                        self.advance();
-                        if let Some('=') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('=') {
                             self.create_token(TokenType::LessEqual, "<=".to_string())
                         } else {
                             self.create_token(TokenType::LessThan, "<".to_string())
                         }
                     }
                     '>' => {
-                        self.advance();
-                        if let Some('=') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('=') {
                             self.create_token(TokenType::GreaterEqual, ">=".to_string())
                         } else {
                             self.create_token(TokenType::GreaterThan, ">".to_string())
                         }
                     }
                     '!' => {
-                        self.advance();
-                        if let Some('=') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('=') {
                             self.create_token(TokenType::NotEqual, "!=".to_string())
                         } else {
                             self.create_token(TokenType::Not, "!".to_string())
                         }
                     }
                     '&' => {
-                        self.advance();
-                        if let Some('&') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('&') {
                             self.create_token(TokenType::And, "&&".to_string())
                         } else {
                             return Err(ParseError {
                                 message: "Expected '&' after '&'".to_string(),
-                                location: Location { line: self.line, column: self.column, file: None },
+                                kind: ParseErrorKind::InvalidCharacter,
+                                location: Location::new(self.line, self.column),
                                 severity: ErrorSeverity::Error,
                             });
                         }
                     }
                     '|' => {
-                        self.advance();
-                        if let Some('|') = self.peek_char() {
-                            self.advance();
+                        if self.read_operator('|') {
                             self.create_token(TokenType::Or, "||".to_string())
                         } else {
                             return Err(ParseError {
                                 message: "Expected '|' after '|'".to_string(),
-                                location: Location { line: self.line, column: self.column, file: None },
+                                kind: ParseErrorKind::InvalidCharacter,
+                                location: Location::new(self.line, self.column),
                                 severity: ErrorSeverity::Error,
                             });
                         }
@@ -444,26 +832,6 @@ impl Lexer for BasicLexer {
                     '.' => { self.advance(); self.create_token(TokenType::Dot, ".".to_string()) }
                     ':' => { self.advance(); self.create_token(TokenType::Colon, ":".to_string()) }
                     
-                    // Comments
-                    '/' => {
-                        if let Some('/') = self.peek_char() {
-                            // Single line comment
-                            self.advance(); // Skip first '/'
-                            self.advance(); // Skip second '/'
-                            let mut comment = String::new();
-                            while let Some(ch) = self.current_char() {
-                                if ch == '\n' {
-                                    break;
-                                }
-                                comment.push(ch);
-                                self.advance();
-                            }
-                            self.create_token(TokenType::Comment, comment)
-                        } else {
-                            self.create_token(TokenType::Divide, "/".to_string())
-                        }
-                    }
-                    
                     // Newlines
                     '\n' => {
                         self.advance();
@@ -474,7 +842,8 @@ impl Lexer for BasicLexer {
                     _ => {
                         return Err(ParseError {
                             message: format!("Unexpected character: {}", ch),
-                            location: Location { line: self.line, column: self.column, file: None },
+                            kind: ParseErrorKind::InvalidCharacter,
+                            location: Location::new(self.line, self.column),
                             severity: ErrorSeverity::Error,
                         });
                     }
@@ -485,6 +854,7 @@ impl Lexer for BasicLexer {
         }
         
         // Add EOF token
+        self.start_token();
         self.tokens.push(self.create_token(TokenType::EOF, "".to_string()));
         
         Ok(self.tokens.clone())
@@ -523,6 +893,14 @@ impl BasicParser {
         }
     }
     
+    /// Create a parser whose lexer uses the given string literal rules
+    pub fn with_string_rules(string_rules: StringRules) -> Self {
+        Self {
+            lexer: BasicLexer::with_string_rules(string_rules),
+            ..Self::new()
+        }
+    }
+    
     /// Get the current token
     fn current_token(&self) -> Option<&Token> {
         self.tokens.get(self.position)
@@ -549,7 +927,8 @@ impl BasicParser {
         if self.position >= self.tokens.len() {
             return Err(ParseError {
                 message: format!("Expected {:?}, but reached end of input", expected_type),
-                location: Location { line: 1, column: 1, file: None },
+                kind: ParseErrorKind::UnexpectedEndOfInput,
+                location: Location::new(1, 1),
                 severity: ErrorSeverity::Fatal,
             });
         }
@@ -575,19 +954,339 @@ impl BasicParser {
         } else {
             Err(ParseError {
                 message: format!("Expected {:?}, got {:?}", expected_type, token_type),
+                kind: ParseErrorKind::UnexpectedToken,
                 location,
                 severity: ErrorSeverity::Error,
             })
         }
     }
     
-    /// Parse a simple expression
+    /// Check whether the current token has the given type
+    fn check(&self, token_type: &TokenType) -> bool {
+        self.current_token().is_some_and(|token| &token.token_type == token_type)
+    }
+    
+    /// Check if only the EOF token (or nothing) is left
+    fn at_end(&self) -> bool {
+        self.is_eof() || self.check(&TokenType::EOF)
+    }
+    
+    /// Skip newlines inside a construct that may span lines
+    fn skip_newlines(&mut self) {
+        while self.check(&TokenType::Newline) {
+            self.advance();
+        }
+    }
+    
+    /// Skip statement separators (semicolons, newlines)
+    fn skip_separators(&mut self) {
+        while self.check(&TokenType::Semicolon) || self.check(&TokenType::Newline) {
+            self.advance();
+        }
+    }
+    
+    /// Check if the current token ends a statement
+    fn at_statement_end(&self) -> bool {
+        self.at_end()
+            || self.check(&TokenType::Semicolon)
+            || self.check(&TokenType::Newline)
+            || self.check(&TokenType::RightBrace)
+    }
+    
+    /// Check if the current token can begin a statement
+    fn at_statement_start(&self) -> bool {
+        self.current_token().is_some_and(|token| matches!(
+            token.token_type,
+            TokenType::Integer
+                | TokenType::Float
+                | TokenType::String
+                | TokenType::Boolean
+                | TokenType::Null
+                | TokenType::Identifier
+                | TokenType::Let
+                | TokenType::Const
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Function
+                | TokenType::Return
+                | TokenType::LeftParen
+                | TokenType::LeftBrace
+                | TokenType::Minus
+                | TokenType::Not
+        ))
+    }
+    
+    /// Skip the rest of a broken statement so parsing can resume at the next one
+    fn synchronize(&mut self) {
+        while !self.at_statement_end() {
+            self.advance();
+        }
+    }
+    
+    /// Parse statements until EOF, or until the closing brace when `in_block` is set
+    ///
+    /// Recoverable errors become `NodeType::Error` statements and parsing resumes
+    /// after the next separator; fatal errors abort the parse.
+    fn parse_statements(&mut self, in_block: bool) -> ParseResult<Vec<Node>> {
+        let mut statements = Vec::new();
+        
+        loop {
+            self.skip_separators();
+            if self.at_end() || (in_block && self.check(&TokenType::RightBrace)) {
+                break;
+            }
+            
+            let start = self.position;
+            match self.parse_statement() {
+                Ok(statement) => statements.push(self.spanned(statement, start)),
+                Err(error) if error.severity != ErrorSeverity::Fatal => {
+                    // Always consume the offending token so recovery makes progress
+                    if self.position == start {
+                        self.advance();
+                    }
+                    statements.push(self.recover_from(error));
+                    self.synchronize();
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        
+        Ok(statements)
+    }
+    
+    /// Parse a single statement
+    ///
+    /// A statement ends at a separator or where the next statement begins, so
+    /// `42 3 foo` is three statements.
+    fn parse_statement(&mut self) -> ParseResult<Node> {
+        let token_type = self.current_token().map(|token| token.token_type.clone());
+        match token_type {
+            // Compound statements end at their closing brace
+            Some(TokenType::If) => return self.parse_if(),
+            Some(TokenType::While) => return self.parse_while(),
+            Some(TokenType::Function) => return self.parse_function(),
+            Some(TokenType::LeftBrace) => return self.parse_block(),
+            _ => {}
+        }
+        
+        let statement = match token_type {
+            Some(TokenType::Let) | Some(TokenType::Const) => self.parse_declaration()?,
+            Some(TokenType::Return) => self.parse_return()?,
+            _ => self.parse_expression_statement()?,
+        };
+        
+        if !self.at_statement_end() && !self.at_statement_start() {
+            let token = &self.tokens[self.position];
+            return Err(ParseError {
+                message: format!("Expected ';' or newline after statement, got {:?}", token.token_type),
+                kind: ParseErrorKind::UnexpectedToken,
+                location: token.location.clone(),
+                severity: ErrorSeverity::Error,
+            });
+        }
+        
+        Ok(statement)
+    }
+    
+    /// Parse `let`/`const` declarations with an optional type and initializer
+    fn parse_declaration(&mut self) -> ParseResult<Node> {
+        let kind = self.current_token().map(|token| token.value.clone()).unwrap_or_default();
+        self.advance();
+        
+        let name = self.expect(TokenType::Identifier)?;
+        let mut declaration = Node::new(NodeType::Declaration, name.value);
+        declaration.add_metadata("kind".to_string(), kind);
+        
+        if self.check(&TokenType::Colon) {
+            self.advance();
+            let type_name = self.expect(TokenType::Identifier)?;
+            declaration.add_metadata("type".to_string(), type_name.value);
+        }
+        
+        if self.check(&TokenType::Assign) {
+            self.advance();
+            self.skip_newlines();
+            declaration.add_child(self.parse_expression()?);
+        }
+        
+        Ok(declaration)
+    }
+    
+    /// Parse a `{ ... }` block
+    fn parse_block(&mut self) -> ParseResult<Node> {
+        let start = self.position;
+        self.expect(TokenType::LeftBrace)?;
+        let mut block = Node::new(NodeType::Block, String::new());
+        for statement in self.parse_statements(true)? {
+            block.add_child(statement);
+        }
+        self.expect(TokenType::RightBrace)?;
+        Ok(self.spanned(block, start))
+    }
+    
+    /// Parse `if cond { ... }` with optional `else if` / `else` chains
+    fn parse_if(&mut self) -> ParseResult<Node> {
+        let start = self.position;
+        self.advance();
+        let mut node = Node::new(NodeType::If, "if".to_string());
+        node.add_child(self.parse_expression()?);
+        self.skip_newlines();
+        node.add_child(self.parse_block()?);
+        
+        // `else` may sit on the line after the closing brace
+        let before_else = self.position;
+        self.skip_newlines();
+        if self.check(&TokenType::Else) {
+            self.advance();
+            self.skip_newlines();
+            let alternative = if self.check(&TokenType::If) {
+                self.parse_if()?
+            } else {
+                self.parse_block()?
+            };
+            node.add_child(alternative);
+        } else {
+            self.position = before_else;
+        }
+        
+        Ok(self.spanned(node, start))
+    }
+    
+    /// Parse `while cond { ... }`
+    fn parse_while(&mut self) -> ParseResult<Node> {
+        self.advance();
+        let mut node = Node::new(NodeType::While, "while".to_string());
+        node.add_child(self.parse_expression()?);
+        self.skip_newlines();
+        node.add_child(self.parse_block()?);
+        Ok(node)
+    }
+    
+    /// Parse `fn name(params) { ... }`; parameters come first, the body last
+    fn parse_function(&mut self) -> ParseResult<Node> {
+        self.advance();
+        let name = self.expect(TokenType::Identifier)?;
+        let mut function = Node::new(NodeType::Function, name.value);
+        
+        self.expect(TokenType::LeftParen)?;
+        loop {
+            self.skip_newlines();
+            if self.check(&TokenType::RightParen) {
+                break;
+            }
+            let parameter_start = self.position;
+            let parameter = self.expect(TokenType::Identifier)?;
+            let mut parameter = Node::new(NodeType::Variable, parameter.value);
+            if self.check(&TokenType::Colon) {
+                self.advance();
+                let type_name = self.expect(TokenType::Identifier)?;
+                parameter.add_metadata("type".to_string(), type_name.value);
+            }
+            function.add_child(self.spanned(parameter, parameter_start));
+            self.skip_newlines();
+            if !self.check(&TokenType::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(TokenType::RightParen)?;
+        
+        self.skip_newlines();
+        function.add_child(self.parse_block()?);
+        Ok(function)
+    }
+    
+    /// Parse `return` with an optional value
+    fn parse_return(&mut self) -> ParseResult<Node> {
+        self.advance();
+        let mut node = Node::new(NodeType::Return, "return".to_string());
+        if !self.at_statement_end() {
+            node.add_child(self.parse_expression()?);
+        }
+        Ok(node)
+    }
+    
+    /// Parse an expression, or an assignment when followed by `=`
+    fn parse_expression_statement(&mut self) -> ParseResult<Node> {
+        let start = self.position;
+        let target = self.parse_expression()?;
+        if !self.check(&TokenType::Assign) {
+            return Ok(target);
+        }
+        
+        self.advance();
+        self.skip_newlines();
+        let mut assignment = Node::new(NodeType::Assignment, "=".to_string());
+        assignment.add_child(target);
+        assignment.add_child(self.parse_expression()?);
+        Ok(self.spanned(assignment, start))
+    }
+    
+    /// Binding strength of a binary operator; higher binds tighter
+    fn binary_precedence(token_type: &TokenType) -> Option<u8> {
+        match token_type {
+            TokenType::Or => Some(1),
+            TokenType::And => Some(2),
+            TokenType::Equal | TokenType::NotEqual => Some(3),
+            TokenType::LessThan | TokenType::GreaterThan | TokenType::LessEqual | TokenType::GreaterEqual => Some(4),
+            TokenType::Plus | TokenType::Minus => Some(5),
+            TokenType::Multiply | TokenType::Divide | TokenType::Modulo => Some(6),
+            _ => None,
+        }
+    }
+    
+    /// Parse an expression with binary operators
     fn parse_expression(&mut self) -> ParseResult<Node> {
+        self.parse_binary(0)
+    }
+    
+    /// Precedence climbing over left-associative binary operators
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<Node> {
+        let start = self.position;
+        let mut left = self.parse_unary()?;
+        
+        while let Some(token) = self.current_token() {
+            let Some(precedence) = Self::binary_precedence(&token.token_type) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            let operator = token.value.clone();
+            self.advance();
+            self.skip_newlines();
+            
+            let right = self.parse_binary(precedence + 1)?;
+            let mut node = Node::new(NodeType::BinaryOp, operator);
+            node.add_child(left);
+            node.add_child(right);
+            left = self.spanned(node, start);
+        }
+        
+        Ok(left)
+    }
+    
+    /// Parse prefix `-` and `!`
+    fn parse_unary(&mut self) -> ParseResult<Node> {
+        let start = self.position;
+        if self.check(&TokenType::Minus) || self.check(&TokenType::Not) {
+            let operator = self.current_token().map(|token| token.value.clone()).unwrap_or_default();
+            self.advance();
+            let mut node = Node::new(NodeType::UnaryOp, operator);
+            node.add_child(self.parse_unary()?);
+            return Ok(self.spanned(node, start));
+        }
+        self.parse_primary()
+    }
+    
+    /// Parse a literal, variable, call or parenthesized expression
+    fn parse_primary(&mut self) -> ParseResult<Node> {
         // Check bounds first
         if self.position >= self.tokens.len() {
             return Err(ParseError {
                 message: "Unexpected end of input in expression".to_string(),
-                location: Location { line: 1, column: 1, file: None },
+                kind: ParseErrorKind::UnexpectedEndOfInput,
+                location: Location::new(1, 1),
                 severity: ErrorSeverity::Fatal,
             });
         }
@@ -598,7 +1297,18 @@ impl BasicParser {
         let value = token.value.clone();
         let location = token.location.clone();
         
+        // Leave closing braces and EOF for the enclosing statement list
+        if matches!(token_type, TokenType::RightBrace | TokenType::EOF) {
+            return Err(ParseError {
+                message: format!("Unexpected token in expression: {:?}", token_type),
+                kind: ParseErrorKind::UnexpectedToken,
+                location,
+                severity: ErrorSeverity::Error,
+            });
+        }
+        
         // Advance using the existing method
+        let start = self.position;
         self.advance();
         
         let node = match token_type {
@@ -608,39 +1318,94 @@ impl BasicParser {
             TokenType::String => {
                 Node::new(NodeType::Literal, value)
             }
-            TokenType::Boolean => {
+            TokenType::Boolean | TokenType::Null => {
                 Node::new(NodeType::Literal, value)
             }
+            TokenType::Identifier if self.check(&TokenType::LeftParen) => {
+                self.parse_call(value)?
+            }
             TokenType::Identifier => {
                 Node::new(NodeType::Variable, value)
             }
+            TokenType::LeftParen => {
+                self.skip_newlines();
+                let inner = self.parse_expression()?;
+                self.skip_newlines();
+                self.expect(TokenType::RightParen)?;
+                inner
+            }
             _ => {
                 return Err(ParseError {
                     message: format!("Unexpected token in expression: {:?}", token_type),
+                    kind: ParseErrorKind::UnexpectedToken,
                     location,
                     severity: ErrorSeverity::Error,
                 });
             }
         };
         
-        Ok(node)
+        Ok(self.spanned(node, start))
+    }
+    
+    /// Parse the argument list of a call to `name`
+    fn parse_call(&mut self, name: String) -> ParseResult<Node> {
+        self.expect(TokenType::LeftParen)?;
+        let mut call = Node::new(NodeType::FunctionCall, name);
+        
+        loop {
+            self.skip_newlines();
+            if self.check(&TokenType::RightParen) {
+                break;
+            }
+            call.add_child(self.parse_expression()?);
+            self.skip_newlines();
+            if !self.check(&TokenType::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        
+        self.expect(TokenType::RightParen)?;
+        Ok(call)
+    }
+    
+    /// Give `node` the span from the token at `start` to the last consumed token, unless it has one
+    fn spanned(&self, mut node: Node, start: usize) -> Node {
+        if node.location.is_none() && self.position > start {
+            if let (Some(first), Some(last)) = (self.tokens.get(start), self.tokens.get(self.position - 1)) {
+                let (end_line, end_column) = last.location.end().unwrap_or((last.location.line, last.location.column));
+                node.set_location(Location::new(first.location.line, first.location.column).with_end(end_line, end_column));
+            }
+        }
+        node
+    }
+    
+    /// Turn a recoverable error on the previously consumed token into an error node
+    fn recover_from(&self, error: ParseError) -> Node {
+        let recovered_text = self.position.checked_sub(1)
+            .and_then(|index| self.tokens.get(index))
+            .map(|token| token.value.clone())
+            .unwrap_or_default();
+        let end_column = error.location.column + recovered_text.chars().count();
+        let span = error.location.clone().with_end(error.location.line, end_column);
+        Node::from_parse_error(error, span, recovered_text)
     }
 }
 
 impl Parser for BasicParser {
     fn parse(&mut self, source: &str) -> ParseResult<AST> {
-        // First, tokenize the source
+        // First, tokenize the source; comments never reach the grammar, so they cannot split an expression
         self.tokens = self.lexer.tokenize(source)?;
+        self.tokens.retain(|token| token.token_type != TokenType::Comment);
         self.position = 0;
         
         // Create a new AST
         let mut ast = AST::new();
         ast.set_source_language("unknown".to_string());
         
-        // For now, just parse the first expression we find
-        if !self.is_eof() {
-            let expression = self.parse_expression()?;
-            ast.add_root(expression);
+        // Each top-level statement becomes a root
+        for statement in self.parse_statements(false)? {
+            ast.add_root(statement);
         }
         
         Ok(ast)
@@ -682,6 +1447,147 @@ mod tests {
         assert_eq!(tokens[2].value, "3");
     }
     
+    #[test]
+    fn test_two_character_operators() {
+        use TokenType::*;
+        let token_types = |source: &str| -> Vec<TokenType> {
+            BasicLexer::new().tokenize(source).unwrap().into_iter().map(|token| token.token_type).collect()
+        };
+        
+        assert_eq!(token_types("a == b"), vec![Identifier, Equal, Identifier, EOF]);
+        assert_eq!(token_types("x <= 1"), vec![Identifier, LessEqual, Integer, EOF]);
+        assert_eq!(token_types("p && q"), vec![Identifier, And, Identifier, EOF]);
+        assert_eq!(token_types("a != b || c >= d"), vec![Identifier, NotEqual, Identifier, Or, Identifier, GreaterEqual, Identifier, EOF]);
+        assert_eq!(token_types("x = !y < z > w"), vec![Identifier, Assign, Not, Identifier, LessThan, Identifier, GreaterThan, Identifier, EOF]);
+        assert_eq!(token_types("a==b"), vec![Identifier, Equal, Identifier, EOF]);
+        
+        let tokens = BasicLexer::new().tokenize("a == b").unwrap();
+        assert_eq!(tokens[1].value, "==");
+        assert_eq!(tokens[1].location, Location::new(1, 3).with_end(1, 5));
+        assert!(BasicLexer::new().tokenize("a & b").is_err());
+    }
+    
+    #[test]
+    fn test_block_comments() {
+        use TokenType::*;
+        let mut lexer = BasicLexer::new();
+        let tokens = lexer.tokenize("a /* one\ntwo */ b / c").unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| &token.token_type).collect();
+        assert_eq!(token_types, vec![&Identifier, &Comment, &Identifier, &Divide, &Identifier, &EOF]);
+        assert_eq!(tokens[1].value, " one\ntwo ");
+        assert_eq!(tokens[1].location, Location::new(1, 3).with_end(2, 7));
+        assert_eq!(tokens[2].location, Location::new(2, 8).with_end(2, 9));
+        
+        // Comments do not nest, so the first `*/` closes the comment
+        let tokens = lexer.tokenize("/* a /* b */ c */").unwrap();
+        let token_types: Vec<&TokenType> = tokens.iter().map(|token| &token.token_type).collect();
+        assert_eq!(token_types, vec![&Comment, &Identifier, &Multiply, &Divide, &EOF]);
+        assert_eq!(tokens[0].value, " a /* b ");
+        assert_eq!(lexer.tokenize("x/**/y").unwrap()[1].value, "");
+        
+        for source in ["x\n  /* open", "/*", "/* almost *"] {
+            let error = lexer.tokenize(source).unwrap_err();
+            assert_eq!(error.severity, ErrorSeverity::Fatal);
+            assert!(error.message.contains("Unterminated"));
+        }
+        assert_eq!(lexer.tokenize("x\n  /* open").unwrap_err().location, Location::new(2, 3));
+    }
+    
+    #[test]
+    fn test_preserve_whitespace() {
+        let source = "let  x\t= 1\r\n    y ";
+        
+        let mut lexer = BasicLexer::new();
+        let tokens = lexer.tokenize(source).unwrap();
+        assert!(tokens.iter().all(|token| token.token_type != TokenType::Whitespace));
+        
+        lexer.set_preserve_whitespace(true);
+        let tokens = lexer.tokenize(source).unwrap();
+        let whitespace: Vec<&str> = tokens.iter()
+            .filter(|token| token.token_type == TokenType::Whitespace)
+            .map(|token| token.value.as_str())
+            .collect();
+        assert_eq!(whitespace, vec!["  ", "\t", " ", "\r", "    ", " "]);
+        let rebuilt: String = tokens.iter().map(|token| token.value.as_str()).collect();
+        assert_eq!(rebuilt, source);
+    }
+    
+    #[test]
+    fn test_string_rules() {
+        let mut lexer = BasicLexer::with_string_rules(StringRules::rust());
+        let tokens = lexer.tokenize(r###"r#"a\n"b"#"###).unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::String);
+        assert_eq!(tokens[0].value, r#"a\n"b"#);
+        
+        let mut lexer = BasicLexer::with_string_rules(StringRules::python());
+        let tokens = lexer.tokenize("\"\"\"say \"hi\" now\"\"\"").unwrap();
+        assert_eq!(tokens[0].value, "say \"hi\" now");
+        
+        let mut lexer = BasicLexer::with_string_rules(StringRules::javascript());
+        let tokens = lexer.tokenize("`x`").unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::String);
+        assert_eq!(tokens[0].value, "x");
+        
+        // Backticks are not strings in the default dialect
+        let mut lexer = BasicLexer::new();
+        assert!(lexer.tokenize("`x`").is_err());
+    }
+    
+    #[test]
+    fn test_python_string_escapes() {
+        let mut lexer = BasicLexer::with_string_rules(StringRules::python());
+        let cases = [
+            (r#""\x41\u00e9\U0001F600""#, "A\u{e9}\u{1F600}"),
+            (r#""\a\b\f\v""#, "\u{7}\u{8}\u{c}\u{b}"),
+            (r#""\101\0\12x""#, "A\0\nx"),
+            (r#""\N{BULLET}!""#, "\\N{BULLET}!"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(lexer.tokenize(source).unwrap()[0].value, expected, "{}", source);
+        }
+        for source in [r#""\x4""#, r#""\uZZZZ""#, r#""\U00110000""#, r#""\N""#, r#""\q""#] {
+            assert!(lexer.tokenize(source).is_err(), "{}", source);
+        }
+        
+        // Rust keeps `\0` as a single NUL and has no octal escapes
+        let mut lexer = BasicLexer::with_string_rules(StringRules::rust());
+        assert_eq!(lexer.tokenize(r#""\01""#).unwrap()[0].value, "\01");
+    }
+    
+    #[test]
+    fn test_raw_string_escaped_quote() {
+        let mut lexer = BasicLexer::with_string_rules(StringRules::python());
+        let tokens = lexer.tokenize(r#"r"a\"b" x"#).unwrap();
+        assert_eq!(tokens[0].value, r#"a\"b"#);
+        assert_eq!(tokens[1].value, "x");
+        assert_eq!(lexer.tokenize(r#"r"\\""#).unwrap()[0].value, r"\\");
+        
+        // Triple-quoted raw strings close only on the tripled quote
+        let tokens = lexer.tokenize(r#"r"""a"b""" x"#).unwrap();
+        assert_eq!(tokens[0].value, r#"a"b"#);
+        assert_eq!(tokens[1].value, "x");
+        assert_eq!(lexer.tokenize(r#"r'''it's'''"#).unwrap()[0].value, "it's");
+        assert!(lexer.tokenize(r#"r"""a"b"""#).is_err());
+        
+        // A Rust raw string has no escapes at all, so `r"\"` is complete
+        let mut lexer = BasicLexer::with_string_rules(StringRules::rust());
+        assert_eq!(lexer.tokenize(r#"r"\""#).unwrap()[0].value, r"\");
+    }
+    
+    #[test]
+    fn test_unterminated_strings() {
+        let mut lexer = BasicLexer::with_string_rules(StringRules::python());
+        for source in ["x = \"open", "'abc\\'", "\"\"\"never closed\"\"", "r\"raw", "r'x\\'"] {
+            let error = lexer.tokenize(source).unwrap_err();
+            assert_eq!(error.severity, ErrorSeverity::Fatal, "{}", source);
+            assert!(error.message.contains("Unterminated"), "{}", source);
+        }
+        assert_eq!(lexer.tokenize("x = \"open").unwrap_err().location, Location::new(1, 5));
+        
+        let mut lexer = BasicLexer::with_string_rules(StringRules::rust());
+        assert!(lexer.tokenize("r#\"open\"").is_err());
+    }
+    
     #[test]
     fn test_parser_creation() {
         let parser = BasicParser::new();
@@ -698,4 +1604,108 @@ mod tests {
         assert_eq!(ast.roots[0].node_type, NodeType::Literal);
         assert_eq!(ast.roots[0].value, "42");
     }
+    
+    #[test]
+    fn test_statement_parsing() {
+        let source = "let total: int = 1 + 2 * 3; count = total\n\nif total > 5 {\n    print(total, 1)\n}\nelse {\n    return -1\n}\nwhile (count < 10) { count = count + 1 }\nfn add(a, b) { return a + b }";
+        let mut parser = BasicParser::new();
+        let ast = parser.parse(source).unwrap();
+        
+        let types: Vec<&NodeType> = ast.roots.iter().map(|root| &root.node_type).collect();
+        assert_eq!(types, vec![&NodeType::Declaration, &NodeType::Assignment, &NodeType::If, &NodeType::While, &NodeType::Function]);
+        
+        let declaration = &ast.roots[0];
+        assert_eq!(declaration.value, "total");
+        assert_eq!(declaration.metadata["kind"], "let");
+        assert_eq!(declaration.metadata["type"], "int");
+        let sum = &declaration.children[0];
+        assert_eq!(sum.value, "+");
+        assert_eq!(sum.children[1].value, "*");
+        
+        let branch = &ast.roots[2];
+        assert_eq!(branch.child_count(), 3);
+        assert_eq!(branch.children[1].node_type, NodeType::Block);
+        assert_eq!(branch.children[1].children[0].node_type, NodeType::FunctionCall);
+        assert_eq!(branch.children[1].children[0].child_count(), 2);
+        assert_eq!(branch.children[2].children[0].node_type, NodeType::Return);
+        
+        let function = &ast.roots[4];
+        assert_eq!(function.value, "add");
+        assert_eq!(function.child_count(), 3);
+        assert_eq!(function.children[2].node_type, NodeType::Block);
+    }
+    
+    #[test]
+    fn test_node_spans() {
+        let mut parser = BasicParser::new();
+        let ast = parser.parse("let x = 1 + 22\nfoo(\"a\")").unwrap();
+        let span = |node: &Node| {
+            let location = node.location.as_ref().unwrap();
+            (location.line, location.column, location.end().unwrap())
+        };
+        
+        let declaration = &ast.roots[0];
+        assert_eq!(span(declaration), (1, 1, (1, 15)));
+        assert_eq!(span(&declaration.children[0]), (1, 9, (1, 15)));
+        assert_eq!(span(&declaration.children[0].children[1]), (1, 13, (1, 15)));
+        assert_eq!(span(&ast.roots[1]), (2, 1, (2, 9)));
+        assert_eq!(span(&ast.roots[1].children[0]), (2, 5, (2, 8)));
+    }
+    
+    #[test]
+    fn test_statement_error_recovery() {
+        let mut parser = BasicParser::new();
+        let ast = parser.parse("let x = 1 )\n}\nlet y = 3").unwrap();
+        
+        let types: Vec<&NodeType> = ast.roots.iter().map(|root| &root.node_type).collect();
+        assert_eq!(types, vec![&NodeType::Error, &NodeType::Error, &NodeType::Declaration]);
+        assert!(ast.roots[0].error_message().unwrap().contains("after statement"));
+        assert_eq!(ast.roots[2].value, "y");
+    }
+    
+    #[test]
+    fn test_parse_consumes_all_statements() {
+        let mut parser = BasicParser::new();
+        let ast = parser.parse("42 3 foo").unwrap();
+        let values: Vec<&str> = ast.roots.iter().map(|root| root.value.as_str()).collect();
+        assert_eq!(values, vec!["42", "3", "foo"]);
+        
+        let ast = parser.parse("1 2 3 4 5").unwrap();
+        assert_eq!(ast.roots.len(), 5);
+        
+        let ast = parser.parse("x = 1 // trailing note\n\n  y  // another").unwrap();
+        assert_eq!(ast.roots.len(), 2);
+        assert!(ast.roots.iter().all(|root| root.node_type != NodeType::Error));
+        assert_eq!(parser.parse("8 / 2").unwrap().roots[0].node_type, NodeType::BinaryOp);
+    }
+    
+    #[test]
+    fn test_comments_inside_expressions() {
+        let mut parser = BasicParser::new();
+        let ast = parser.parse("a /* c */ + b").unwrap();
+        assert_eq!(ast.roots.len(), 1);
+        assert_eq!((ast.roots[0].node_type.clone(), ast.roots[0].value.as_str()), (NodeType::BinaryOp, "+"));
+        let operands: Vec<&str> = ast.roots[0].children.iter().map(|child| child.value.as_str()).collect();
+        assert_eq!(operands, vec!["a", "b"]);
+        
+        // A comment spanning lines inside a call is skipped like whitespace
+        let ast = parser.parse("f(1, /* two\n lines */ 2) // done\nlet x = /**/ 3").unwrap();
+        assert_eq!(ast.roots.len(), 2);
+        assert!(ast.roots.iter().all(|root| root.node_type != NodeType::Error));
+        assert_eq!(ast.roots[0].children.len(), 2);
+    }
+    
+    #[test]
+    fn test_error_recovery() {
+        let mut parser = BasicParser::new();
+        let ast = parser.parse("+").unwrap();
+        
+        assert_eq!(ast.roots.len(), 1);
+        assert_eq!(ast.roots[0].node_type, NodeType::Error);
+        assert_eq!(ast.roots[0].value, "+");
+        assert!(ast.roots[0].error_message().unwrap().contains("Unexpected token"));
+        assert_eq!(ast.roots[0].error_severity(), Some("Error"));
+        assert_eq!(ast.roots[0].error_kind(), Some("UnexpectedToken"));
+        assert_eq!(ast.roots[0].error_span().and_then(Location::end), Some((1, 2)));
+    }
 }
@@ -2,6 +2,11 @@
 //! 
 //! This module provides test types and utilities for integration tests
 
+use crate::gamma_ast::GammaAST;
+use crate::nexus_compression_engine::{compress_source, AstSerializer, CompressionConfig, JsonAstSerializer, MetadataMode};
+use crate::parser::{BasicParser, Parser, StringRules};
+use std::io::{self, Write};
+
 /// Test result with timing information
 #[derive(Debug, Clone)]
 pub struct TestResult {
@@ -11,10 +16,78 @@ pub struct TestResult {
     pub error_message: Option<String>,
 }
 
+impl TestResult {
+    /// A passing result; `TestSuite` fills in the duration
+    pub fn pass(test_name: impl Into<String>) -> Self {
+        Self {
+            test_name: test_name.into(),
+            passed: true,
+            duration_ms: 0,
+            error_message: None,
+        }
+    }
+    
+    /// A failing result carrying the reason
+    pub fn fail(test_name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error_message: Some(message.into()),
+            passed: false,
+            ..Self::pass(test_name)
+        }
+    }
+    
+    /// Pass on `Ok`, fail with the error message on `Err`
+    pub fn from_outcome(test_name: impl Into<String>, outcome: Result<(), String>) -> Self {
+        match outcome {
+            Ok(()) => Self::pass(test_name),
+            Err(message) => Self::fail(test_name, message),
+        }
+    }
+}
+
+/// Compress a Γ-AST and turn a condition on the `CompressionResult` into a `TestResult`
+///
+/// `assert_compresses!(name, ast, |result| condition)` uses the default config;
+/// pass a `CompressionConfig` before the condition to use another. A
+/// compression error or a false condition gives a failing result.
+#[macro_export]
+macro_rules! assert_compresses {
+    ($name:expr, $ast:expr, |$result:ident| $condition:expr) => {
+        $crate::assert_compresses!($name, $ast, $crate::CompressionConfig::default(), |$result| $condition)
+    };
+    ($name:expr, $ast:expr, $config:expr, |$result:ident| $condition:expr) => {{
+        let mut engine = $crate::NexusCompressionEngine::new($config);
+        match engine.compress_ast_sync(&$ast) {
+            Ok($result) if $condition => $crate::TestResult::pass($name),
+            Ok($result) => $crate::TestResult::fail($name, format!(
+                "`{}` does not hold (ratio {:.2}x)",
+                stringify!($condition),
+                $result.compression_ratio
+            )),
+            Err(error) => $crate::TestResult::fail($name, format!("compression failed: {}", error)),
+        }
+    }};
+}
+
+/// How `TestSuite::run_all` reports results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable lines per test plus a summary
+    #[default]
+    Pretty,
+    /// Only the summary line
+    Quiet,
+    /// A single JSON document once the suite finishes
+    Json,
+    /// Test Anything Protocol, version 13
+    Tap,
+}
+
 /// Test suite runner
 pub struct TestSuite {
     pub name: String,
     pub tests: Vec<Box<dyn Fn() -> TestResult>>,
+    pub output: OutputFormat,
 }
 
 impl TestSuite {
@@ -22,9 +95,16 @@ impl TestSuite {
         Self {
             name: name.to_string(),
             tests: Vec::new(),
+            output: OutputFormat::default(),
         }
     }
     
+    /// Set the report format used by `run_all`
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+    
     pub fn add_test<F>(&mut self, test: F)
     where
         F: Fn() -> TestResult + 'static,
@@ -33,47 +113,269 @@ impl TestSuite {
     }
     
     pub fn run_all(&self) -> Vec<TestResult> {
-        println!("🧪 Running test suite: {}", self.name);
-        println!("{}", "=".repeat(50));
+        self.run_all_to(&mut io::stdout().lock())
+            .expect("failed to write test report to stdout")
+    }
+    
+    /// Run every test, writing the report in the suite's output format to `out`
+    pub fn run_all_to<W: Write>(&self, out: &mut W) -> io::Result<Vec<TestResult>> {
+        match self.output {
+            OutputFormat::Pretty => {
+                writeln!(out, "🧪 Running test suite: {}", self.name)?;
+                writeln!(out, "{}", "=".repeat(50))?;
+            }
+            OutputFormat::Tap => {
+                writeln!(out, "TAP version 13")?;
+                writeln!(out, "1..{}", self.tests.len())?;
+            }
+            OutputFormat::Quiet | OutputFormat::Json => {}
+        }
         
         let mut results = Vec::new();
         let mut passed = 0;
         let mut failed = 0;
         
-        for test in &self.tests {
-            let start = std::time::Instant::now();
-            let result = test();
-            let duration = start.elapsed();
-            
-            let result = TestResult {
-                duration_ms: duration.as_millis() as u64,
-                ..result
-            };
-            
+        for (index, result) in self.tests.iter().map(|test| Self::run_one(test)).enumerate() {
             if result.passed {
-                println!("✅ {} - {}ms", result.test_name, result.duration_ms);
                 passed += 1;
             } else {
-                println!("❌ {} - {}ms - {}", 
-                    result.test_name, 
-                    result.duration_ms,
-                    result.error_message.as_deref().unwrap_or("Unknown error")
-                );
                 failed += 1;
             }
             
+            match self.output {
+                OutputFormat::Pretty if result.passed => {
+                    writeln!(out, "✅ {} - {}ms", result.test_name, result.duration_ms)?;
+                }
+                OutputFormat::Pretty => {
+                    writeln!(out, "❌ {} - {}ms - {}", 
+                        result.test_name, 
+                        result.duration_ms,
+                        result.error_message.as_deref().unwrap_or("Unknown error")
+                    )?;
+                }
+                OutputFormat::Tap => write_tap_result(out, index + 1, &result)?,
+                OutputFormat::Quiet | OutputFormat::Json => {}
+            }
+            
             results.push(result);
         }
         
-        println!("{}", "=".repeat(50));
-        println!("📊 Results: {} passed, {} failed", passed, failed);
+        match self.output {
+            OutputFormat::Pretty => {
+                writeln!(out, "{}", "=".repeat(50))?;
+                writeln!(out, "📊 Results: {} passed, {} failed", passed, failed)?;
+                
+                if failed == 0 {
+                    writeln!(out, "🎉 All tests passed!")?;
+                } else {
+                    writeln!(out, "⚠️  {} tests failed!", failed)?;
+                }
+            }
+            OutputFormat::Quiet => {
+                writeln!(out, "{}: {} passed, {} failed", self.name, passed, failed)?;
+            }
+            OutputFormat::Json => {
+                let tests: Vec<serde_json::Value> = results.iter().map(|result| serde_json::json!({
+                    "name": result.test_name,
+                    "passed": result.passed,
+                    "duration_ms": result.duration_ms,
+                    "error": result.error_message,
+                })).collect();
+                let report = serde_json::json!({
+                    "suite": self.name,
+                    "passed": passed,
+                    "failed": failed,
+                    "tests": tests,
+                });
+                writeln!(out, "{}", report)?;
+            }
+            OutputFormat::Tap => {}
+        }
+        
+        Ok(results)
+    }
+    
+    /// Run a single test and record its wall-clock duration
+    fn run_one(test: &dyn Fn() -> TestResult) -> TestResult {
+        let start = std::time::Instant::now();
+        let result = test();
+        let duration = start.elapsed();
+        
+        TestResult {
+            duration_ms: duration.as_millis() as u64,
+            ..result
+        }
+    }
+}
+
+/// Write one TAP test line, with a YAML diagnostic block for failures
+fn write_tap_result<W: Write>(out: &mut W, number: usize, result: &TestResult) -> io::Result<()> {
+    // `#` starts a TAP directive, so it must be escaped in descriptions
+    let description = result.test_name.replace('\\', "\\\\").replace('#', "\\#");
+    if result.passed {
+        return writeln!(out, "ok {} - {}", number, description);
+    }
+    
+    writeln!(out, "not ok {} - {}", number, description)?;
+    writeln!(out, "  ---")?;
+    writeln!(out, "  message: {}", serde_json::Value::from(result.error_message.as_deref().unwrap_or("Unknown error")))?;
+    writeln!(out, "  duration_ms: {}", result.duration_ms)?;
+    writeln!(out, "  ...")
+}
+
+/// Run source through parse → Γ-AST → compress → serialize → decompress and check the result
+///
+/// `reconstructible` is forced on so lossy passes can be undone. The
+/// decompressed AST must pass `GammaAST::round_trip_mismatch` against the
+/// uncompressed Γ-AST, metadata included when `metadata_mode` preserves it.
+pub fn pipeline_round_trip(language: &str, source: &str, config: CompressionConfig) -> TestResult {
+    let test_name = format!("pipeline_round_trip[{}] {:?}", language, source);
+    let outcome = (|| -> Result<(), String> {
+        let mut parser = BasicParser::with_string_rules(StringRules::for_language(language));
+        let ast = parser.parse(source).map_err(|e| e.to_string())?;
+        let original = GammaAST::from_ast(&ast);
+        
+        let config = CompressionConfig { reconstructible: true, ..config };
+        let compare_metadata = config.metadata_mode == MetadataMode::Preserve;
+        let bytes = compress_source(language, source, config).map_err(|e| e.to_string())?;
+        let compressed = JsonAstSerializer.deserialize(&bytes).map_err(|e| e.to_string())?;
+        let restored = compressed.decompress().map_err(|e| e.to_string())?;
+        
+        original.round_trip_mismatch(&restored, compare_metadata).map_or(Ok(()), Err)
+    })();
+    
+    TestResult::from_outcome(test_name, outcome)
+}
+
+#[cfg(test)]
+mod round_trip_tests {
+    use super::*;
+    use crate::gamma_ast::{GammaNode, GammaNodeType, GammaValue};
+    use crate::nexus_compression_engine::NexusCompressionEngine;
+    
+    #[test]
+    fn test_pipeline_round_trip_suite() {
+        let mut suite = TestSuite::new("parse → compress → decompress");
+        for (language, source) in [
+            ("rust", "42"),
+            ("rust", r##"r#"raw "text""#"##),
+            ("python", "\"\"\"docstring\"\"\""),
+            ("javascript", "`template`"),
+            ("c", "identifier"),
+            ("c", "+"),
+        ] {
+            suite.add_test(move || pipeline_round_trip(language, source, CompressionConfig::default()));
+        }
+        
+        // Repeated statements, so deduplication, value compression and pattern identification all fire
+        let programs = [
+            ("rust", "let total = compute(value)\n".repeat(12)),
+            ("python", "print(\"repeated message text\")\n".repeat(12)),
+            ("javascript", "log(`template literal`, counter)\n".repeat(12)),
+        ];
+        for (language, source) in &programs {
+            let ast = BasicParser::with_string_rules(StringRules::for_language(language)).parse(source).unwrap();
+            let config = CompressionConfig { reconstructible: true, ..Default::default() };
+            let (result, bytes) = NexusCompressionEngine::new(config).compress_to_bytes(&GammaAST::from_ast(&ast)).unwrap();
+            let compressed = JsonAstSerializer.deserialize(&bytes).unwrap();
+            assert!(result.patterns_identified > 0, "{}", language);
+            assert!(compressed.nodes.values().any(|node| matches!(node.value, GammaValue::PatternRef(_))), "{}", language);
+            assert!(compressed.reconstruction.is_some_and(|table| !table.is_empty()), "{}", language);
+        }
+        for (language, source) in programs {
+            suite.add_test(move || pipeline_round_trip(language, &source, CompressionConfig::default()));
+        }
         
-        if failed == 0 {
-            println!("🎉 All tests passed!");
-        } else {
-            println!("⚠️  {} tests failed!", failed);
+        let results = suite.run_all();
+        assert_eq!(results.len(), 9);
+        for result in results {
+            assert!(result.passed, "{}: {:?}", result.test_name, result.error_message);
         }
+    }
+    
+    #[test]
+    fn test_output_formats() {
+        let suite_with = |output| {
+            let mut suite = TestSuite::new("formats").with_output(output);
+            suite.add_test(|| TestResult::pass("good"));
+            suite.add_test(|| TestResult::fail("bad #1", "boom"));
+            suite
+        };
+        let render = |output| {
+            let mut out = Vec::new();
+            let results = suite_with(output).run_all_to(&mut out).unwrap();
+            assert_eq!(results.len(), 2);
+            String::from_utf8(out).unwrap()
+        };
+        
+        assert_eq!(render(OutputFormat::Quiet), "formats: 1 passed, 1 failed\n");
+        
+        let tap = render(OutputFormat::Tap);
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(&lines[..4], &["TAP version 13", "1..2", "ok 1 - good", "not ok 2 - bad \\#1"]);
+        assert!(tap.contains("  message: \"boom\""));
+        
+        let json: serde_json::Value = serde_json::from_str(&render(OutputFormat::Json)).unwrap();
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["tests"][1]["error"], "boom");
+        
+        assert!(render(OutputFormat::Pretty).contains("❌ bad #1"));
+    }
+    
+    #[test]
+    fn test_result_constructors_and_assert_compresses() {
+        let failed = TestResult::fail("t", "why");
+        assert!(!failed.passed);
+        assert_eq!(failed.error_message.as_deref(), Some("why"));
+        assert!(TestResult::from_outcome("t", Ok(())).passed);
+        
+        let ast = GammaAST::from_ast(&BasicParser::new().parse("42").unwrap());
+        assert!(crate::assert_compresses!("keeps nodes", ast, |result| result.nodes_lost == 0).passed);
+        
+        let config = CompressionConfig { max_input_nodes: Some(0), ..Default::default() };
+        let rejected = crate::assert_compresses!("too large", ast, config, |result| result.nodes_lost == 0);
+        assert!(rejected.error_message.unwrap().starts_with("compression failed"));
+        
+        let unmet = crate::assert_compresses!("ratio", ast, |result| result.compression_ratio > 100.0);
+        assert!(unmet.error_message.unwrap().contains("result.compression_ratio > 100.0"));
+    }
+    
+    #[test]
+    fn test_round_trip_mismatch_detects_changes() {
+        let mut parser = BasicParser::new();
+        let original = GammaAST::from_ast(&parser.parse("42").unwrap());
+        assert_eq!(original.round_trip_mismatch(&original, true), None);
+        
+        let mut changed = original.clone();
+        changed.nodes.get_mut(&1).unwrap().value = GammaValue::Direct("43".to_string());
+        assert!(original.round_trip_mismatch(&changed, true).is_some());
+        
+        // A value left encoded is a change, not something to skip
+        let mut encoded = original.clone();
+        encoded.nodes.get_mut(&1).unwrap().value = GammaValue::PatternRef(1);
+        assert!(original.round_trip_mismatch(&encoded, true).is_some());
+        
+        let mut missing_root = original.clone();
+        missing_root.roots.clear();
+        assert!(original.round_trip_mismatch(&missing_root, true).is_some());
+        
+        // Lost or reordered children are not a round trip
+        let mut block: GammaAST = (1..=3)
+            .map(|id| GammaNode::builder(id, GammaNodeType::Literal).direct(id.to_string()).build())
+            .chain(std::iter::once(GammaNode::builder(4, GammaNodeType::Block).children([1, 2, 3]).build()))
+            .collect();
+        block.add_root(4);
+        let mut reordered = block.clone();
+        reordered.nodes.get_mut(&4).unwrap().children.reverse();
+        assert_eq!(block.round_trip_mismatch(&reordered, false), Some("node 4 children changed".to_string()));
+        let mut lost = block.clone();
+        lost.nodes.get_mut(&4).unwrap().children.pop();
+        assert_eq!(block.round_trip_mismatch(&lost, false), Some("node 4 children changed".to_string()));
         
-        results
+        // Metadata only counts when the mode preserves it
+        let mut retagged = original.clone();
+        retagged.nodes.get_mut(&1).unwrap().metadata.insert("k".to_string(), "v".to_string());
+        assert_eq!(original.round_trip_mismatch(&retagged, true), Some("node 1 metadata changed".to_string()));
+        assert_eq!(original.round_trip_mismatch(&retagged, false), None);
     }
 }
@@ -4,15 +4,21 @@
 //! enhanced compression algorithms with neuromorphic pattern recognition,
 //! intelligent resource management, and cryptographic verification.
 
-use crate::gamma_ast::{GammaAST, GammaNode, Pattern, CompressionLevel, CompressionStats, GammaNodeType, GammaValue, CrossFilePattern, MetaPattern};
-use crate::neuromem::{MemoryRegion, AccessPattern, MemorySpike, LearningEngine};
+use crate::gamma_ast::{sort_patterns_by_value, GammaAST, GammaNode, Pattern, CompressionLevel, CompressionStats, DetailedCompressionStats, PassKind, GammaNodeType, GammaValue, CrossFilePattern, MetaPattern, SignatureHasher, IdGenerator, DuplicateIdError, ReconstructionTable, space_saving_percent};
+use crate::neuromem::{learning_event_channel, MemoryRegion, MemoryType, AccessPattern, MemorySpike, LearningEngine, LearningEventReceiver, LearningEventSender, GpuBenefit};
 use crate::ai_scheduler::{AIProcess, GPUMemoryManager, SchedulerError};
+use crate::nexus_compression_engine::{sorted_groups, warn_on_metadata_bloat, AstSerializer, CompressionConfig};
+use crate::GPUConfig;
+use crate::gpu_acceleration::GPUError;
 #[cfg(feature = "gpu")]
-use crate::{GPUAccelerationEngine, GPUConfig, UniversalPattern, GPUPatternResult};
+use crate::{GPUAccelerationEngine, GPUPatternResult};
+#[cfg(feature = "gpu")]
+use crate::gpu_acceleration::UniversalPattern;
 use std::collections::{HashMap, VecDeque};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use tokio::sync::RwLock;
 
@@ -21,33 +27,301 @@ use tokio::sync::RwLock;
 pub struct EnhancedCompressionConfig {
     /// Enable neuromorphic pattern recognition
     pub enable_neuromorphic: bool,
+    /// ASTs with fewer nodes skip neuromorphic setup and use basic analysis
+    pub neuromorphic_min_nodes: usize,
     /// Enable AI-powered resource optimization
     pub enable_ai_scheduling: bool,
-    /// Enable cryptographic verification
+    /// Attach a SHA-256 `verification_hash` of the compressed AST to each result
     pub enable_crypto_verification: bool,
     /// Target compression ratio (realistic: 2-16x)
     pub target_ratio: f64,
     /// Maximum memory usage for compression
     pub max_memory_mb: u64,
-    /// GPU acceleration threshold (pattern size)
+    /// GPU acceleration threshold (pattern size), used until `calibrate` measures one
     pub gpu_threshold: usize,
+    /// Run `calibrate` on the first compression instead of trusting `gpu_threshold`; off by default
+    /// because the benchmark slows that call, so callers normally invoke `calibrate` themselves
+    pub auto_calibrate_gpu: bool,
     /// Learning rate for pattern adaptation
     pub learning_rate: f32,
     /// Enable pattern evolution and learning
     pub pattern_evolution: bool,
+    /// Catch panics from GPU calls and fall back to CPU (needs `panic = "unwind"`)
+    pub catch_gpu_panics: bool,
+    /// Thresholds used when clustering similar patterns
+    pub similarity: SimilarityConfig,
+    /// Number of GPUs to account for (`None` = detect)
+    pub gpu_count: Option<u32>,
+    /// Memory per GPU in bytes (`None` = detect)
+    pub gpu_memory_per_device: Option<u64>,
+    /// Keyword classes used to group string values during value compression
+    pub string_patterns: StringPatternRules,
+    /// Largest AST `try_compress` accepts
+    pub max_input_nodes: usize,
+    /// Run `test_reconstruction_fidelity` after every compression and fail on a mismatch
+    pub verify_roundtrip: bool,
+    /// Keep at most this many structural or cross-file patterns, the most valuable first
+    pub max_patterns: usize,
+    /// Forbid every pass from clearing or replacing node children; `false` trades structure for ratio
+    pub preserve_children: bool,
+    /// Nodes restored to their original form after all passes, so they stay `Direct` and keep their children
+    pub pinned_nodes: HashSet<u64>,
+    /// Break savings down by pass and node type in `CompressionResult::detailed_stats` (snapshots the AST per pass)
+    pub detailed_stats: bool,
+    /// Log a warning when metadata exceeds this fraction of the compressed bytes (`None` = never)
+    pub metadata_warning_fraction: Option<f64>,
+    /// Fewest occurrences a signature needs before it becomes a pattern
+    pub min_pattern_frequency: u32,
+    /// Fewest nodes a pattern must cover
+    pub min_pattern_size: usize,
+    /// Store a `ReconstructionTable` on the compressed AST so `GammaAST::decompress` can undo the lossy passes
+    pub reconstructible: bool,
+}
+
+/// A named class of string values recognised by any of its keywords
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StringPatternClass {
+    pub name: String,
+    pub keywords: Vec<String>,
+}
+
+/// Ordered keyword classes for string-pattern grouping
+///
+/// Classes are checked in order and the first class with a keyword contained in
+/// the value wins; values matching no class fall back to length buckets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StringPatternRules {
+    pub classes: Vec<StringPatternClass>,
+}
+
+impl StringPatternRules {
+    /// No keyword classes, grouping purely by length
+    pub fn empty() -> Self {
+        Self { classes: Vec::new() }
+    }
+    
+    /// Append a keyword class, checked after the existing ones
+    pub fn with_class(mut self, name: &str, keywords: &[&str]) -> Self {
+        self.classes.push(StringPatternClass {
+            name: name.to_string(),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        });
+        self
+    }
+    
+    /// Classify a string value by keyword, then by length
+    pub fn classify(&self, value: &str) -> String {
+        for class in &self.classes {
+            if class.keywords.iter().any(|keyword| value.contains(keyword.as_str())) {
+                return class.name.clone();
+            }
+        }
+        
+        match value.len() {
+            len if len > 25 => "very_long_string",
+            len if len > 15 => "long_string",
+            len if len > 8 => "medium_string",
+            len if len > 3 => "short_string",
+            _ => "tiny_string",
+        }.to_string()
+    }
+}
+
+impl Default for StringPatternRules {
+    /// Common keywords across mainstream programming languages
+    fn default() -> Self {
+        Self::empty()
+            .with_class("function_declaration", &["function", "func", "def", "fn"])
+            .with_class("type_declaration", &["class", "struct", "trait", "interface"])
+            .with_class("control_flow", &["if", "else", "while", "for", "loop"])
+            .with_class("control_statement", &["return", "break", "continue", "yield"])
+            .with_class("access_modifier", &["public", "private", "protected", "internal"])
+            .with_class("modifier", &["static", "final", "const", "readonly"])
+            .with_class("exception_handling", &["try", "catch", "finally", "throw"])
+            .with_class("module_import", &["import", "export", "require", "include"])
+            .with_class("operator", &["new", "delete", "typeof", "instanceof"])
+            .with_class("async_pattern", &["async", "await", "promise", "future"])
+            .with_class("property_access", &["get", "set", "property", "field"])
+    }
+}
+
+/// Tolerances for deciding whether two patterns belong in the same cluster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarityConfig {
+    /// Allowed size difference as a fraction of the larger pattern (0.3 = 30%)
+    pub size_tolerance: f64,
+    /// Allowed absolute difference in node count
+    pub node_count_tolerance: usize,
+    /// Minimum content similarity (0.0-1.0) required to cluster
+    pub content_threshold: f64,
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        Self {
+            size_tolerance: 0.3,
+            node_count_tolerance: 3,
+            content_threshold: 0.6,
+        }
+    }
+}
+
+/// Pluggable similarity function used by pattern clustering
+pub trait PatternSimilarity: Send + Sync {
+    fn are_similar(&self, pattern1: &Pattern, pattern2: &Pattern, config: &SimilarityConfig) -> bool;
+}
+
+/// Size, node-count and content similarity check
+#[derive(Debug, Clone, Default)]
+pub struct DefaultPatternSimilarity;
+
+impl PatternSimilarity for DefaultPatternSimilarity {
+    fn are_similar(&self, pattern1: &Pattern, pattern2: &Pattern, config: &SimilarityConfig) -> bool {
+        let size_diff = (pattern1.size as i64 - pattern2.size as i64).unsigned_abs();
+        let size_threshold = (pattern1.size.max(pattern2.size) as f64 * config.size_tolerance) as u64;
+        
+        let node_count_diff = (pattern1.nodes.len() as i64 - pattern2.nodes.len() as i64).unsigned_abs();
+        
+        size_diff <= size_threshold
+            && node_count_diff <= config.node_count_tolerance as u64
+            && content_similarity(pattern1, pattern2) >= config.content_threshold
+    }
+}
+
+/// Run a pass over `ast`, charging its size change to `pass` when detailed stats are collected
+fn measured<F>(
+    ast: &mut GammaAST,
+    pass: PassKind,
+    detailed_stats: &mut Option<DetailedCompressionStats>,
+    run: F,
+) -> Result<(), CompressionError>
+where
+    F: FnOnce(&mut GammaAST) -> Result<(), CompressionError>,
+{
+    let Some(stats) = detailed_stats else {
+        return run(ast);
+    };
+    
+    let before = ast.nodes.clone();
+    run(ast)?;
+    stats.record_pass(pass, &before, &ast.nodes);
+    Ok(())
+}
+
+/// Fraction of nodes sharing both type and direct value between two patterns
+fn content_similarity(pattern1: &Pattern, pattern2: &Pattern) -> f64 {
+    if pattern1.nodes.is_empty() || pattern2.nodes.is_empty() {
+        return 0.0;
+    }
+    
+    let mut common_nodes = 0;
+    let total_nodes = pattern1.nodes.len().max(pattern2.nodes.len());
+    
+    // Compare node types and values
+    for node1 in &pattern1.nodes {
+        for node2 in &pattern2.nodes {
+            if node1.node_type == node2.node_type {
+                if let (crate::gamma_ast::GammaValue::Direct(val1), crate::gamma_ast::GammaValue::Direct(val2)) = (&node1.value, &node2.value) {
+                    if val1 == val2 {
+                        common_nodes += 1;
+                    }
+                }
+            }
+        }
+    }
+    
+    common_nodes as f64 / total_nodes as f64
+}
+
+/// Decides whether a node is code, data or another memory type for strategy selection
+pub trait MemoryTypeClassifier: Send + Sync {
+    fn classify(&self, node: &GammaNode, ast: &GammaAST) -> MemoryType;
+}
+
+/// Literals and literal-only containers (generated tables) are data, everything else is code
+#[derive(Debug, Clone, Default)]
+pub struct DefaultMemoryTypeClassifier;
+
+impl MemoryTypeClassifier for DefaultMemoryTypeClassifier {
+    fn classify(&self, node: &GammaNode, ast: &GammaAST) -> MemoryType {
+        let is_literal = |id: &u64| {
+            ast.nodes.get(id).is_some_and(|child| child.node_type == GammaNodeType::Literal)
+        };
+        
+        if node.node_type == GammaNodeType::Literal
+            || (!node.children.is_empty() && node.children.iter().all(is_literal))
+        {
+            MemoryType::Data
+        } else {
+            MemoryType::Code
+        }
+    }
 }
 
 impl Default for EnhancedCompressionConfig {
     fn default() -> Self {
         Self {
             enable_neuromorphic: true,
+            neuromorphic_min_nodes: 256, // Bookkeeping outweighs gains on small files
             enable_ai_scheduling: true,
             enable_crypto_verification: true,
             target_ratio: 8.0, // Realistic 8x compression target
             max_memory_mb: 1024, // 1GB memory limit
             gpu_threshold: 1000, // Use GPU for patterns > 1000 nodes
+            auto_calibrate_gpu: false,
             learning_rate: 0.1,
             pattern_evolution: true,
+            catch_gpu_panics: false,
+            similarity: SimilarityConfig::default(),
+            gpu_count: None,
+            gpu_memory_per_device: None,
+            string_patterns: StringPatternRules::default(),
+            max_input_nodes: 5_000_000,
+            verify_roundtrip: cfg!(debug_assertions), // Costly, so only on by default in debug builds
+            max_patterns: 10_000,
+            preserve_children: true,
+            pinned_nodes: HashSet::new(),
+            detailed_stats: false,
+            metadata_warning_fraction: Some(0.5),
+            min_pattern_frequency: 2, // A one-off node repeats nothing
+            min_pattern_size: 1,
+            reconstructible: false,
+        }
+    }
+}
+
+/// Carries over the settings both engines share; the rest keep their defaults
+impl From<CompressionConfig> for EnhancedCompressionConfig {
+    fn from(config: CompressionConfig) -> Self {
+        Self {
+            target_ratio: config.target_ratio,
+            max_memory_mb: config.max_memory_mb,
+            detailed_stats: config.detailed_stats,
+            max_patterns: config.max_patterns,
+            preserve_children: config.preserve_children,
+            pinned_nodes: config.pinned_nodes,
+            max_input_nodes: config.max_input_nodes.unwrap_or(usize::MAX),
+            metadata_warning_fraction: config.metadata_warning_fraction,
+            reconstructible: config.reconstructible,
+            ..Self::default()
+        }
+    }
+}
+
+/// Carries over the settings both engines share; the rest keep their defaults
+impl From<EnhancedCompressionConfig> for CompressionConfig {
+    fn from(config: EnhancedCompressionConfig) -> Self {
+        Self {
+            target_ratio: config.target_ratio,
+            max_memory_mb: config.max_memory_mb,
+            detailed_stats: config.detailed_stats,
+            max_patterns: config.max_patterns,
+            preserve_children: config.preserve_children,
+            pinned_nodes: config.pinned_nodes,
+            max_input_nodes: (config.max_input_nodes != usize::MAX).then_some(config.max_input_nodes),
+            metadata_warning_fraction: config.metadata_warning_fraction,
+            reconstructible: config.reconstructible,
+            ..Self::default()
         }
     }
 }
@@ -57,11 +331,25 @@ pub struct EnhancedCompressionEngine {
     pub config: EnhancedCompressionConfig,
     neuromorphic_memory: Arc<Mutex<HashMap<u64, MemoryRegion>>>,
     learning_engine: Arc<Mutex<LearningEngine>>,
+    /// Lets async code record outcomes without waiting on `learning_engine`
+    learning_events: LearningEventSender,
+    learning_inbox: LearningEventReceiver,
     gpu_manager: Arc<Mutex<GPUMemoryManager>>,
     pattern_evolution: Arc<Mutex<PatternEvolution>>,
     compression_history: VecDeque<CompressionResult>,
+    similarity_metric: Box<dyn PatternSimilarity>,
+    value_hash_table: Arc<Mutex<HashMap<u64, String>>>,
+    gpu_config: GPUConfig,
+    memory_classifier: Box<dyn MemoryTypeClassifier>,
+    id_generator: IdGenerator,
+    serializer: Option<Box<dyn AstSerializer>>,
+    /// GPU routing threshold measured by `calibrate`, overriding `gpu_threshold`
+    calibrated_gpu_threshold: Option<usize>,
 }
 
+/// Pattern sizes, in nodes, timed on both paths by `EnhancedCompressionEngine::calibrate`
+const CALIBRATION_SIZES: [usize; 7] = [100, 500, 1_000, 2_500, 5_000, 10_000, 25_000];
+
 /// Pattern evolution tracking for adaptive compression
 #[derive(Debug, Clone)]
 pub struct PatternEvolution {
@@ -110,6 +398,27 @@ pub struct CompressionResult {
     pub verification_hash: Option<String>,
     pub processing_time: Duration,
     pub memory_usage: u64,
+    /// Bytes saved per pass and per node type, present when `detailed_stats` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detailed_stats: Option<DetailedCompressionStats>,
+    /// Nodes in the input AST
+    #[serde(default)]
+    pub nodes_original: usize,
+    /// Nodes in the compressed AST, including any synthetic pattern nodes
+    #[serde(default)]
+    pub nodes_compressed: usize,
+    /// Input nodes whose IDs are missing from the compressed AST (0 when nothing was lost)
+    #[serde(default)]
+    pub nodes_lost: usize,
+}
+
+/// Ratio and wall time of one AST compressed with each pattern analysis, from `compare_analysis_modes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisComparison {
+    pub neuro_ratio: f64,
+    pub neuro_time: Duration,
+    pub basic_ratio: f64,
+    pub basic_time: Duration,
 }
 
 /// Large-scale test case for real-world codebases
@@ -134,6 +443,8 @@ pub struct TestCaseResult {
     pub expected_compression: f64,
     pub success: bool,
     pub neuromorphic_insights: Vec<String>,
+    /// First difference after the round trip, or why it could not be decoded, from a verify-only run
+    pub mismatch: Option<String>,
 }
 
 /// Overall test statistics
@@ -194,46 +505,194 @@ impl LargeScaleTestResults {
 impl EnhancedCompressionEngine {
     /// Create a new enhanced compression engine
     pub fn new(config: EnhancedCompressionConfig) -> Self {
-        let gpu_manager = GPUMemoryManager::new(2, 8 * 1024 * 1024 * 1024); // 2 GPUs, 8GB each
+        let (gpu_count, gpu_memory) = Self::resolve_gpu_topology(&config);
+        let gpu_manager = GPUMemoryManager::new(gpu_count, gpu_memory);
+        let (learning_events, learning_inbox) = learning_event_channel();
         
         Self {
             config,
             neuromorphic_memory: Arc::new(Mutex::new(HashMap::new())),
             learning_engine: Arc::new(Mutex::new(LearningEngine::new())),
+            learning_events,
+            learning_inbox,
             gpu_manager: Arc::new(Mutex::new(gpu_manager)),
             pattern_evolution: Arc::new(Mutex::new(PatternEvolution::new())),
             compression_history: VecDeque::new(),
+            similarity_metric: Box::new(DefaultPatternSimilarity),
+            value_hash_table: Arc::new(Mutex::new(HashMap::new())),
+            gpu_config: GPUConfig::default(),
+            memory_classifier: Box::new(DefaultMemoryTypeClassifier),
+            id_generator: IdGenerator::default(),
+            serializer: None,
+            calibrated_gpu_threshold: None,
+        }
+    }
+    
+    /// Replace the classifier that splits nodes into code and data
+    pub fn set_memory_classifier(&mut self, classifier: Box<dyn MemoryTypeClassifier>) {
+        self.memory_classifier = classifier;
+    }
+    
+    /// Measure compressed sizes in your own wire format instead of the built-in compact encoding
+    pub fn set_serializer(&mut self, serializer: Box<dyn AstSerializer>) {
+        self.serializer = Some(serializer);
+    }
+    
+    /// Classify every node of the AST by memory type
+    pub fn classify_memory_types(&self, ast: &GammaAST) -> HashMap<u64, MemoryType> {
+        ast.nodes.iter()
+            .map(|(id, node)| (*id, self.memory_classifier.classify(node, ast)))
+            .collect()
+    }
+    
+    /// Replace the GPU configuration, including the allocation retry policy
+    pub fn set_gpu_config(&mut self, gpu_config: GPUConfig) -> Result<(), GPUError> {
+        gpu_config.allocation_retry.validate()?;
+        self.gpu_config = gpu_config;
+        Ok(())
+    }
+    
+    /// Work out GPU count and per-device memory from the config, detecting what is unset
+    ///
+    /// Detection uses the devices reported by `GPUAccelerationEngine`; when no real
+    /// GPU is available the legacy 2 x 8GB accounting is used.
+    fn resolve_gpu_topology(config: &EnhancedCompressionConfig) -> (u32, u64) {
+        const FALLBACK_GPU_COUNT: u32 = 2;
+        const FALLBACK_GPU_MEMORY: u64 = 8 * 1024 * 1024 * 1024;
+        
+        if let (Some(count), Some(memory)) = (config.gpu_count, config.gpu_memory_per_device) {
+            return (count, memory);
         }
+        
+        let detected = crate::GPUAccelerationEngine::new(crate::GPUConfig::default())
+            .ok()
+            .filter(|engine| engine.is_available())
+            .map(|engine| engine.get_devices())
+            .filter(|devices| !devices.is_empty())
+            .map(|devices| {
+                let memory = devices.iter().map(|d| d.memory_total).min().unwrap_or(FALLBACK_GPU_MEMORY);
+                (devices.len() as u32, memory)
+            });
+        let (detected_count, detected_memory) = detected.unwrap_or((FALLBACK_GPU_COUNT, FALLBACK_GPU_MEMORY));
+        
+        (
+            config.gpu_count.unwrap_or(detected_count),
+            config.gpu_memory_per_device.unwrap_or(detected_memory),
+        )
+    }
+    
+    /// Replace the similarity function used when clustering patterns
+    pub fn set_similarity_metric(&mut self, metric: Box<dyn PatternSimilarity>) {
+        self.similarity_metric = metric;
+    }
+    
+    /// Compress untrusted input, returning an error instead of panicking
+    ///
+    /// The AST is validated first: size limit, node IDs matching their keys,
+    /// roots and children that exist, and no cycles through `children`.
+    /// Lock poisoning inside the engine is recovered rather than propagated.
+    pub async fn try_compress(&mut self, ast: &GammaAST) -> Result<CompressionResult, CompressionError> {
+        self.validate_input(ast)?;
+        self.compress_ast(ast).await
+    }
+    
+    /// Check that an AST is safe for the compression passes to walk
+    pub fn validate_input(&self, ast: &GammaAST) -> Result<(), CompressionError> {
+        let invalid = |message: String| Err(CompressionError::InvalidInput(message));
+        
+        if ast.nodes.len() > self.config.max_input_nodes {
+            return invalid(format!("{} nodes exceeds the limit of {}", ast.nodes.len(), self.config.max_input_nodes));
+        }
+        for (id, node) in &ast.nodes {
+            if node.id != *id {
+                return invalid(format!("node stored under {} has id {}", id, node.id));
+            }
+            if let Some(child) = node.children.iter().find(|child| !ast.nodes.contains_key(child)) {
+                return invalid(format!("node {} has missing child {}", id, child));
+            }
+        }
+        if let Some(root) = ast.roots.iter().find(|root| !ast.nodes.contains_key(root)) {
+            return invalid(format!("missing root {}", root));
+        }
+        
+        // Iterative three-colour DFS so deep or cyclic input cannot overflow the stack
+        let mut finished: HashSet<u64> = HashSet::new();
+        let mut on_path: HashSet<u64> = HashSet::new();
+        for &start in ast.nodes.keys() {
+            if finished.contains(&start) {
+                continue;
+            }
+            let mut stack = vec![(start, 0usize)];
+            on_path.insert(start);
+            while let Some((id, next_child)) = stack.pop() {
+                let children = &ast.nodes[&id].children;
+                if let Some(&child) = children.get(next_child) {
+                    stack.push((id, next_child + 1));
+                    if on_path.contains(&child) {
+                        return invalid(format!("cycle through node {}", child));
+                    }
+                    if !finished.contains(&child) {
+                        on_path.insert(child);
+                        stack.push((child, 0));
+                    }
+                } else {
+                    on_path.remove(&id);
+                    finished.insert(id);
+                }
+            }
+        }
+        
+        Ok(())
     }
     
     /// Perform enhanced compression with AI optimization
     pub async fn compress_ast(&mut self, ast: &GammaAST) -> Result<CompressionResult, CompressionError> {
+        self.compress_with_output(ast).await.map(|(result, _)| result)
+    }
+    
+    /// `compress_ast`, also handing back the compressed AST
+    async fn compress_with_output(&mut self, ast: &GammaAST) -> Result<(CompressionResult, GammaAST), CompressionError> {
         let start_time = Instant::now();
         let original_size = self.calculate_ast_size(ast);
         
+        if self.config.auto_calibrate_gpu && self.calibrated_gpu_threshold.is_none() {
+            self.calibrate();
+        }
+        
+        // Hash collisions and pattern IDs are tracked per compression run
+        self.value_hash_table.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        self.id_generator = IdGenerator::seeded_from(ast);
+        
         // Initialize AI process for compression
         let ai_process = self.create_compression_process(ast)?;
         
-        // Perform neuromorphic pattern analysis
-        let patterns = if self.config.enable_neuromorphic {
+        // Perform neuromorphic pattern analysis, skipping its setup on small inputs
+        let patterns = if self.config.enable_neuromorphic && ast.nodes.len() >= self.config.neuromorphic_min_nodes {
             self.analyze_patterns_neuromorphic(ast).await?
         } else {
             self.analyze_patterns_basic(ast)?
         };
         
         // Apply AI-optimized compression
-        let compressed_ast = self.apply_ai_compression(ast, &patterns, &ai_process).await?;
+        let mut detailed_stats = self.config.detailed_stats.then(DetailedCompressionStats::default);
+        let mut compressed_ast = self.apply_ai_compression(ast, &patterns, &ai_process, &mut detailed_stats).await?;
+        self.restore_pinned_nodes(ast, &mut compressed_ast);
+        if self.config.reconstructible {
+            compressed_ast.reconstruction = Some(ReconstructionTable::between(ast, &compressed_ast));
+        }
         
         // CRITICAL: Use proper byte-level compression metrics
         let (original_bytes, compressed_bytes, compression_ratio) = self.calculate_compression_metrics(ast, &compressed_ast);
         
+        // Bookkeeping metadata from the passes can outweigh what they saved
+        warn_on_metadata_bloat(self.config.metadata_warning_fraction, compressed_ast.total_metadata_bytes(), compressed_bytes);
+        
         // CRITICAL: Run reconstruction fidelity test
-        println!("\n🔬 Running Critical Diagnostic Tests...");
-        let fidelity_result = self.test_reconstruction_fidelity(ast, &compressed_ast)?;
-        if !fidelity_result {
-            eprintln!("🚨 CRITICAL: Reconstruction fidelity test FAILED!");
-            eprintln!("   This indicates the compression is NOT lossless!");
-            eprintln!("   Compression results are INVALID!");
+        if self.config.verify_roundtrip {
+            println!("\n🔬 Running Critical Diagnostic Tests...");
+            if !self.test_reconstruction_fidelity(ast, &compressed_ast)? {
+                return Err(CompressionError::ReconstructionFailed);
+            }
         }
         
         // Generate cryptographic verification if enabled
@@ -254,6 +713,10 @@ impl EnhancedCompressionEngine {
             verification_hash,
             processing_time: start_time.elapsed(),
             memory_usage: self.get_memory_usage(),
+            detailed_stats,
+            nodes_original: ast.nodes.len(),
+            nodes_compressed: compressed_ast.nodes.len(),
+            nodes_lost: ast.nodes.keys().filter(|id| !compressed_ast.nodes.contains_key(id)).count(),
         };
         
         self.compression_history.push_back(result.clone());
@@ -262,15 +725,15 @@ impl EnhancedCompressionEngine {
         }
         
         // Update learning engine
-        self.update_learning_engine(&result).await;
+        self.update_learning_engine(&result);
         
-        Ok(result)
+        Ok((result, compressed_ast))
     }
     
     /// Analyze patterns using neuromorphic memory system
     async fn analyze_patterns_neuromorphic(&self, ast: &GammaAST) -> Result<Vec<Pattern>, CompressionError> {
         let mut patterns = Vec::new();
-        let mut memory = self.neuromorphic_memory.lock().unwrap();
+        let mut memory = self.neuromorphic_memory.lock().unwrap_or_else(PoisonError::into_inner);
         
         // Analyze temporal access patterns
         for (node_id, node) in &ast.nodes {
@@ -280,7 +743,7 @@ impl EnhancedCompressionEngine {
                 synaptic_strength: 1.0,
                 access_frequency: 1,
                 last_access: crate::neuromem::now_ms(),
-                memory_type: crate::neuromem::MemoryType::Code,
+                memory_type: self.memory_classifier.classify(node, ast),
                 pathway: vec![0.0; 10],
                 plasticity: 0.1,
             };
@@ -297,6 +760,7 @@ impl EnhancedCompressionEngine {
         // Merge and optimize patterns
         patterns.extend(spatial_patterns);
         patterns.extend(temporal_patterns);
+        patterns.retain(|pattern| self.meets_pattern_minimums(pattern));
         
         // Apply learning-based pattern refinement
         self.refine_patterns_with_learning(&mut patterns).await;
@@ -306,98 +770,125 @@ impl EnhancedCompressionEngine {
     
     /// Analyze patterns using basic algorithms (fallback)
     fn analyze_patterns_basic(&self, ast: &GammaAST) -> Result<Vec<Pattern>, CompressionError> {
-        let mut patterns = Vec::new();
-        let mut pattern_signatures = std::collections::HashMap::new();
+        let mut patterns: Vec<Pattern> = Vec::new();
+        let mut pattern_index: HashMap<u64, usize> = HashMap::new();
         
         // Real pattern recognition: identify common structural patterns
-        for (node_id, node) in &ast.nodes {
-            // Create a signature based on node structure
-            let signature = self.hash_string(&format!("{:?}{:?}{}", 
-                node.node_type, 
-                node.children.len(),
-                if let crate::gamma_ast::GammaValue::Direct(ref s) = &node.value { s.len() } else { 0 }
-            ));
-            
-            // Count frequency of this pattern
-            let entry = pattern_signatures.entry(signature).or_insert((0, Vec::<crate::gamma_ast::GammaNode>::new()));
-            entry.0 += 1;
-            entry.1.push(node.clone());
+        for node in ast.nodes.values() {
+            // Create a signature based on node structure, hashed without building a string
+            let mut hasher = SignatureHasher::new();
+            hasher.write_str(&node.node_type.as_str());
+            hasher.write_u64(node.children.len() as u64);
+            hasher.write_u64(if let crate::gamma_ast::GammaValue::Direct(ref s) = &node.value { s.len() as u64 } else { 0 });
+            let signature = hasher.finish();
             
-            // Create pattern if we have multiple occurrences (even single nodes can be patterns)
-            if entry.0 >= 1 {
-                let pattern = Pattern {
-                    id: signature,
-                    signature,
-                    frequency: entry.0,
-                    size: entry.1.len(),
-                    nodes: entry.1.clone(),
-                    languages: vec![ast.source_language.clone()],
-                };
-                
-                // Only add if not already present
-                if !patterns.iter().any(|p: &crate::gamma_ast::Pattern| p.id == signature) {
-                    patterns.push(pattern);
+            // The first node seen for a signature forms its pattern; only that node
+            // is cloned, later occurrences are just counted by signature
+            match pattern_index.get(&signature) {
+                Some(&index) => patterns[index].frequency += 1,
+                None => {
+                    pattern_index.insert(signature, patterns.len());
+                    patterns.push(Pattern {
+                        id: signature,
+                        signature,
+                        frequency: 1,
+                        size: 1,
+                        nodes: vec![node.clone()],
+                        languages: vec![ast.source_language.clone()],
+                    });
                 }
             }
         }
         
+        // Signatures seen only once are not patterns, just nodes
+        patterns.retain(|pattern| self.meets_pattern_minimums(pattern));
         Ok(patterns)
     }
     
+    /// Whether a candidate pattern reaches the configured frequency and size
+    fn meets_pattern_minimums(&self, pattern: &Pattern) -> bool {
+        pattern.frequency >= self.config.min_pattern_frequency
+            && pattern.size >= self.config.min_pattern_size
+    }
+    
     /// Apply AI-optimized compression that preserves structure
     async fn apply_ai_compression(
         &self,
         ast: &GammaAST,
         patterns: &[Pattern],
         ai_process: &AIProcess,
+        detailed_stats: &mut Option<DetailedCompressionStats>,
     ) -> Result<GammaAST, CompressionError> {
         // Start with the original AST
         let mut compressed_ast = ast.clone();
         
+        // Structural compression targets code; data is left to constant pooling below
+        let memory_types = self.classify_memory_types(ast);
+        let is_code_pattern = |pattern: &Pattern| {
+            pattern.nodes.iter().all(|node| memory_types.get(&node.id) != Some(&MemoryType::Data))
+        };
+        
         // WORKING COMPRESSION PIPELINE - Proven safe functions only
         
         // 1. Apply basic pattern compression (only if it saves space)
         if !patterns.is_empty() {
             // Only apply patterns that actually save space
             let mut profitable_patterns = Vec::new();
-            for pattern in patterns {
+            let mut predictions = HashMap::new();
+            for pattern in patterns.iter().filter(|pattern| is_code_pattern(pattern)) {
                 let estimated_savings = self.estimate_pattern_savings(pattern, &compressed_ast);
                 let pattern_overhead = 64; // Pattern metadata cost
                 
-                                                    // ENHANCED: Intelligent pattern selection based on quality and frequency
-        let pattern_quality = self.calculate_pattern_quality(pattern, &compressed_ast);
-        let quality_threshold = pattern_overhead * 3 / 4; // ENHANCED: Lower base threshold for better compression
-        
-        // ENHANCED: More aggressive boosting for high-quality patterns
-        let adjusted_threshold = if pattern_quality > 0.8 {
-            quality_threshold * 1 / 2 // 50% lower threshold for high-quality patterns
-        } else if pattern_quality > 0.6 {
-            quality_threshold * 2 / 3 // 33% lower threshold for medium-quality patterns
-        } else if pattern_quality > 0.4 {
-            quality_threshold * 5 / 6 // 17% lower threshold for low-quality patterns
-        } else {
-            quality_threshold // Standard threshold for very low-quality patterns
-        };
-        
-        if estimated_savings > adjusted_threshold {
-            profitable_patterns.push(pattern);
-        }
+                // ENHANCED: Intelligent pattern selection based on quality and frequency
+                let pattern_quality = self.calculate_pattern_quality(pattern, &compressed_ast);
+                let quality_threshold = pattern_overhead * 3 / 4; // ENHANCED: Lower base threshold for better compression
+                
+                // The learning engine scales the bar per quality band from past outcomes
+                let adjusted_threshold = self.lock_learning_engine()
+                    .profitability_threshold(pattern_quality, quality_threshold);
+                
+                if estimated_savings > adjusted_threshold {
+                    predictions.insert(pattern.id, (pattern_quality, estimated_savings));
+                    profitable_patterns.push(pattern);
+                }
             }
             
-            // Apply only profitable patterns
-            for pattern in &profitable_patterns {
-                self.apply_pattern_to_ast(&mut compressed_ast, pattern)?;
-            }
+            // Most profitable first, so overlapping patterns cannot steal their nodes
+            sort_patterns_by_value(&mut profitable_patterns, |pattern| {
+                self.estimate_pattern_savings(pattern, &compressed_ast) as f64
+            });
+            
+            // Apply only profitable patterns, teaching the learning engine what each really saved
+            measured(&mut compressed_ast, PassKind::PatternApplication, detailed_stats, |ast| {
+                for pattern in &profitable_patterns {
+                    let pattern_bytes = |ast: &GammaAST| pattern.nodes.iter().map(|node| ast.node_bytes(node.id)).sum::<usize>();
+                    let before = pattern_bytes(ast);
+                    self.apply_pattern_to_ast(ast, pattern)?;
+                    let actual_savings = before as isize - pattern_bytes(ast) as isize;
+                    
+                    if let Some(&(quality, predicted)) = predictions.get(&pattern.id) {
+                        self.lock_learning_engine()
+                            .observe_savings(quality, predicted, actual_savings);
+                    }
+                }
+                Ok(())
+            })?;
         }
         
-        // 2. Apply value compression (strings, numbers) - this actually saves space
-        self.apply_value_compression(&mut compressed_ast)?;
+        // 2. Apply value compression (strings, numbers) - constant pooling for data and code literals
+        measured(&mut compressed_ast, PassKind::ValueCompression, detailed_stats, |ast| {
+            self.apply_value_compression(ast)
+        })?;
         
         // 3. Apply basic deduplication (only if it saves space)
-        self.apply_basic_deduplication(&mut compressed_ast)?;
+        measured(&mut compressed_ast, PassKind::Deduplication, detailed_stats, |ast| {
+            self.apply_basic_deduplication(ast)
+        })?;
         
         // 4. ENHANCED: Apply advanced cross-file pattern compression for better ratios
-        self.apply_advanced_cross_file_compression(&mut compressed_ast)?;
+        measured(&mut compressed_ast, PassKind::CrossFile, detailed_stats, |ast| {
+            self.apply_advanced_cross_file_compression(ast)
+        })?;
         
         // 5. Skip all the complex algorithms that add overhead
         // DISABLED: apply_aggressive_byte_compression, apply_smart_pattern_compression, etc.
@@ -406,6 +897,44 @@ impl EnhancedCompressionEngine {
         Ok(compressed_ast)
     }
     
+    /// Put pinned nodes back exactly as they were in `original`
+    ///
+    /// Passes are free to rewrite any node; undoing their work on pinned nodes
+    /// here keeps every pass, present and future, honouring `pinned_nodes`.
+    /// Descendants a pass removed come back with them, so no restored child
+    /// ID is left dangling.
+    fn restore_pinned_nodes(&self, original: &GammaAST, compressed: &mut GammaAST) {
+        let mut stack: Vec<u64> = self.config.pinned_nodes.iter().copied().collect();
+        while let Some(id) = stack.pop() {
+            let Some(node) = original.nodes.get(&id) else { continue };
+            stack.extend(node.children.iter().copied().filter(|child| !compressed.nodes.contains_key(child)));
+            compressed.nodes.insert(id, node.clone());
+        }
+    }
+    
+    /// Drop a rewritten node's children, unless `preserve_children` forbids it
+    ///
+    /// Every pass that would discard children goes through here, so the config
+    /// flag is the single switch between lossless and lossy output. Dropping
+    /// children marks the node `Maximum`, since they cannot be decoded again.
+    fn clear_children(&self, node: &mut GammaNode) {
+        if !self.config.preserve_children && !node.children.is_empty() {
+            node.children.clear();
+            node.compression_level = CompressionLevel::Maximum;
+        }
+    }
+    
+    /// Level for a node about to become a reference to a duplicate of itself
+    ///
+    /// Only direct values survive the round trip (`Light`); a reference standing
+    /// in for another reference loses its target.
+    fn dedup_level(value: &GammaValue) -> CompressionLevel {
+        match value {
+            GammaValue::Direct(_) => CompressionLevel::Light,
+            _ => CompressionLevel::Maximum,
+        }
+    }
+    
     /// ENHANCED: Safe structural pattern compression that preserves AST integrity
     fn apply_safe_structural_compression(&self, ast: &mut GammaAST) -> Result<(), CompressionError> {
         // Find structural patterns that can be safely compressed
@@ -413,22 +942,23 @@ impl EnhancedCompressionEngine {
         
         // Group nodes by their structural signature (children count + node type)
         for (node_id, node) in &ast.nodes {
-            let structural_key = format!("{:?}:{}", node.node_type, node.children.len());
+            let structural_key = format!("{}:{}", node.node_type, node.children.len());
             structural_patterns.entry(structural_key).or_insert_with(Vec::new).push(*node_id);
         }
         
         // Apply compression to structural patterns that appear multiple times
         let mut total_savings = 0;
-        for (_, node_ids) in structural_patterns {
+        for (_, node_ids) in sorted_groups(structural_patterns) {
             if node_ids.len() > 2 { // Only compress if pattern appears 3+ times
                 let reference_id = node_ids[0];
                 
                 // Replace duplicate structural patterns with references
                 for &duplicate_id in &node_ids[1..] {
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
-                        // Replace with reference to save space
+                        // Replace with reference to save space; shapes match but values may not
                         duplicate_node.value = crate::gamma_ast::GammaValue::PatternRef(reference_id);
-                        duplicate_node.children.clear(); // Remove children to save space
+                        duplicate_node.compression_level = CompressionLevel::Maximum;
+                        self.clear_children(duplicate_node);
                         duplicate_node.metadata.clear(); // Remove metadata to save space
                         
                         // Calculate savings
@@ -471,15 +1001,16 @@ impl EnhancedCompressionEngine {
             }
         }
         
-        // Replace duplicate nodes with references to the first occurrence
-        for (value, node_ids) in value_map {
+        // Replace duplicate nodes with references to the lowest-ID occurrence
+        for (value, node_ids) in sorted_groups(value_map) {
             if node_ids.len() > 1 {
                 let reference_id = node_ids[0];
                 for &duplicate_id in &node_ids[1..] {
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
                         // Replace duplicate with reference to save space
+                        duplicate_node.compression_level = Self::dedup_level(&duplicate_node.value);
                         duplicate_node.value = crate::gamma_ast::GammaValue::PatternRef(reference_id);
-                        duplicate_node.children.clear(); // Remove children to save space
+                        self.clear_children(duplicate_node);
                         duplicate_node.metadata.clear(); // Remove metadata to save space
                     }
                 }
@@ -497,14 +1028,25 @@ impl EnhancedCompressionEngine {
         large_patterns: Vec<&Pattern>,
     ) -> Result<(), CompressionError> {
         // CRITICAL: Use GPU acceleration for large pattern processing
-        let gpu_engine_result = crate::GPUAccelerationEngine::new(
-            crate::GPUConfig::default()
-        );
+        let gpu_engine_result = if self.config.catch_gpu_panics {
+            match Self::catch_gpu_panic(|| GPUAccelerationEngine::new(self.gpu_config.clone())) {
+                Ok(result) => result,
+                Err(e) => {
+                    log::warn!("{}, falling back to CPU-only processing", e);
+                    for pattern in large_patterns {
+                        self.apply_cpu_pattern_compression(ast, pattern)?;
+                    }
+                    return Ok(());
+                }
+            }
+        } else {
+            GPUAccelerationEngine::new(self.gpu_config.clone())
+        };
         
         let mut gpu_engine = match gpu_engine_result {
             Ok(engine) => engine,
             Err(e) => {
-                println!("⚠️ GPU engine creation failed: {}, falling back to CPU-only processing", e);
+                log::warn!("GPU engine creation failed: {}, falling back to CPU-only processing", e);
                 // Fall back to CPU processing for all patterns
                 for pattern in large_patterns {
                     self.apply_cpu_pattern_compression(ast, pattern)?;
@@ -517,8 +1059,14 @@ impl EnhancedCompressionEngine {
         let mut patterns_processed = 0;
         
         for pattern in large_patterns {
+            // Shapes the GPU has not paid off for go straight to the CPU
+            if !self.route_to_gpu(pattern) {
+                self.apply_cpu_pattern_compression(ast, pattern)?;
+                continue;
+            }
+            
             // Convert gamma_ast::Pattern to gpu_acceleration::UniversalPattern
-            let universal_pattern = crate::UniversalPattern {
+            let universal_pattern = UniversalPattern {
                 id: pattern.id,
                 pattern_type: "AST_Pattern".to_string(),
                 data: self.serialize_pattern_to_bytes(pattern),
@@ -528,39 +1076,184 @@ impl EnhancedCompressionEngine {
             };
             
             // Process pattern on GPU for enhanced compression
-            match gpu_engine.process_universal_pattern(&universal_pattern) {
+            let gpu_outcome = if self.config.catch_gpu_panics {
+                match Self::catch_gpu_panic(|| gpu_engine.process_universal_pattern(&universal_pattern)) {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        self.fall_back_to_cpu(ast, pattern, &e)?;
+                        continue;
+                    }
+                }
+            } else {
+                gpu_engine.process_universal_pattern(&universal_pattern)
+            };
+            
+            match gpu_outcome {
                 Ok(result) => {
                     total_compression_improvement += result.compression_improvement;
                     patterns_processed += 1;
+                    self.record_gpu_outcome(pattern, result.compression_improvement, result.processing_time);
                     
                     // Apply GPU-optimized compression to the project
                     self.apply_gpu_optimized_compression(ast, pattern, &result)?;
                 }
                 Err(e) => {
-                    println!("⚠️ GPU processing failed for pattern {}: {}, falling back to CPU", 
-                        pattern.id, e);
-                    // Fall back to CPU processing
-                    self.apply_cpu_pattern_compression(ast, pattern)?;
+                    self.fall_back_to_cpu(ast, pattern, &format_args!("GPU processing failed: {}", e))?;
                 }
             }
         }
         
         if patterns_processed > 0 {
             let avg_improvement = total_compression_improvement / patterns_processed as f64;
-            println!("✅ GPU Acceleration: {} patterns processed, avg improvement: {:.2}x", 
+            log::info!("GPU acceleration: {} patterns processed, avg improvement: {:.2}x",
                 patterns_processed, avg_improvement);
         }
         
         Ok(())
     }
     
+    /// Send a pattern to the GPU if its signature has paid off there before
+    ///
+    /// Until the learning engine has enough history for the signature, patterns
+    /// larger than `gpu_threshold()` nodes go to the GPU.
+    fn route_to_gpu(&self, pattern: &Pattern) -> bool {
+        self.lock_learning_engine()
+            .prefers_gpu(pattern.signature)
+            .unwrap_or(pattern.nodes.len() > self.gpu_threshold())
+    }
+    
+    /// Pattern size above which patterns go to the GPU: the calibrated crossover, else `config.gpu_threshold`
+    pub fn gpu_threshold(&self) -> usize {
+        self.calibrated_gpu_threshold.unwrap_or(self.config.gpu_threshold)
+    }
+    
+    /// Measure where the GPU starts beating the CPU on this machine and route by it
+    ///
+    /// Sample patterns of increasing size are timed on both paths, best of three
+    /// runs; the threshold lands just below the smallest sample from which the GPU
+    /// wins at every larger size. Without a usable GPU, or if it never wins, the
+    /// threshold is `usize::MAX` so everything stays on the CPU. The result is
+    /// cached until the next call.
+    pub fn calibrate(&mut self) -> usize {
+        let threshold = self.measure_gpu_crossover().unwrap_or(usize::MAX);
+        self.calibrated_gpu_threshold = Some(threshold);
+        threshold
+    }
+    
+    /// Smallest calibration size (minus one) from which the GPU is faster, if any
+    fn measure_gpu_crossover(&self) -> Option<usize> {
+        const RUNS: usize = 3;
+        let engine_with = |memory_threshold| {
+            crate::GPUAccelerationEngine::new(crate::GPUConfig { memory_threshold, ..self.gpu_config.clone() }).ok()
+        };
+        let gpu = engine_with(0).filter(|engine| engine.is_available())?;
+        let cpu = engine_with(usize::MAX)?;
+        let best_time = |engine: &crate::GPUAccelerationEngine, pattern: &crate::gpu_acceleration::UniversalPattern| {
+            (0..RUNS)
+                .filter_map(|_| engine.process_universal_pattern(pattern).ok())
+                .map(|result| result.processing_time)
+                .min()
+        };
+        
+        let mut crossover = None;
+        for size in CALIBRATION_SIZES {
+            let sample = crate::gpu_acceleration::UniversalPattern {
+                id: size as u64,
+                pattern_type: "calibration".to_string(),
+                data: vec![0; size * 16],
+                size,
+                compression_potential: 1.0,
+                gpu_optimized: false,
+            };
+            let gpu_wins = match (best_time(&gpu, &sample), best_time(&cpu, &sample)) {
+                (Some(gpu_time), Some(cpu_time)) => gpu_time < cpu_time,
+                _ => false,
+            };
+            crossover = match (gpu_wins, crossover) {
+                (true, None) => Some(size - 1),
+                (true, found) => found,
+                (false, _) => None,
+            };
+        }
+        crossover
+    }
+    
+    /// Compress `ast` once with neuromorphic and once with basic pattern analysis
+    ///
+    /// Each run uses a fresh engine with this configuration, so neither sees the
+    /// other's (or this engine's) learning state; the neuromorphic run ignores
+    /// `neuromorphic_min_nodes`. GPU calibration is not repeated, both runs use
+    /// this engine's current threshold, so the times cover compression only.
+    pub async fn compare_analysis_modes(&self, ast: &GammaAST) -> Result<AnalysisComparison, CompressionError> {
+        let run = |enable_neuromorphic| {
+            let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig {
+                enable_neuromorphic,
+                neuromorphic_min_nodes: 0,
+                gpu_threshold: self.gpu_threshold(),
+                auto_calibrate_gpu: false,
+                ..self.config.clone()
+            });
+            async move {
+                let start = Instant::now();
+                let result = engine.compress_ast(ast).await?;
+                Ok::<_, CompressionError>((result.compression_ratio, start.elapsed()))
+            }
+        };
+        
+        let (neuro_ratio, neuro_time) = run(true).await?;
+        let (basic_ratio, basic_time) = run(false).await?;
+        Ok(AnalysisComparison { neuro_ratio, neuro_time, basic_ratio, basic_time })
+    }
+    
+    fn record_gpu_outcome(&self, pattern: &Pattern, improvement: f64, time: Duration) {
+        self.lock_learning_engine()
+            .record_gpu_outcome(pattern.signature, improvement, time);
+    }
+    
+    /// Per-signature record of whether the GPU improved compression, for inspection
+    pub fn gpu_benefit_stats(&self) -> HashMap<u64, GpuBenefit> {
+        self.lock_learning_engine().gpu_benefit.clone()
+    }
+    
+    /// Compress `pattern` on the CPU after the GPU failed on it, recording the miss
+    fn fall_back_to_cpu(
+        &self,
+        ast: &mut GammaAST,
+        pattern: &Pattern,
+        reason: &dyn std::fmt::Display,
+    ) -> Result<(), CompressionError> {
+        log::warn!("{} for pattern {}, falling back to CPU", reason, pattern.id);
+        self.record_gpu_outcome(pattern, 0.0, Duration::ZERO);
+        self.apply_cpu_pattern_compression(ast, pattern)
+    }
+    
+    /// Run a GPU call behind a panic boundary, turning a panic into `CompressionError::GPUPanic`
+    ///
+    /// Only effective when the crate is built with `panic = "unwind"`; under the
+    /// release profile's `panic = "abort"` the process still aborts.
+    fn catch_gpu_panic<T, F>(call: F) -> Result<T, CompressionError>
+    where
+        F: FnOnce() -> T,
+    {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(call)).map_err(|payload| {
+            let message = if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "unknown panic payload".to_string()
+            };
+            CompressionError::GPUPanic(message)
+        })
+    }
+    
     #[cfg(feature = "gpu")]
     /// Apply GPU-optimized compression to a specific pattern
     fn apply_gpu_optimized_compression(
         &self,
         ast: &mut GammaAST,
         pattern: &Pattern,
-        gpu_result: &crate::GPUPatternResult,
+        gpu_result: &GPUPatternResult,
     ) -> Result<(), CompressionError> {
         // CRITICAL: Apply GPU-optimized compression while preserving structure
         for node in &pattern.nodes {
@@ -570,8 +1263,8 @@ impl EnhancedCompressionEngine {
                     // Replace with GPU-optimized reference
                     ast_node.node_type = crate::gamma_ast::GammaNodeType::Custom("GPUOptimized".to_string());
                     ast_node.value = crate::gamma_ast::GammaValue::PatternRef(gpu_result.pattern_id);
-                    // CRITICAL: Preserve children for structural integrity
-                    // ast_node.children.clear(); // DISABLED - This destroys structure!
+                    ast_node.compression_level = CompressionLevel::Maximum;
+                    self.clear_children(ast_node);
                 }
             }
         }
@@ -591,8 +1284,8 @@ impl EnhancedCompressionEngine {
                 // Apply CPU-optimized compression
                                     ast_node.node_type = crate::gamma_ast::GammaNodeType::Custom("CPUOptimized".to_string());
                     ast_node.value = crate::gamma_ast::GammaValue::PatternRef(pattern.id);
-                // CRITICAL: Preserve children for structural integrity
-                // ast_node.children.clear(); // DISABLED - This destroys structure!
+                ast_node.compression_level = CompressionLevel::Maximum;
+                self.clear_children(ast_node);
             }
         }
         
@@ -621,7 +1314,7 @@ impl EnhancedCompressionEngine {
         }
         
         // ENHANCED: Pattern clustering for better compression
-        let clustered_patterns = self.cluster_similar_patterns(&effective_patterns);
+        let clustered_patterns = self.cluster_similar_patterns(&effective_patterns, &self.config.similarity);
         
         // Create pattern table with clustering optimization
         for pattern_group in &clustered_patterns {
@@ -729,17 +1422,15 @@ impl EnhancedCompressionEngine {
         // Find and optimize common control flow patterns
         let mut control_flow_savings = 0;
         
-        // Look for if-else chains that can be optimized
+        // Look for if-else chains and loops that can be optimized
         for (node_id, node) in &ast.nodes {
-            if let crate::gamma_ast::GammaNodeType::Custom(ref node_type) = node.node_type {
-                if node_type.contains("if") || node_type.contains("else") || node_type.contains("while") || node_type.contains("for") {
-                    // Optimize control flow node
-                    if let Some(parent_id) = self.find_parent_node(ast, *node_id) {
-                        if let Some(parent) = ast.nodes.get(&parent_id) {
-                            // Check if we can optimize this control flow pattern
-                            if self.can_optimize_control_flow(node, parent) {
-                                control_flow_savings += 24; // Increased from 16 to 24 bytes
-                            }
+            if node.node_type.is_control_flow() && node.node_type != GammaNodeType::Switch {
+                // Optimize control flow node
+                if let Some(parent_id) = self.find_parent_node(ast, *node_id) {
+                    if let Some(parent) = ast.nodes.get(&parent_id) {
+                        // Check if we can optimize this control flow pattern
+                        if self.can_optimize_control_flow(node, parent) {
+                            control_flow_savings += 24; // Increased from 16 to 24 bytes
                         }
                     }
                 }
@@ -748,21 +1439,19 @@ impl EnhancedCompressionEngine {
         
         // ENHANCED: Look for switch/case statements
         for (node_id, node) in &ast.nodes {
-            if let crate::gamma_ast::GammaNodeType::Custom(ref node_type) = node.node_type {
-                if node_type.contains("switch") || node_type.contains("case") || node_type.contains("match") {
-                    // Create a dummy parent node for switch statement optimization
-                    let dummy_parent = crate::gamma_ast::GammaNode {
-                        id: 0,
-                        node_type: crate::gamma_ast::GammaNodeType::Block,
-                        value: crate::gamma_ast::GammaValue::None,
-                        location: None,
-                        children: vec![],
-                        metadata: HashMap::new(),
-                        compression_level: crate::gamma_ast::CompressionLevel::None,
-                    };
-                    if self.can_optimize_control_flow(node, &dummy_parent) {
-                        control_flow_savings += 32; // Switch statements often have more savings
-                    }
+            if node.node_type == GammaNodeType::Switch {
+                // Create a dummy parent node for switch statement optimization
+                let dummy_parent = crate::gamma_ast::GammaNode {
+                    id: 0,
+                    node_type: crate::gamma_ast::GammaNodeType::Block,
+                    value: crate::gamma_ast::GammaValue::None,
+                    location: None,
+                    children: vec![],
+                    metadata: HashMap::new(),
+                    compression_level: crate::gamma_ast::CompressionLevel::None,
+                };
+                if self.can_optimize_control_flow(node, &dummy_parent) {
+                    control_flow_savings += 32; // Switch statements often have more savings
                 }
             }
         }
@@ -973,8 +1662,10 @@ impl EnhancedCompressionEngine {
                 }
                 
                 // Apply compression if we found a new value
+                // Value-table IDs only decode through a reconstruction table
                 if let Some(compressed_value) = new_value {
                     node.value = compressed_value;
+                    node.compression_level = CompressionLevel::Heavy;
                 }
             }
         }
@@ -992,50 +1683,19 @@ impl EnhancedCompressionEngine {
 
     /// ENHANCED: Extract string patterns for better compression
     fn extract_string_pattern(&self, value: &str) -> String {
-        // ENHANCED: More intelligent programming pattern recognition for better compression
-        
-        // Function and method patterns
-        if value.contains("function") || value.contains("func") || value.contains("def") || value.contains("fn") {
-            "function_declaration".to_string()
-        } else if value.contains("class") || value.contains("struct") || value.contains("trait") || value.contains("interface") {
-            "type_declaration".to_string()
-        } else if value.contains("if") || value.contains("else") || value.contains("while") || value.contains("for") || value.contains("loop") {
-            "control_flow".to_string()
-        } else if value.contains("return") || value.contains("break") || value.contains("continue") || value.contains("yield") {
-            "control_statement".to_string()
-        } else if value.contains("public") || value.contains("private") || value.contains("protected") || value.contains("internal") {
-            "access_modifier".to_string()
-        } else if value.contains("static") || value.contains("final") || value.contains("const") || value.contains("readonly") {
-            "modifier".to_string()
-        } else if value.contains("try") || value.contains("catch") || value.contains("finally") || value.contains("throw") {
-            "exception_handling".to_string()
-        } else if value.contains("import") || value.contains("export") || value.contains("require") || value.contains("include") {
-            "module_import".to_string()
-        } else if value.contains("new") || value.contains("delete") || value.contains("typeof") || value.contains("instanceof") {
-            "operator".to_string()
-        } else if value.contains("async") || value.contains("await") || value.contains("promise") || value.contains("future") {
-            "async_pattern".to_string()
-        } else if value.contains("get") || value.contains("set") || value.contains("property") || value.contains("field") {
-            "property_access".to_string()
-        } else if value.len() > 25 {
-            "very_long_string".to_string()
-        } else if value.len() > 15 {
-            "long_string".to_string()
-        } else if value.len() > 8 {
-            "medium_string".to_string()
-        } else if value.len() > 3 {
-            "short_string".to_string()
-        } else {
-            "tiny_string".to_string()
-        }
+        self.config.string_patterns.classify(value)
     }
 
     /// ENHANCED: Cluster similar patterns for better compression
-    fn cluster_similar_patterns(&self, patterns: &[Pattern]) -> Vec<Vec<Pattern>> {
+    fn cluster_similar_patterns(&self, patterns: &[Pattern], similarity: &SimilarityConfig) -> Vec<Vec<Pattern>> {
         let mut clusters: Vec<Vec<Pattern>> = Vec::new();
         let mut processed = HashSet::new();
         
-        for (i, pattern) in patterns.iter().enumerate() {
+        // Greedy clustering depends on order, and callers often hand over HashMap order
+        let mut patterns: Vec<&Pattern> = patterns.iter().collect();
+        patterns.sort_by_key(|pattern| (pattern.signature, pattern.size, pattern.id));
+        
+        for (i, &pattern) in patterns.iter().enumerate() {
             if processed.contains(&i) {
                 continue;
             }
@@ -1044,12 +1704,12 @@ impl EnhancedCompressionEngine {
             processed.insert(i);
             
             // ENHANCED: Find similar patterns based on structure, size, and content
-            for (j, other_pattern) in patterns.iter().enumerate().skip(i + 1) {
+            for (j, &other_pattern) in patterns.iter().enumerate().skip(i + 1) {
                 if processed.contains(&j) {
                     continue;
                 }
                 
-                if self.patterns_are_similar(pattern, other_pattern) {
+                if self.patterns_are_similar(pattern, other_pattern, similarity) {
                     cluster.push(other_pattern.clone());
                     processed.insert(j);
                 }
@@ -1065,54 +1725,15 @@ impl EnhancedCompressionEngine {
     }
     
     /// ENHANCED: Check if two patterns are similar enough to cluster
-    fn patterns_are_similar(&self, pattern1: &Pattern, pattern2: &Pattern) -> bool {
-        // ENHANCED: More aggressive similarity detection for better compression
-        
-        // Size similarity (allow 30% difference instead of 20%)
-        let size_diff = (pattern1.size as i32 - pattern2.size as i32).abs();
-        let size_threshold = (pattern1.size.max(pattern2.size) as f32 * 0.3) as i32;
-        
-        // Node count similarity (allow 3 difference instead of 2)
-        let node_count_diff = (pattern1.nodes.len() as i32 - pattern2.nodes.len() as i32).abs();
-        let node_threshold = 3;
-        
-        // ENHANCED: Content similarity check
-        let content_similarity = self.calculate_content_similarity(pattern1, pattern2);
-        let content_threshold = 0.6; // 60% similarity threshold
-        
-        size_diff <= size_threshold && node_count_diff <= node_threshold && content_similarity >= content_threshold
-    }
-    
-    /// ENHANCED: Calculate content similarity between patterns
-    fn calculate_content_similarity(&self, pattern1: &Pattern, pattern2: &Pattern) -> f64 {
-        if pattern1.nodes.is_empty() || pattern2.nodes.is_empty() {
-            return 0.0;
-        }
-        
-        let mut common_nodes = 0;
-        let total_nodes = pattern1.nodes.len().max(pattern2.nodes.len());
-        
-        // Compare node types and values
-        for node1 in &pattern1.nodes {
-            for node2 in &pattern2.nodes {
-                if node1.node_type == node2.node_type {
-                    if let (crate::gamma_ast::GammaValue::Direct(val1), crate::gamma_ast::GammaValue::Direct(val2)) = (&node1.value, &node2.value) {
-                        if val1 == val2 {
-                            common_nodes += 1;
-                        }
-                    }
-                }
-            }
-        }
-        
-        common_nodes as f64 / total_nodes as f64
+    fn patterns_are_similar(&self, pattern1: &Pattern, pattern2: &Pattern, similarity: &SimilarityConfig) -> bool {
+        self.similarity_metric.are_similar(pattern1, pattern2, similarity)
     }
 
     /// Generate unique signature for pattern
     fn generate_pattern_signature(&self, pattern: &Pattern) -> String {
         let mut signature = String::new();
         for node in &pattern.nodes {
-            signature.push_str(&format!("{:?}:", node.node_type));
+            signature.push_str(&format!("{}:", node.node_type));
         }
         signature
     }
@@ -1147,7 +1768,7 @@ impl EnhancedCompressionEngine {
         }
         
         // CRITICAL FIX: Instead of removing nodes, compress their values while preserving structure
-        for (_, function_ids) in function_groups {
+        for (_, function_ids) in sorted_groups(function_groups) {
             if function_ids.len() > 1 {
                 // Keep first function as reference, compress others to references
                 let reference_id = function_ids[0];
@@ -1167,7 +1788,7 @@ impl EnhancedCompressionEngine {
 
     /// Generate function signature for comparison
     fn generate_function_signature(&self, node: &crate::gamma_ast::GammaNode) -> String {
-        format!("{:?}:{}:{:?}", node.node_type, node.children.len(), node.value)
+        format!("{}:{}:{:?}", node.node_type, node.children.len(), node.value)
     }
 
     /// Merge similar variable declarations
@@ -1203,7 +1824,7 @@ impl EnhancedCompressionEngine {
 
     /// Generate variable signature for grouping
     fn generate_variable_signature(&self, node: &GammaNode) -> String {
-        format!("{:?}:{}", node.node_type, node.value.to_string())
+        format!("{}:{}", node.node_type, node.value.to_string())
     }
 
     /// Merge similar type definitions
@@ -1213,7 +1834,7 @@ impl EnhancedCompressionEngine {
         // Group types by structure
         for (node_id, node) in &ast.nodes {
             if node.node_type == crate::gamma_ast::GammaNodeType::Class {
-                let signature = format!("{:?}:{}:{:?}", node.node_type, node.children.len(), node.value);
+                let signature = format!("{}:{}:{:?}", node.node_type, node.children.len(), node.value);
                 type_groups.entry(signature).or_insert_with(Vec::new).push(*node_id);
             }
         }
@@ -1244,22 +1865,23 @@ impl EnhancedCompressionEngine {
         ast: &mut GammaAST,
         pattern: &Pattern,
     ) -> Result<(), CompressionError> {
-        let mut gpu_manager = self.gpu_manager.lock().unwrap();
-        
-        // Check GPU availability
-        if !gpu_manager.can_allocate_gpu(0, pattern.nodes.len() as u64 * 1024) {
-            return Err(CompressionError::GPUAllocationFailed);
-        }
+        let memory_needed = pattern.nodes.len() as u64 * 1024;
         
-        // Allocate GPU resources
-        gpu_manager.allocate_gpu(0, pattern.nodes.len() as u64 * 1024, 0)?;
+        // Retry transient contention with backoff before giving up to the CPU path
+        self.gpu_config.allocation_retry
+            .retry(
+                || self.gpu_manager.lock().unwrap_or_else(PoisonError::into_inner).allocate_gpu(0, memory_needed, 0),
+                tokio::time::sleep,
+            )
+            .await
+            .map_err(|_| CompressionError::GPUAllocationFailed)?;
         
         // Apply GPU-accelerated compression
         // This would integrate with actual GPU computation libraries
         // For now, we simulate the compression
         
         // Release GPU resources
-        gpu_manager.release_gpu(0, 0)?;
+        self.gpu_manager.lock().unwrap_or_else(PoisonError::into_inner).release_gpu(0, 0)?;
         
         Ok(())
     }
@@ -1347,12 +1969,17 @@ impl EnhancedCompressionEngine {
                 
                 if let Some(s) = string_to_compress {
                     // REAL COMPRESSION: Replace long strings with compressed hashes
-                    let original_length = s.len();
-                    let compressed_value = self.hash_string(&s);
-                    first_node.value = GammaValue::CompressedHash(compressed_value);
-                    first_node.metadata.insert("original_value".to_string(), s);
-                    first_node.metadata.insert("value_compression".to_string(), "true".to_string());
-                    first_node.metadata.insert("compression_ratio".to_string(), format!("{:.1}x", original_length as f64 / 8.0)); // 8 bytes for hash
+                    // (left Direct if no collision-free hash exists)
+                    if let Some((compressed_value, disambiguator)) = self.collision_free_hash(&s) {
+                        let original_length = s.len();
+                        first_node.value = GammaValue::CompressedHash(compressed_value);
+                        first_node.metadata.insert("original_value".to_string(), s);
+                        first_node.metadata.insert("value_compression".to_string(), "true".to_string());
+                        first_node.metadata.insert("compression_ratio".to_string(), format!("{:.1}x", original_length as f64 / 8.0)); // 8 bytes for hash
+                        if disambiguator > 0 {
+                            first_node.metadata.insert("hash_disambiguator".to_string(), disambiguator.to_string());
+                        }
+                    }
                 }
             }
         }
@@ -1380,12 +2007,17 @@ impl EnhancedCompressionEngine {
                 
                 if let Some(s) = string_to_compress {
                     // REAL COMPRESSION: Replace long strings with compressed hashes
-                    let original_length = s.len();
-                    let compressed_value = self.hash_string(&s);
-                    node.value = GammaValue::CompressedHash(compressed_value);
-                    node.metadata.insert("original_value".to_string(), s);
-                    node.metadata.insert("value_compression".to_string(), "true".to_string());
-                    node.metadata.insert("compression_ratio".to_string(), format!("{:.1}x", original_length as f64 / 8.0)); // 8 bytes for hash
+                    // (left Direct if no collision-free hash exists)
+                    if let Some((compressed_value, disambiguator)) = self.collision_free_hash(&s) {
+                        let original_length = s.len();
+                        node.value = GammaValue::CompressedHash(compressed_value);
+                        node.metadata.insert("original_value".to_string(), s);
+                        node.metadata.insert("value_compression".to_string(), "true".to_string());
+                        node.metadata.insert("compression_ratio".to_string(), format!("{:.1}x", original_length as f64 / 8.0)); // 8 bytes for hash
+                        if disambiguator > 0 {
+                            node.metadata.insert("hash_disambiguator".to_string(), disambiguator.to_string());
+                        }
+                    }
                 }
             }
         }
@@ -1493,7 +2125,7 @@ impl EnhancedCompressionEngine {
         // Add parameter types to signature
         for &child_id in &node.children {
             if let Some(child) = ast.nodes.get(&child_id) {
-                signature.push_str(&format!("_{:?}", child.node_type));
+                signature.push_str(&format!("_{}", child.node_type));
             }
         }
         
@@ -1537,7 +2169,7 @@ impl EnhancedCompressionEngine {
         // Add type information
         for &child_id in &node.children {
             if let Some(child) = ast.nodes.get(&child_id) {
-                type_key.push_str(&format!("_{:?}", child.node_type));
+                type_key.push_str(&format!("_{}", child.node_type));
             }
         }
         
@@ -1574,7 +2206,7 @@ impl EnhancedCompressionEngine {
     
     /// Get expression key for grouping
     fn get_expression_key(&self, node: &GammaNode, ast: &GammaAST) -> String {
-        let mut key = format!("expr_{:?}_{}", node.node_type, node.children.len());
+        let mut key = format!("expr_{}_{}", node.node_type, node.children.len());
         
         // Add value information
         if let GammaValue::Direct(ref s) = &node.value {
@@ -1863,7 +2495,7 @@ impl EnhancedCompressionEngine {
             GammaNodeType::If => format!("if_{}_{}", node.children.len(), node.metadata.len()),
             GammaNodeType::Variable => format!("var_{}", node.children.len()),
             GammaNodeType::Custom(ref s) => format!("custom_{}_{}", s, node.children.len()),
-            _ => format!("other_{}_{}", format!("{:?}", node.node_type), node.children.len()),
+            _ => format!("other_{}_{}", node.node_type, node.children.len()),
         }
     }
     
@@ -1879,7 +2511,7 @@ impl EnhancedCompressionEngine {
         for &id in node_ids {
             if let Some(node) = ast.nodes.get(&id) {
                 total_size += std::mem::size_of_val(node);
-                let structure = format!("{:?}_{}_{}", node.node_type, node.children.len(), node.metadata.len());
+                let structure = format!("{}_{}_{}", node.node_type, node.children.len(), node.metadata.len());
                 unique_structures.insert(structure);
             }
         }
@@ -1997,7 +2629,7 @@ impl EnhancedCompressionEngine {
     
     /// Create a unique signature for a node (conservative approach)
     fn create_node_signature_conservative(&self, node: &GammaNode, ast: &GammaAST) -> String {
-        let mut signature = format!("{:?}_{}", node.node_type, node.children.len());
+        let mut signature = format!("{}_{}", node.node_type, node.children.len());
         
         // Add value information (but be conservative about what we consider "duplicate")
         if let GammaValue::Direct(ref s) = &node.value {
@@ -2010,7 +2642,7 @@ impl EnhancedCompressionEngine {
         // Add child type information (but be conservative)
         for &child_id in &node.children {
             if let Some(child) = ast.nodes.get(&child_id) {
-                signature.push_str(&format!("_child_{:?}", child.node_type));
+                signature.push_str(&format!("_child_{}", child.node_type));
             }
         }
         
@@ -2083,6 +2715,8 @@ impl EnhancedCompressionEngine {
                 matching_ids.push(*id);
             }
         }
+        // Lowest ID first so the template node is the same on every run
+        matching_ids.sort_unstable();
         
         if matching_ids.is_empty() {
             None
@@ -2092,7 +2726,14 @@ impl EnhancedCompressionEngine {
     }
     
     /// Test compression on large-scale real-world codebases
-    pub async fn test_large_scale_compression(&mut self, test_cases: &[LargeScaleTestCase]) -> Result<LargeScaleTestResults, CompressionError> {
+    ///
+    /// Prints `[n/total]` progress and an ETA from the average time per case so
+    /// far; `quiet` suppresses progress and per-case output but keeps the summary.
+    /// With `verify_only` a case passes when its compressed AST decompresses back
+    /// to the original (see `GammaAST::round_trip_mismatch`) instead of when it reaches the
+    /// expected ratio, turning the suite into a correctness gate. Lossy passes
+    /// only pass it with `reconstructible` set.
+    pub async fn test_large_scale_compression(&mut self, test_cases: &[LargeScaleTestCase], quiet: bool, verify_only: bool) -> Result<LargeScaleTestResults, CompressionError> {
         let mut results = LargeScaleTestResults {
             test_cases: Vec::new(),
             overall_stats: OverallTestStats::default(),
@@ -2101,17 +2742,35 @@ impl EnhancedCompressionEngine {
         println!("🧪 Starting Large-Scale Compression Testing...");
         println!("{}", "=".repeat(60));
         
+        let total = test_cases.len();
+        let run_start = Instant::now();
         for (i, test_case) in test_cases.iter().enumerate() {
-            println!("\n📊 Test Case {}: {}", i + 1, test_case.name);
-            println!("   - Source: {}", test_case.source);
-            println!("   - Node count: {}", test_case.ast.nodes.len());
-            println!("   - Expected compression: {}x", test_case.expected_compression);
+            if !quiet {
+                println!("\n📊 Test Case [{}/{}]: {}", i + 1, total, test_case.name);
+                println!("   - Source: {}", test_case.source);
+                println!("   - Node count: {}", test_case.ast.nodes.len());
+                println!("   - Expected compression: {}x", test_case.expected_compression);
+            }
             
             // Run compression on this test case
             let start_time = Instant::now();
-            let compression_result = self.compress_ast(&test_case.ast.clone()).await?;
+            let (compression_result, compressed_ast) = self.compress_with_output(&test_case.ast).await?;
             let test_time = start_time.elapsed();
             
+            let mismatch = if verify_only {
+                match compressed_ast.decompress() {
+                    Ok(restored) => test_case.ast.round_trip_mismatch(&restored, true),
+                    Err(e) => Some(e.to_string()),
+                }
+            } else {
+                None
+            };
+            let success = if verify_only {
+                mismatch.is_none()
+            } else {
+                compression_result.compression_ratio >= test_case.expected_compression
+            };
+            
             // Calculate test case results
             let test_result = TestCaseResult {
                 name: test_case.name.clone(),
@@ -2122,22 +2781,41 @@ impl EnhancedCompressionEngine {
                 patterns_identified: compression_result.patterns_identified,
                 processing_time: test_time,
                 expected_compression: test_case.expected_compression,
-                success: compression_result.compression_ratio >= test_case.expected_compression,
+                success,
                 neuromorphic_insights: compression_result.neuromorphic_insights.clone(),
+                mismatch,
             };
             
             // Display results
-            println!("   ✅ Compression completed in {:?}", test_time);
-            println!("   - Original size: {} bytes", test_result.original_size);
-            println!("   - Compressed size: {} bytes", test_result.compressed_size);
-            println!("   - Compression ratio: {:.2}x", test_result.compression_ratio);
-            println!("   - Size reduction: {:.1}%", 
-                ((test_result.original_size - test_result.compressed_size) as f64 / test_result.original_size as f64) * 100.0);
-            println!("   - Patterns identified: {}", test_result.patterns_identified);
-            println!("   - Expected: {}x | Achieved: {:.2}x | {}", 
-                test_case.expected_compression, 
-                test_result.compression_ratio,
-                if test_result.success { "✅ PASS" } else { "❌ FAIL" });
+            if !quiet {
+                println!("   ✅ Compression completed in {:?}", test_time);
+                println!("   - Original size: {} bytes", test_result.original_size);
+                println!("   - Compressed size: {} bytes", test_result.compressed_size);
+                println!("   - Compression ratio: {:.2}x", test_result.compression_ratio);
+                println!("   - Size reduction: {:.1}%", 
+                    space_saving_percent(test_result.original_size, test_result.compressed_size));
+                println!("   - Patterns identified: {}", test_result.patterns_identified);
+                if verify_only {
+                    match &test_result.mismatch {
+                        None => println!("   - Round trip: lossless | ✅ PASS"),
+                        Some(mismatch) => println!("   - Round trip: {} | ❌ FAIL", mismatch),
+                    }
+                } else {
+                    println!("   - Expected: {}x | Achieved: {:.2}x | {}", 
+                        test_case.expected_compression, 
+                        test_result.compression_ratio,
+                        if test_result.success { "✅ PASS" } else { "❌ FAIL" });
+                }
+                
+                // ETA assumes the remaining cases take the average time of those run so far
+                let done = i + 1;
+                let average = run_start.elapsed().as_secs_f64() / done as f64;
+                println!("   ⏳ [{}/{}] {:.0}% complete, ETA {:.1}s", 
+                    done, 
+                    total, 
+                    done as f64 / total as f64 * 100.0,
+                    average * (total - done) as f64);
+            }
             
             results.test_cases.push(test_result);
         }
@@ -2501,6 +3179,7 @@ impl EnhancedCompressionEngine {
             created_at: Instant::now(),
             model_type: "compression_engine".to_string(),
             batch_size: 1,
+            deadline: None,
         })
     }
     
@@ -2513,29 +3192,18 @@ impl EnhancedCompressionEngine {
         size
     }
     
-    /// Generate cryptographic verification hash
+    /// Generate a content verification hash
+    ///
+    /// SHA-256 of the canonical serialization of the whole compressed AST, as
+    /// lowercase hex, so any change to a node, value, pattern or root changes the
+    /// hash and matching content cannot be forged. Map keys are sorted by
+    /// `serde_json::Value`, which keeps the result stable across processes.
     pub fn generate_verification_hash(&self, ast: &GammaAST) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::Hasher;
-        
-        let mut hasher = DefaultHasher::new();
-        
-        // Custom hash implementation for GammaAST
-        // Hash the root nodes
-        for &root_id in &ast.roots {
-            hasher.write_u64(root_id);
-        }
+        let canonical = serde_json::to_value(ast)
+            .expect("GammaAST always serializes to JSON")
+            .to_string();
         
-        // Hash node count
-        hasher.write_usize(ast.nodes.len());
-        
-        // Hash pattern count
-        hasher.write_usize(ast.patterns.len());
-        
-        // Hash source language
-        hasher.write(ast.source_language.as_bytes());
-        
-        format!("{:x}", hasher.finish())
+        format!("{:x}", Sha256::digest(canonical.as_bytes()))
     }
     
     /// Extract neuromorphic insights from patterns
@@ -2593,17 +3261,33 @@ impl EnhancedCompressionEngine {
     
 
     
+    /// Current learned profitability threshold multipliers, best quality band first
+    ///
+    /// Band bounds are `neuromem::QUALITY_BANDS`; the base threshold is scaled by
+    /// these before a pattern is applied.
+    pub fn learned_thresholds(&self) -> [f32; 4] {
+        self.lock_learning_engine().profitability_multipliers
+    }
+    
     /// Update learning engine with compression results
-    async fn update_learning_engine(&self, result: &CompressionResult) {
-        let mut learning_engine = self.learning_engine.lock().unwrap();
-        
+    ///
+    /// Called from async code, so the event is queued rather than waiting on
+    /// the learning engine's lock; it lands on the engine's next use.
+    fn update_learning_engine(&self, result: &CompressionResult) {
         let change = result.compression_ratio - self.config.target_ratio;
-        learning_engine.record_event(
+        self.learning_events.record_event(
             change as f32,
             format!("Compression ratio: {:.2}x", result.compression_ratio)
         );
     }
     
+    /// Lock the learning engine, first applying any events queued by async code
+    fn lock_learning_engine(&self) -> MutexGuard<'_, LearningEngine> {
+        let mut learning_engine = self.learning_engine.lock().unwrap_or_else(PoisonError::into_inner);
+        self.learning_inbox.drain_into(&mut learning_engine);
+        learning_engine
+    }
+    
     /// Identify spatial clusters in the AST
     fn identify_spatial_clusters(&self, ast: &GammaAST) -> Vec<Pattern> {
         let mut clusters = Vec::new();
@@ -2615,10 +3299,10 @@ impl EnhancedCompressionEngine {
                 continue;
             }
             
-            let mut cluster_nodes = Vec::new();
+            let mut cluster_ids = Vec::new();
             let mut cluster_size = 0;
             
-            // Find similar nodes in the same region
+            // Find similar nodes in the same region, tracking IDs until the cluster qualifies
             for (other_id, other_node) in &ast.nodes {
                 if visited.contains(other_id) || node_id == other_id {
                     continue;
@@ -2626,19 +3310,24 @@ impl EnhancedCompressionEngine {
                 
                 // Check structural similarity
                 if self.nodes_are_similar(node, other_node) {
-                    cluster_nodes.push(other_node.clone());
+                    cluster_ids.push(*other_id);
                     cluster_size += std::mem::size_of_val(other_node);
                     visited.insert(*other_id);
                 }
             }
             
-            if cluster_nodes.len() >= 2 {
+            if cluster_ids.len() >= 2 {
                 let pattern = Pattern {
                     id: self.hash_string(&format!("spatial_{}", node_id)),
-                    signature: self.hash_string(&format!("{:?}{}", node.node_type, cluster_nodes.len())),
-                    frequency: cluster_nodes.len() as u32,
-                    size: cluster_nodes.len(),
-                    nodes: cluster_nodes,
+                    signature: {
+                        let mut hasher = SignatureHasher::new();
+                        hasher.write_str(&node.node_type.as_str());
+                        hasher.write_u64(cluster_ids.len() as u64);
+                        hasher.finish()
+                    },
+                    frequency: cluster_ids.len() as u32,
+                    size: cluster_ids.len(),
+                    nodes: Self::materialize_nodes(ast, &cluster_ids),
                     languages: vec![ast.source_language.clone()],
                 };
                 clusters.push(pattern);
@@ -2663,7 +3352,7 @@ impl EnhancedCompressionEngine {
                 });
                 
                 entry.0 += 1;
-                entry.1.push(node.clone());
+                entry.1.push(*node_id);
                 entry.2 += std::mem::size_of_val(node);
                 
                 // Create pattern for frequently accessed sequences
@@ -2673,7 +3362,7 @@ impl EnhancedCompressionEngine {
                         signature: entry.0 as u64,
                         frequency: entry.0,
                         size: entry.1.len(),
-                        nodes: entry.1.clone(),
+                        nodes: Self::materialize_nodes(ast, &entry.1),
                         languages: vec![ast.source_language.clone()],
                     };
                     
@@ -2688,6 +3377,11 @@ impl EnhancedCompressionEngine {
         patterns
     }
     
+    /// Clone the nodes for a set of IDs, only once a pattern is actually emitted
+    fn materialize_nodes(ast: &GammaAST, node_ids: &[u64]) -> Vec<crate::gamma_ast::GammaNode> {
+        node_ids.iter().filter_map(|id| ast.nodes.get(id).cloned()).collect()
+    }
+    
     /// Refine patterns using learning engine
     async fn refine_patterns_with_learning(&self, patterns: &mut Vec<Pattern>) {
         // Apply learning-based pattern refinement
@@ -2704,6 +3398,33 @@ impl EnhancedCompressionEngine {
         hasher.finish()
     }
     
+    /// Hash a value for `GammaValue::CompressedHash`, checking it against values hashed earlier
+    ///
+    /// Returns the hash plus the disambiguation counter mixed in (0 if the plain hash
+    /// was free), or `None` when every candidate collides and the value must stay `Direct`.
+    fn collision_free_hash(&self, value: &str) -> Option<(u64, u32)> {
+        const MAX_DISAMBIGUATION_ATTEMPTS: u32 = 8;
+        let mut table = self.value_hash_table.lock().unwrap_or_else(PoisonError::into_inner);
+        
+        for attempt in 0..=MAX_DISAMBIGUATION_ATTEMPTS {
+            let hash = if attempt == 0 {
+                self.hash_string(value)
+            } else {
+                self.hash_string(&format!("{}\u{0}{}", value, attempt))
+            };
+            match table.get(&hash) {
+                None => {
+                    table.insert(hash, value.to_string());
+                    return Some((hash, attempt));
+                }
+                Some(existing) if existing == value => return Some((hash, attempt)),
+                Some(_) => continue,
+            }
+        }
+        
+        None
+    }
+    
     /// Optimize children references for compression
     fn optimize_children(&self, children: &[u64], ast: &GammaAST) -> Vec<u64> {
         let mut optimized = Vec::new();
@@ -2737,18 +3458,17 @@ impl EnhancedCompressionEngine {
     
     /// Create access sequence signature for temporal analysis
     fn create_access_sequence(&self, node: &crate::gamma_ast::GammaNode, ast: &GammaAST) -> u64 {
-        let mut sequence_signature = self.hash_string(&format!("{:?}", node.node_type));
+        let mut sequence = SignatureHasher::new();
+        sequence.write_str(&node.node_type.as_str());
         
-        // Include children information in sequence
+        // Include children in order, so reordered children give a different sequence
         for &child_id in &node.children {
             if let Some(child_node) = ast.nodes.get(&child_id) {
-                sequence_signature = sequence_signature.wrapping_add(
-                    self.hash_string(&format!("{:?}", child_node.node_type))
-                );
+                sequence.write_str(&child_node.node_type.as_str());
             }
         }
         
-        sequence_signature
+        sequence.finish()
     }
     
     /// Calculate compression metrics with proper validation
@@ -2788,7 +3508,14 @@ impl EnhancedCompressionEngine {
     }
     
     /// Serialize AST to bytes for accurate compression measurement
+    ///
+    /// Uses the serializer from `set_serializer` when one is set, so reported
+    /// ratios match the bytes actually shipped.
     fn serialize_ast_to_bytes(&self, ast: &GammaAST) -> Vec<u8> {
+        if let Some(serializer) = &self.serializer {
+            return serializer.serialize(ast);
+        }
+        
         // CRITICAL FIX: Optimize serialization to reduce overhead and get accurate compression measurement
         let mut bytes = Vec::new();
         
@@ -2857,6 +3584,11 @@ impl EnhancedCompressionEngine {
             }
         }
         
+        // The reconstruction table ships with the AST, so its cost counts against the ratio
+        if let Some(table) = &ast.reconstruction {
+            bytes.extend(serde_json::to_vec(table).expect("ReconstructionTable always serializes to JSON"));
+        }
+        
         bytes
     }
     
@@ -2960,11 +3692,15 @@ impl EnhancedCompressionEngine {
             
             if let Some(s) = string_to_compress {
                 // Replace very long strings with compressed references (but preserve structure)
-                let compressed_hash = self.hash_string(&s);
-                optimized_node.value = GammaValue::CompressedHash(compressed_hash);
-                optimized_node.metadata.insert("original_string".to_string(), s);
-                optimized_node.metadata.insert("compression_type".to_string(), "string_compression".to_string());
-                optimized_node.metadata.insert("structural_preservation".to_string(), "100%".to_string());
+                if let Some((compressed_hash, disambiguator)) = self.collision_free_hash(&s) {
+                    optimized_node.value = GammaValue::CompressedHash(compressed_hash);
+                    optimized_node.metadata.insert("original_string".to_string(), s);
+                    optimized_node.metadata.insert("compression_type".to_string(), "string_compression".to_string());
+                    optimized_node.metadata.insert("structural_preservation".to_string(), "100%".to_string());
+                    if disambiguator > 0 {
+                        optimized_node.metadata.insert("hash_disambiguator".to_string(), disambiguator.to_string());
+                    }
+                }
             }
             
             // 2. Optimize children references (but preserve ALL relationships)
@@ -3043,12 +3779,12 @@ impl EnhancedCompressionEngine {
     
     /// Get parent-child structure key for grouping
     fn get_parent_child_structure(&self, node: &GammaNode, ast: &GammaAST) -> String {
-        let mut structure = format!("pc_{:?}_{}", node.node_type, node.children.len());
+        let mut structure = format!("pc_{}_{}", node.node_type, node.children.len());
         
         // Add child type information
         for &child_id in &node.children {
             if let Some(child) = ast.nodes.get(&child_id) {
-                structure.push_str(&format!("_{:?}", child.node_type));
+                structure.push_str(&format!("_{}", child.node_type));
             }
         }
         
@@ -3121,7 +3857,7 @@ impl EnhancedCompressionEngine {
     
     /// Get structural key for grouping
     fn get_structural_key(&self, node: &GammaNode, ast: &GammaAST) -> String {
-        let mut key = format!("struct_{:?}_{}", node.node_type, node.children.len());
+        let mut key = format!("struct_{}_{}", node.node_type, node.children.len());
         
         // Add structural information based on node type
         match &node.node_type {
@@ -3241,6 +3977,14 @@ impl EnhancedCompressionEngine {
             }
         }
         
+        // Keep the most valuable patterns, frequency × potential, ties by signature
+        cross_patterns.sort_by(|a, b| {
+            let a_value = a.frequency as f64 * a.compression_potential;
+            let b_value = b.frequency as f64 * b.compression_potential;
+            b_value.total_cmp(&a_value).then_with(|| a.signature.cmp(&b.signature))
+        });
+        cross_patterns.truncate(self.config.max_patterns);
+        
         cross_patterns
     }
     
@@ -3331,9 +4075,9 @@ impl EnhancedCompressionEngine {
                 // Create a cross-file pattern reference
                 node.node_type = GammaNodeType::Custom("CrossFilePattern".to_string());
                 node.value = GammaValue::PatternRef(pattern.id);
+                node.compression_level = CompressionLevel::Maximum;
                 
-                // Clear children to save space (this is safe for cross-file patterns)
-                node.children.clear();
+                self.clear_children(node);
             }
         }
         
@@ -3356,9 +4100,9 @@ impl EnhancedCompressionEngine {
                         // Create a meta-pattern reference
                         ast_node.node_type = GammaNodeType::Custom("MetaPattern".to_string());
                         ast_node.value = GammaValue::PatternRef(pattern.id);
+                        ast_node.compression_level = CompressionLevel::Maximum;
                         
-                        // Clear children to save space (this is safe for meta-patterns)
-                        ast_node.children.clear();
+                        self.clear_children(ast_node);
                     }
                 }
             }
@@ -3393,12 +4137,12 @@ impl EnhancedCompressionEngine {
     
     /// Calculate hierarchical level for a pattern
     fn calculate_hierarchical_level(&self, node_ids: &[u64], ast: &GammaAST) -> u8 {
-        let mut max_depth = 0;
-        
-        for node_id in node_ids {
-            let depth = self.calculate_node_depth(*node_id, ast);
-            max_depth = max_depth.max(depth);
-        }
+        let depths = ast.node_depths();
+        let max_depth = node_ids.iter()
+            .filter_map(|node_id| depths.get(node_id))
+            .max()
+            .copied()
+            .unwrap_or(0);
         
         // Convert depth to hierarchical level (1-4)
         match max_depth {
@@ -3445,33 +4189,12 @@ impl EnhancedCompressionEngine {
     
 
     
-    /// Calculate node depth in the AST
-    fn calculate_node_depth(&self, node_id: u64, ast: &GammaAST) -> u32 {
-        // Since GammaNode doesn't have parent tracking, we use a simplified depth calculation
-        // based on the node's position in the AST structure
-        let mut depth = 0;
-        
-        // For now, return a basic depth estimate based on node ID
-        // This could be enhanced with proper parent tracking in future versions
-        if node_id <= 100 {
-            depth = 1;
-        } else if node_id <= 1000 {
-            depth = 2;
-        } else if node_id <= 10000 {
-            depth = 3;
-        } else {
-            depth = 4;
-        }
-        
-        depth
-    }
-    
     /// Get advanced structural key for cross-file pattern recognition
     fn get_advanced_structural_key(&self, node: &GammaNode, ast: &GammaAST) -> String {
         let mut key_parts = Vec::new();
         
         // Add node type
-        key_parts.push(format!("{:?}", node.node_type));
+        key_parts.push(node.node_type.to_string());
         
         // Add value type
         key_parts.push(format!("{:?}", node.value));
@@ -3485,11 +4208,9 @@ impl EnhancedCompressionEngine {
         key_parts.join("|")
     }
     
-    /// Generate a unique pattern ID
+    /// Generate a pattern ID that is unique within the current compression run
     fn generate_pattern_id(&self) -> u64 {
-        let start = SystemTime::now();
-        let since_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-        since_epoch.as_nanos() as u64
+        self.id_generator.next_id()
     }
     
     /// Identify structural patterns in the AST
@@ -3505,29 +4226,36 @@ impl EnhancedCompressionEngine {
         }
         
         // ENHANCED: Create high-quality patterns with better filtering
-        for (signature, node_ids) in structural_groups {
-            // ENHANCED: More intelligent pattern filtering
-            let should_create_pattern = if node_ids.len() > 3 {
-                // Always create patterns for large groups
-                true
-            } else if node_ids.len() > 1 {
-                // Create patterns for medium groups only if they have high structural value
-                self.has_high_structural_value(&signature, &node_ids, ast)
-            } else {
-                false
+        let mut candidates: Vec<(String, Vec<u64>)> = structural_groups.into_iter()
+            .filter(|(signature, node_ids)| {
+                // ENHANCED: More intelligent pattern filtering
+                if node_ids.len() > 3 {
+                    // Always create patterns for large groups
+                    true
+                } else if node_ids.len() > 1 {
+                    // Create patterns for medium groups only if they have high structural value
+                    self.has_high_structural_value(signature, node_ids, ast)
+                } else {
+                    false
+                }
+            })
+            .collect();
+        
+        // Frequency and size are both the group size, so the largest groups are
+        // the most valuable; cap them before cloning any nodes
+        candidates.sort_by(|(a_sig, a_ids), (b_sig, b_ids)| b_ids.len().cmp(&a_ids.len()).then_with(|| a_sig.cmp(b_sig)));
+        candidates.truncate(self.config.max_patterns);
+        
+        for (signature, node_ids) in candidates {
+            let pattern = Pattern {
+                id: self.generate_pattern_id(),
+                signature: self.hash_string(&signature),
+                frequency: node_ids.len() as u32,
+                size: node_ids.len(),
+                nodes: Self::materialize_nodes(ast, &node_ids),
+                languages: vec![ast.source_language.clone()],
             };
-            
-            if should_create_pattern {
-                let pattern = Pattern {
-                    id: self.generate_pattern_id(),
-                    signature: self.hash_string(&signature),
-                    frequency: node_ids.len() as u32,
-                    size: node_ids.len(),
-                    nodes: node_ids.iter().filter_map(|&id| ast.nodes.get(&id).cloned()).collect(),
-                    languages: vec![ast.source_language.clone()],
-                };
-                patterns.push(pattern);
-            }
+            patterns.push(pattern);
         }
         
         patterns
@@ -3555,9 +4283,9 @@ impl EnhancedCompressionEngine {
                 // Replace with pattern reference
                 ast_node.node_type = GammaNodeType::Custom("StructuralPattern".to_string());
                 ast_node.value = GammaValue::PatternRef(pattern.id);
+                ast_node.compression_level = CompressionLevel::Maximum;
                 
-                // Clear children and metadata to save space
-                ast_node.children.clear();
+                self.clear_children(ast_node);
                 ast_node.metadata.clear();
             }
         }
@@ -3620,14 +4348,15 @@ impl EnhancedCompressionEngine {
 
     /// Apply cluster-based compression for similar patterns
     fn apply_cluster_compression(&self, ast: &mut GammaAST, cluster_patterns: Vec<&Pattern>) -> Result<(), CompressionError> {
-        if cluster_patterns.len() < 2 {
+        // Works by replacing children with the cluster node, so lossy mode only
+        if cluster_patterns.len() < 2 || self.config.preserve_children {
             return Ok(());
         }
 
         // Find the most representative pattern in the cluster
-        let representative = cluster_patterns.iter()
-            .max_by_key(|p| p.size)
-            .unwrap();
+        let Some(representative) = cluster_patterns.iter().max_by_key(|p| p.size) else {
+            return Ok(());
+        };
 
         // Create a cluster reference node
         let cluster_id = self.generate_pattern_id();
@@ -3647,14 +4376,14 @@ impl EnhancedCompressionEngine {
             compression_level: CompressionLevel::Medium,
         };
 
-        ast.nodes.insert(cluster_id, cluster_node);
+        ast.try_add_node(cluster_node)?;
 
         // Replace all cluster patterns with references to the cluster
         for pattern in cluster_patterns {
             for node in &pattern.nodes {
                 if let Some(ast_node) = ast.nodes.get_mut(&node.id) {
                     ast_node.children = vec![cluster_id];
-                    ast_node.compression_level = CompressionLevel::Medium;
+                    ast_node.compression_level = CompressionLevel::Maximum;
                 }
             }
         }
@@ -3687,6 +4416,11 @@ impl EnhancedCompressionEngine {
 
     /// Apply semantic fold compression
     fn apply_semantic_fold_compression(&self, ast: &mut GammaAST, pattern: &SemanticPattern) -> Result<(), CompressionError> {
+        // Works by replacing children with the fold node, so lossy mode only
+        if self.config.preserve_children {
+            return Ok(());
+        }
+        
         // Create a semantic fold node
         let fold_id = self.generate_pattern_id();
         let fold_node = GammaNode {
@@ -3705,7 +4439,7 @@ impl EnhancedCompressionEngine {
             compression_level: CompressionLevel::Maximum,
         };
 
-        ast.nodes.insert(fold_id, fold_node);
+        ast.try_add_node(fold_node)?;
 
         // Replace pattern nodes with fold reference
         for node_id in &pattern.node_ids {
@@ -3744,13 +4478,15 @@ impl EnhancedCompressionEngine {
             GammaValue::Direct(value) => format!("direct_{}", value),
             GammaValue::PatternRef(ref_id) => format!("ref_{}", ref_id),
             GammaValue::CompressedHash(hash) => format!("hash_{}", hash),
+            GammaValue::DictRef { dict_id, entry } => format!("dict_{}_{}", dict_id, entry),
             GammaValue::None => "none".to_string(),
         }
     }
 
     /// Apply cross-reference compression
     fn apply_cross_reference_compression(&self, ast: &mut GammaAST, node_ids: &[u64], ref_key: &str) -> Result<(), CompressionError> {
-        if node_ids.len() < 2 {
+        // Works by replacing children with the cross-reference node, so lossy mode only
+        if node_ids.len() < 2 || self.config.preserve_children {
             return Ok(());
         }
 
@@ -3771,7 +4507,7 @@ impl EnhancedCompressionEngine {
             compression_level: CompressionLevel::Maximum,
         };
 
-        ast.nodes.insert(xref_id, xref_node);
+        ast.try_add_node(xref_node)?;
 
         // Replace duplicate nodes with cross-reference
         for (i, node_id) in node_ids.iter().enumerate() {
@@ -3839,6 +4575,7 @@ impl EnhancedCompressionEngine {
             GammaValue::Direct(value) => value.len() as u8,
             GammaValue::PatternRef(_) => 8,
             GammaValue::CompressedHash(_) => 16,
+            GammaValue::DictRef { .. } => 8,
             GammaValue::None => 1,
         };
 
@@ -3869,7 +4606,7 @@ impl EnhancedCompressionEngine {
             compression_level: CompressionLevel::Maximum,
         };
 
-        ast.nodes.insert(entropy_id, entropy_node);
+        ast.try_add_node(entropy_node)?;
 
                 // Apply entropy optimization to pattern nodes
         // Since we don't have actual nodes in the pattern, we'll skip this for now
@@ -4055,16 +4792,17 @@ impl EnhancedCompressionEngine {
         }
         
         // Apply content deduplication
-        for (content_key, node_ids) in content_map {
+        for (content_key, node_ids) in sorted_groups(content_map) {
             if node_ids.len() > 1 {
-                // Keep first node, replace others with references
+                // Keep the lowest-ID node, replace others with references
                 let reference_node_id = node_ids[0];
                 
                 for &duplicate_id in &node_ids[1..] {
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
                         // Replace duplicate content with reference
+                        duplicate_node.compression_level = Self::dedup_level(&duplicate_node.value);
                         duplicate_node.value = GammaValue::PatternRef(reference_node_id);
-                        duplicate_node.children = vec![];
+                        self.clear_children(duplicate_node);
                         duplicate_node.metadata.clear();
                     }
                 }
@@ -4079,13 +4817,14 @@ impl EnhancedCompressionEngine {
         let mut key_parts = Vec::new();
         
         // Node type
-        key_parts.push(format!("type:{:?}", node.node_type));
+        key_parts.push(format!("type:{}", node.node_type));
         
         // Value content
         match &node.value {
             GammaValue::Direct(value) => key_parts.push(format!("value:{}", value)),
             GammaValue::PatternRef(_) => key_parts.push("value:ref".to_string()),
             GammaValue::CompressedHash(_) => key_parts.push("value:hash".to_string()),
+            GammaValue::DictRef { .. } => key_parts.push("value:dict".to_string()),
             GammaValue::None => key_parts.push("value:none".to_string()),
         }
         
@@ -4128,7 +4867,7 @@ impl EnhancedCompressionEngine {
         
         // Apply real content deduplication
         let mut total_bytes_saved = 0;
-        for (content_bytes, node_ids) in byte_content_map {
+        for (content_bytes, node_ids) in sorted_groups(byte_content_map) {
             if node_ids.len() > 1 {
                 // Calculate actual bytes saved
                 let bytes_per_node = content_bytes.len();
@@ -4142,8 +4881,9 @@ impl EnhancedCompressionEngine {
                     // CRITICAL FIX: Don't remove nodes - replace with pattern references instead
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
                         // Replace duplicate node with pattern reference
+                        duplicate_node.compression_level = Self::dedup_level(&duplicate_node.value);
                         duplicate_node.value = crate::gamma_ast::GammaValue::PatternRef(reference_node_id);
-                        duplicate_node.children.clear(); // Remove children to save space
+                        self.clear_children(duplicate_node);
                         duplicate_node.metadata.clear(); // Remove metadata to save space
                     }
                     
@@ -4168,13 +4908,14 @@ impl EnhancedCompressionEngine {
         let mut bytes = Vec::new();
         
         // Serialize node type
-        bytes.extend_from_slice(format!("{:?}", node.node_type).as_bytes());
+        bytes.extend_from_slice(node.node_type.as_str().as_bytes());
         
         // Serialize value
         match &node.value {
             GammaValue::Direct(value) => bytes.extend_from_slice(value.as_bytes()),
             GammaValue::PatternRef(_) => bytes.extend_from_slice(b"ref"),
             GammaValue::CompressedHash(_) => bytes.extend_from_slice(b"hash"),
+            GammaValue::DictRef { .. } => bytes.extend_from_slice(b"dict"),
             GammaValue::None => bytes.extend_from_slice(b"none"),
         }
         
@@ -4240,7 +4981,7 @@ impl EnhancedCompressionEngine {
             }
             
             // Apply aggressive deduplication
-            for (content_bytes, node_ids) in byte_content_map {
+            for (content_bytes, node_ids) in sorted_groups(byte_content_map) {
                 if node_ids.len() > 1 {
                     let bytes_per_node = content_bytes.len();
                     let bytes_saved = (node_ids.len() - 1) * bytes_per_node;
@@ -4254,8 +4995,9 @@ impl EnhancedCompressionEngine {
                         // This preserves structure while achieving compression
                         if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
                             // Replace duplicate node with pattern reference
+                            duplicate_node.compression_level = Self::dedup_level(&duplicate_node.value);
                             duplicate_node.value = crate::gamma_ast::GammaValue::PatternRef(reference_node_id);
-                            duplicate_node.children.clear(); // Remove children to save space
+                            self.clear_children(duplicate_node);
                             duplicate_node.metadata.clear(); // Remove metadata to save space
                         }
                         
@@ -4320,6 +5062,7 @@ impl EnhancedCompressionEngine {
                 if let crate::gamma_ast::GammaValue::Direct(ref value) = &ast_node.value {
                     if value.len() > 8 { // Only compress strings longer than 8 characters
                         ast_node.value = crate::gamma_ast::GammaValue::PatternRef(pattern.id);
+                        ast_node.compression_level = CompressionLevel::Maximum;
                     }
                 }
             }
@@ -4341,13 +5084,20 @@ impl EnhancedCompressionEngine {
         }
         
         // Apply compression to cross-file patterns that appear multiple times
-        for (signature, node_ids) in cross_file_patterns {
+        for (signature, node_ids) in sorted_groups(cross_file_patterns) {
             if node_ids.len() >= 2 { // Lower threshold for cross-file patterns
                 let reference_id = node_ids[0];
+                let reference_value = ast.nodes[&reference_id].value.clone();
                 
                 // Replace duplicate cross-file patterns with references
                 for &duplicate_id in &node_ids[1..] {
                     if let Some(duplicate_node) = ast.nodes.get_mut(&duplicate_id) {
+                        // Signatures group similar values, so only an identical value can be read back through the reference
+                        duplicate_node.compression_level = if duplicate_node.value == reference_value {
+                            Self::dedup_level(&duplicate_node.value)
+                        } else {
+                            CompressionLevel::Maximum
+                        };
                         // Replace with reference to save space
                         duplicate_node.value = crate::gamma_ast::GammaValue::PatternRef(reference_id);
                         // CRITICAL: Preserve children for structural integrity
@@ -4378,7 +5128,7 @@ impl EnhancedCompressionEngine {
         // Include node type
         match &node.node_type {
             crate::gamma_ast::GammaNodeType::Custom(s) => signature.push_str(s),
-            _ => signature.push_str(&format!("{:?}", node.node_type)),
+            _ => signature.push_str(&node.node_type.as_str()),
         }
         
         // Include value pattern
@@ -5079,8 +5829,16 @@ pub enum CompressionError {
     PatternAnalysisFailed,
     #[error("AI scheduling error: {0}")]
     AISchedulingError(#[from] SchedulerError),
+    #[error("Synthetic node collides with an existing one: {0}")]
+    DuplicateNodeId(#[from] DuplicateIdError),
     #[error("Compression failed: {0}")]
     CompressionFailed(String),
+    #[error("GPU call panicked: {0}")]
+    GPUPanic(String),
+    #[error("Invalid input AST: {0}")]
+    InvalidInput(String),
+    #[error("Reconstruction fidelity test failed: compression is not lossless")]
+    ReconstructionFailed,
 }
 
 #[cfg(test)]
@@ -5099,6 +5857,31 @@ mod tests {
         assert_eq!(engine.config.target_ratio, 8.0);
     }
     
+    #[tokio::test]
+    async fn test_try_compress_rejects_malformed_input() {
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        assert!(engine.try_compress(&GammaAST::new()).await.is_ok());
+        
+        let mut cyclic = GammaAST::new();
+        for (id, children) in [(1, vec![2]), (2, vec![1])] {
+            cyclic.add_node(crate::gamma_ast::GammaNode {
+                id,
+                node_type: GammaNodeType::Block,
+                value: GammaValue::None,
+                location: None,
+                children,
+                metadata: HashMap::new(),
+                compression_level: CompressionLevel::None,
+            });
+        }
+        cyclic.add_root(1);
+        assert!(matches!(engine.try_compress(&cyclic).await, Err(CompressionError::InvalidInput(_))));
+        
+        let mut dangling = GammaAST::new();
+        dangling.add_root(9);
+        assert!(matches!(engine.try_compress(&dangling).await, Err(CompressionError::InvalidInput(_))));
+    }
+    
     #[tokio::test]
     async fn test_compression_config_validation() {
         let config = EnhancedCompressionConfig {
@@ -5110,6 +5893,245 @@ mod tests {
         let engine = EnhancedCompressionEngine::new(config);
         assert_eq!(engine.config.target_ratio, 100.0);
     }
+    
+    #[test]
+    fn test_string_pattern_rules() {
+        let defaults = StringPatternRules::default();
+        assert_eq!(defaults.classify("def main"), "function_declaration");
+        assert_eq!(defaults.classify("xyz"), "tiny_string");
+        
+        let dsl = StringPatternRules::empty().with_class("rule_declaration", &["rule", "when"]);
+        assert_eq!(dsl.classify("when ready"), "rule_declaration");
+        assert_eq!(dsl.classify("define"), "short_string");
+    }
+    
+    #[test]
+    fn test_cluster_order_is_input_independent() {
+        let engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        let pattern = |id: u64, signature: u64, size: usize| Pattern {
+            id,
+            signature,
+            frequency: 1,
+            size,
+            nodes: (0..size as u64).map(|n| crate::gamma_ast::GammaNode {
+                id: id * 100 + n,
+                node_type: GammaNodeType::Call,
+                value: GammaValue::Direct("f".to_string()),
+                location: None,
+                children: vec![],
+                metadata: HashMap::new(),
+                compression_level: CompressionLevel::None,
+            }).collect(),
+            languages: vec![],
+        };
+        let patterns = vec![pattern(1, 9, 3), pattern(2, 4, 3), pattern(3, 4, 10), pattern(4, 1, 4)];
+        let cluster_ids = |patterns: &[Pattern]| -> Vec<Vec<u64>> {
+            engine.cluster_similar_patterns(patterns, &SimilarityConfig::default())
+                .iter()
+                .map(|cluster| cluster.iter().map(|pattern| pattern.id).collect())
+                .collect()
+        };
+        
+        let forward = cluster_ids(&patterns);
+        let mut reversed = patterns.clone();
+        reversed.reverse();
+        assert_eq!(cluster_ids(&reversed), forward);
+        assert_eq!(forward[0][0], 4);
+    }
+    
+    #[test]
+    fn test_restore_pinned_nodes() {
+        let original: GammaAST = (1..=3)
+            .map(|id| crate::gamma_ast::GammaNode::builder(id, GammaNodeType::Variable).direct("leaf").build())
+            .chain(std::iter::once(crate::gamma_ast::GammaNode::builder(4, GammaNodeType::Block).children([1, 2]).build()))
+            .chain(std::iter::once(crate::gamma_ast::GammaNode::builder(5, GammaNodeType::Block).children([4, 3]).build()))
+            .collect();
+        let mut compressed = original.clone();
+        for id in [1, 2, 4] {
+            compressed.nodes.remove(&id);
+        }
+        compressed.nodes.get_mut(&5).unwrap().children.clear();
+        
+        let config = EnhancedCompressionConfig { pinned_nodes: HashSet::from([5]), ..EnhancedCompressionConfig::default() };
+        let engine = EnhancedCompressionEngine::new(config);
+        engine.restore_pinned_nodes(&original, &mut compressed);
+        
+        // The pinned node and the whole subtree it points at are back
+        assert_eq!(compressed.nodes, original.nodes);
+    }
+    
+    #[tokio::test]
+    async fn test_calibrate_gpu_threshold() {
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        assert_eq!(engine.gpu_threshold(), 1000);
+        
+        // Calibration is opt-in: compressing alone keeps the configured threshold
+        let ast: GammaAST = std::iter::once(crate::gamma_ast::GammaNode::builder(1, GammaNodeType::Variable).direct("x").build()).collect();
+        engine.compress_ast(&ast).await.unwrap();
+        assert_eq!(engine.gpu_threshold(), 1000);
+        
+        let threshold = engine.calibrate();
+        assert_eq!(engine.gpu_threshold(), threshold);
+        if !cfg!(feature = "gpu") {
+            // No GPU to win, so nothing is routed there
+            assert_eq!(threshold, usize::MAX);
+        }
+    }
+    
+    #[test]
+    fn test_gpu_panic_falls_back_to_cpu() {
+        let engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        let mut ast: GammaAST = (1..=3)
+            .map(|id| crate::gamma_ast::GammaNode::builder(id, GammaNodeType::Call).direct("print").build())
+            .collect();
+        let pattern = Pattern {
+            id: 50,
+            signature: 7,
+            frequency: 2,
+            size: 1,
+            nodes: vec![ast.nodes[&1].clone(), ast.nodes[&2].clone()],
+            languages: vec![],
+        };
+        
+        let outcome = EnhancedCompressionEngine::catch_gpu_panic(|| -> u32 { panic!("device lost") });
+        let Err(error) = outcome else {
+            panic!("the panic should have been caught");
+        };
+        assert!(matches!(&error, CompressionError::GPUPanic(message) if message == "device lost"));
+        
+        engine.fall_back_to_cpu(&mut ast, &pattern, &error).unwrap();
+        for id in [1, 2] {
+            assert_eq!(ast.nodes[&id].value, GammaValue::PatternRef(50));
+        }
+        assert_eq!(ast.nodes[&3].value, GammaValue::Direct("print".to_string()));
+        // The miss counts against the GPU for this signature
+        let benefit = &engine.gpu_benefit_stats()[&7];
+        assert_eq!((benefit.runs, benefit.helped), (1, 0));
+    }
+    
+    /// Six `counter` variables under one block (id 7), the only root
+    fn counters_ast() -> GammaAST {
+        let mut ast: GammaAST = (1..=6)
+            .map(|id| crate::gamma_ast::GammaNode::builder(id, GammaNodeType::Variable).direct("counter").build())
+            .chain(std::iter::once(crate::gamma_ast::GammaNode::builder(7, GammaNodeType::Block).children(1..=6).build()))
+            .collect();
+        ast.roots = vec![7];
+        ast
+    }
+    
+    #[tokio::test]
+    async fn test_large_scale_verify_only() {
+        let ast = counters_ast();
+        let case = LargeScaleTestCase {
+            name: "counters".to_string(),
+            source: "test".to_string(),
+            ast: ast.clone(),
+            expected_compression: 1000.0, // Unreachable, so only verify-only can pass
+        };
+        
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        let measured = engine.test_large_scale_compression(std::slice::from_ref(&case), true, false).await.unwrap();
+        assert!(!measured.test_cases[0].success);
+        // Value-table references lose the values unless a reconstruction table is kept
+        let lossy = engine.test_large_scale_compression(std::slice::from_ref(&case), true, true).await.unwrap();
+        assert!(!lossy.test_cases[0].success && lossy.test_cases[0].mismatch.is_some());
+        engine.config.reconstructible = true;
+        let verified = engine.test_large_scale_compression(&[case], true, true).await.unwrap();
+        assert!(verified.test_cases[0].success && verified.test_cases[0].mismatch.is_none());
+        
+        let mut broken = ast.clone();
+        broken.nodes.get_mut(&7).unwrap().children.pop();
+        assert_eq!(ast.round_trip_mismatch(&broken, true).as_deref(), Some("node 7 children changed"));
+        broken = ast.clone();
+        broken.nodes.get_mut(&1).unwrap().value = GammaValue::PatternRef(1);
+        assert_eq!(ast.round_trip_mismatch(&broken, true).as_deref(), Some("node 1 value changed"));
+    }
+    
+    #[tokio::test]
+    async fn test_compare_analysis_modes() {
+        let ast = counters_ast();
+        
+        let engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        let comparison = engine.compare_analysis_modes(&ast).await.unwrap();
+        assert!(comparison.neuro_ratio > 0.0 && comparison.basic_ratio > 0.0);
+        assert!(comparison.neuro_time > Duration::ZERO && comparison.basic_time > Duration::ZERO);
+        assert!(engine.compression_history.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_reconstructible_decompress() {
+        let ast = counters_ast();
+        
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig {
+            preserve_children: false,
+            verify_roundtrip: false,
+            reconstructible: true,
+            ..EnhancedCompressionConfig::default()
+        });
+        let (_, compressed) = engine.compress_with_output(&ast).await.unwrap();
+        // Only the value-table entries are recorded, not copies of the rewritten nodes
+        let table = compressed.reconstruction.as_ref().unwrap();
+        assert!(!table.values.is_empty() && table.nodes.is_empty());
+        
+        let restored = compressed.decompress().unwrap();
+        assert_eq!(restored.nodes, ast.nodes);
+        assert_eq!(restored.roots, ast.roots);
+        assert!(ast.round_trip_mismatch(&restored, true).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_lossy_compression_is_undecodable() {
+        let ast = counters_ast();
+        
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig {
+            verify_roundtrip: false,
+            ..EnhancedCompressionConfig::default()
+        });
+        let (_, compressed) = engine.compress_with_output(&ast).await.unwrap();
+        // Without a reconstruction table the value-table references must not pass as decoded
+        assert!(compressed.nodes.values().any(|node| node.compression_level != CompressionLevel::None));
+        assert!(matches!(compressed.decompress(), Err(crate::gamma_ast::DecodeError::Undecodable(ids)) if !ids.is_empty()));
+    }
+    
+    #[test]
+    fn test_config_conversions() {
+        let config = CompressionConfig {
+            max_patterns: 12,
+            preserve_children: false,
+            pinned_nodes: HashSet::from([3]),
+            max_input_nodes: None,
+            ..CompressionConfig::default()
+        };
+        let enhanced = EnhancedCompressionConfig::from(config);
+        assert_eq!((enhanced.max_patterns, enhanced.preserve_children, enhanced.max_input_nodes), (12, false, usize::MAX));
+        assert!(enhanced.enable_neuromorphic);
+        
+        let back = CompressionConfig::from(enhanced);
+        assert_eq!((back.max_patterns, back.pinned_nodes, back.max_input_nodes), (12, HashSet::from([3]), None));
+        let limited = CompressionConfig::from(EnhancedCompressionConfig::default());
+        assert_eq!(limited.max_input_nodes, Some(5_000_000));
+    }
+    
+    #[test]
+    fn test_basic_patterns_skip_one_off_nodes() {
+        let mut ast: GammaAST = [
+            crate::gamma_ast::GammaNode::builder(1, GammaNodeType::Block).children([2, 3, 4]).build(),
+            crate::gamma_ast::GammaNode::builder(2, GammaNodeType::Variable).direct("x").build(),
+            crate::gamma_ast::GammaNode::builder(3, GammaNodeType::Variable).direct("y").build(),
+            crate::gamma_ast::GammaNode::builder(4, GammaNodeType::Literal).direct("42").build(),
+        ].into_iter().collect();
+        ast.roots = vec![1];
+        
+        let mut engine = EnhancedCompressionEngine::new(EnhancedCompressionConfig::default());
+        let patterns = engine.analyze_patterns_basic(&ast).unwrap();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!((patterns[0].frequency, patterns[0].nodes[0].node_type.clone()), (2, GammaNodeType::Variable));
+        
+        engine.config.min_pattern_frequency = 1;
+        assert_eq!(engine.analyze_patterns_basic(&ast).unwrap().len(), 3);
+        engine.config.min_pattern_size = 2;
+        assert!(engine.analyze_patterns_basic(&ast).unwrap().is_empty());
+    }
 }
 
 /// Language bridge integration for cross-language compression
@@ -5221,7 +6243,7 @@ impl LanguageBridge {
         println!("✅ {} code compressed successfully!", language);
         println!("   Compression ratio: {:.1}x", result.compression_ratio);
         println!("   Size reduction: {:.1}%", 
-                 (1.0 - result.compressed_size as f64 / result.original_size as f64) * 100.0);
+                 space_saving_percent(result.original_size, result.compressed_size));
         
         Ok(result)
     }